@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use crate::atn_state::ATNState;
 use crate::atn_state::ATNStateRef;
+use crate::atn_state::ATNStateType;
 use crate::atn_type::ATNType;
 use crate::dfa::ScopeExt;
 use crate::interval_set::IntervalSet;
@@ -100,7 +101,16 @@ impl ATN {
 
     // fn remove_state(&self, _state: ATNStateRef) { unimplemented!() }
 
-    // fn define_decision_state(&self, _s: ATNStateRef) -> isize { unimplemented!() }
+    crate fn define_decision_state(&mut self, s: ATNStateRef) -> isize {
+        let decision = self.decision_to_state.len() as isize;
+        self.decision_to_state.push(s);
+        if let ATNStateType::DecisionState { decision: d, .. } =
+            self.states[s].get_state_type_mut()
+        {
+            *d = decision
+        }
+        decision
+    }
 
     pub fn get_decision_state(&self, decision: usize) -> ATNStateRef {
         self.decision_to_state[decision]
@@ -2,6 +2,7 @@ use std::cmp::max;
 use std::collections::HashMap;
 use std::fmt::{Debug, Error, Formatter};
 use std::hash::{Hash, Hasher};
+use std::mem::size_of;
 use std::ops::Deref;
 
 use bit_set::BitSet;
@@ -199,6 +200,14 @@ impl ATNConfigSet {
 
     pub fn length(&self) -> usize { self.configs.len() }
 
+    pub(crate) fn approximate_size(&self) -> usize {
+        size_of::<ATNConfigSet>()
+            + self.configs.capacity() * size_of::<Box<ATNConfig>>()
+            + self.configs.len() * size_of::<ATNConfig>()
+            + self.config_lookup.capacity() * (size_of::<(Key, usize)>() + 1)
+            + self.conflicting_alts.capacity() / 8
+    }
+
     pub fn is_empty(&self) -> bool { self.configs.is_empty() }
 
     pub fn has_semantic_context(&self) -> bool { self.has_semantic_context }
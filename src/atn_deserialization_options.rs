@@ -7,6 +7,20 @@ pub struct ATNDeserializationOptions {
 
 impl ATNDeserializationOptions {
     pub fn is_verify(&self) -> bool { self.verify_atn }
+
+    /// Sets whether deserialized ATN should be checked for consistency
+    pub fn set_verify(&mut self, verify_atn: bool) { self.verify_atn = verify_atn }
+
+    pub fn is_generate_rule_bypass_transitions(&self) -> bool {
+        self.generate_rule_bypass_transitions
+    }
+
+    /// Sets whether parser ATN should get additional transitions that allow to match
+    /// any rule as a single token of the special type `ATN::rule_to_token_type[rule]`.
+    /// Required for parse tree pattern matching.
+    pub fn set_generate_rule_bypass_transitions(&mut self, generate: bool) {
+        self.generate_rule_bypass_transitions = generate
+    }
 }
 
 impl Default for ATNDeserializationOptions {
@@ -1,3 +1,5 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::str::Chars;
 use std::str::FromStr;
 
@@ -38,7 +40,163 @@ lazy_static! {
     ];
 }
 
-const SERIALIZED_VERSION: isize = 3;
+/// Format used by ANTLR tool before 4.10, values are shifted by 2 and feature set is defined by UUID
+const LEGACY_SERIALIZED_VERSION: isize = 3;
+/// Format used by ANTLR tool since 4.10, plain integer values without UUID
+const SERIALIZED_VERSION: isize = 4;
+
+/// Violation of ATN invariants found by `ATNDeserializer::verify_atn`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ATNVerificationError {
+    /// Number of the state that violates invariant
+    pub state: ATNStateRef,
+    /// Description of the violated invariant
+    pub message: String,
+}
+
+impl Display for ATNVerificationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid ATN state {}: {}", self.state, self.message)
+    }
+}
+
+impl Error for ATNVerificationError {}
+
+/// Error returned by `ATNDeserializer::try_deserialize` if data is not a valid serialized ATN
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ATNDeserializationError {
+    /// ATN was serialized with unsupported version of serialization format
+    UnsupportedVersion(isize),
+    /// ATN was serialized with unknown set of features
+    UnsupportedUuid(String),
+    /// Serialized data ended before whole ATN was read
+    UnexpectedEnd,
+    /// Grammar type is neither lexer nor parser
+    InvalidGrammarType(isize),
+    /// Unknown type of ATN state
+    InvalidStateType(isize),
+    /// Unknown type of transition
+    InvalidTransitionType(isize),
+    /// Unknown type of lexer action
+    InvalidLexerActionType(isize),
+    /// Serialized data references state, rule or set that does not exist
+    /// or has unexpected kind
+    InvalidReference {
+        /// What is being referenced
+        kind: &'static str,
+        /// Index of the referenced element
+        index: isize,
+    },
+    /// Number of states, rules, sets etc. is negative or larger than the rest of the data
+    InvalidCount {
+        /// What is being counted
+        kind: &'static str,
+        /// Count found in the serialized data
+        count: isize,
+    },
+    /// Deserialized ATN failed verification
+    InvalidATN(ATNVerificationError),
+}
+
+impl Display for ATNDeserializationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ATNDeserializationError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported ATN version {} (expected {} or {})",
+                version, LEGACY_SERIALIZED_VERSION, SERIALIZED_VERSION
+            ),
+            ATNDeserializationError::UnsupportedUuid(uuid) => {
+                write!(f, "unsupported ATN UUID {}", uuid)
+            }
+            ATNDeserializationError::UnexpectedEnd => f.write_str("unexpected end of ATN data"),
+            ATNDeserializationError::InvalidGrammarType(t) => {
+                write!(f, "invalid ATN grammar type {}", t)
+            }
+            ATNDeserializationError::InvalidStateType(t) => {
+                write!(f, "invalid ATN state type {}", t)
+            }
+            ATNDeserializationError::InvalidTransitionType(t) => {
+                write!(f, "invalid transition type {}", t)
+            }
+            ATNDeserializationError::InvalidLexerActionType(t) => {
+                write!(f, "invalid lexer action type {}", t)
+            }
+            ATNDeserializationError::InvalidReference { kind, index } => {
+                write!(f, "invalid reference to {} {}", kind, index)
+            }
+            ATNDeserializationError::InvalidCount { kind, count } => {
+                write!(f, "invalid number of {} {}", kind, count)
+            }
+            ATNDeserializationError::InvalidATN(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ATNDeserializationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ATNDeserializationError::InvalidATN(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ATNVerificationError> for ATNDeserializationError {
+    fn from(e: ATNVerificationError) -> Self { ATNDeserializationError::InvalidATN(e) }
+}
+
+/// In legacy format all values except version are shifted by 2
+fn unshift_legacy(data: impl Iterator<Item = isize>) -> impl Iterator<Item = isize> {
+    data.enumerate()
+        .map(|(i, value)| if i == 0 { value } else { value - 2 })
+}
+
+/// Decodes integers encoded as 16-bit words, values that don't fit into 15 bits take two words
+fn decode_words(mut words: impl Iterator<Item = isize>) -> impl Iterator<Item = isize> {
+    std::iter::from_fn(move || {
+        let word = words.next()?;
+        if word & 0x8000 == 0 {
+            return Some(word);
+        }
+        let next = words.next()?;
+        if word == 0xFFFF && next == 0xFFFF {
+            Some(-1)
+        } else {
+            Some((word & 0x7FFF) << 16 | (next & 0xFFFF))
+        }
+    })
+}
+
+fn read(data: &mut dyn Iterator<Item = isize>) -> Result<isize, ATNDeserializationError> {
+    data.next().ok_or(ATNDeserializationError::UnexpectedEnd)
+}
+
+/// Reads number of elements that follow, each element is expected to take at least one value,
+/// so count can't be larger than the number of remaining values
+fn read_count(
+    data: &mut (impl Iterator<Item = isize> + ?Sized), kind: &'static str,
+) -> Result<usize, ATNDeserializationError> {
+    let count = data.next().ok_or(ATNDeserializationError::UnexpectedEnd)?;
+    let remaining = data.size_hint().1.unwrap_or(usize::MAX);
+    if count < 0 || count as usize > remaining {
+        return Err(ATNDeserializationError::InvalidCount { kind, count });
+    }
+    Ok(count as usize)
+}
+
+fn read_state(
+    data: &mut dyn Iterator<Item = isize>, states_count: usize,
+) -> Result<ATNStateRef, ATNDeserializationError> {
+    let state = read(data)?;
+    if state < 0 || state as usize >= states_count {
+        return Err(ATNDeserializationError::InvalidReference {
+            kind: "state",
+            index: state,
+        });
+    }
+    Ok(state as ATNStateRef)
+}
 
 #[derive(Debug)]
 pub struct ATNDeserializer {
@@ -52,125 +210,187 @@ impl ATNDeserializer {
         }
     }
 
+    /// Deserializes ATN created by ANTLR tool
+    ///
+    /// Both legacy format of ANTLR tool before 4.10 and version 4 format of ANTLR 4.10+
+    /// are supported. In version 4 format integers are expected to be encoded as 16-bit words
+    /// the same way as in Java target, use `deserialize_ints` if data is a plain integer list.
+    ///
+    /// ### Panics
+    /// If `data` is not a valid serialized ATN, or if verification is enabled in
+    /// `ATNDeserializationOptions` and deserialized ATN fails `verify_atn`.
+    /// Use `try_deserialize` to handle such errors.
     pub fn deserialize(&self, data: Chars<'_>) -> ATN {
-        let mut data = data.clone().map(|ch| {
-            let mut ch = ch as isize;
-            // decode surrogates
-            ch = if ch > 0xFFFF { ch - 0x3000 } else { ch };
-            ch -= 2;
-            ch
-        });
+        self.try_deserialize(data)
+            .unwrap_or_else(|e| panic!("Could not deserialize ATN: {}", e))
+    }
+
+    /// Deserializes ATN created by ANTLR tool,
+    /// returns error if `data` is not a valid serialized ATN.
+    pub fn try_deserialize(&self, data: Chars<'_>) -> Result<ATN, ATNDeserializationError> {
+        // surrogates are not valid chars so generated code shifts them by 0x3000
+        let mut data = data
+            .map(|ch| {
+                let ch = ch as isize;
+                if ch > 0xFFFF {
+                    ch - 0x3000
+                } else {
+                    ch
+                }
+            })
+            .peekable();
+        if data.peek() == Some(&LEGACY_SERIALIZED_VERSION) {
+            self.deserialize_values(unshift_legacy(data))
+        } else {
+            self.deserialize_values(decode_words(data))
+        }
+    }
 
-        self.check_version(data.next().unwrap() + 2);
+    /// Deserializes ATN stored as plain list of integers, like in `.interp` files
+    /// or in code generated by ANTLR 4.10+ for targets other than Java.
+    /// Both legacy and version 4 formats are supported.
+    ///
+    /// ### Panics
+    /// Same as `deserialize`, use `try_deserialize_ints` to handle errors.
+    pub fn deserialize_ints(&self, data: &[i32]) -> ATN {
+        self.try_deserialize_ints(data)
+            .unwrap_or_else(|e| panic!("Could not deserialize ATN: {}", e))
+    }
+
+    /// Same as `deserialize_ints` but returns error if `data` is not a valid serialized ATN
+    pub fn try_deserialize_ints(&self, data: &[i32]) -> Result<ATN, ATNDeserializationError> {
+        let values = data.iter().map(|&it| it as isize);
+        if data.first().map(|&it| it as isize) == Some(LEGACY_SERIALIZED_VERSION) {
+            self.deserialize_values(unshift_legacy(values))
+        } else {
+            self.deserialize_values(values)
+        }
+    }
 
-        let _uuid = self.check_uuid(&mut data);
+    fn deserialize_values(
+        &self, data: impl Iterator<Item = isize>,
+    ) -> Result<ATN, ATNDeserializationError> {
+        // collected so `read_count` can check counts against the size of the remaining data
+        let values = data.collect::<Vec<_>>();
+        let mut data = values.into_iter();
+        let version = read(&mut data)?;
+        match version {
+            LEGACY_SERIALIZED_VERSION => {
+                let _uuid = self.check_uuid(&mut data)?;
+            }
+            SERIALIZED_VERSION => {}
+            _ => return Err(ATNDeserializationError::UnsupportedVersion(version)),
+        }
 
-        let mut atn = self.read_atn(&mut data);
+        let mut atn = self.read_atn(&mut data)?;
 
-        self.read_states(&mut atn, &mut data);
-        self.read_rules(&mut atn, &mut data);
-        self.read_modes(&mut atn, &mut data);
+        self.read_states(&mut atn, &mut data)?;
+        self.read_rules(&mut atn, &mut data)?;
+        self.read_modes(&mut atn, &mut data)?;
 
-        let mut sets = self.read_sets(&mut atn, &mut data, |data| {
-            data.next().unwrap() as u16 as isize
-        });
+        let sets = if version == LEGACY_SERIALIZED_VERSION {
+            let mut sets =
+                self.read_sets(&mut atn, &mut data, |data| Ok(read(data)? as u16 as isize))?;
 
-        sets.extend(self.read_sets(&mut atn, &mut data, |data| {
-            (data.next().unwrap() & 0xFFFF) | data.next().unwrap() << 16
-        }));
+            sets.extend(self.read_sets(&mut atn, &mut data, |data| {
+                Ok((read(data)? & 0xFFFF) | read(data)? << 16)
+            })?);
+            sets
+        } else {
+            self.read_sets(&mut atn, &mut data, |data| read(data))?
+        };
 
-        self.read_edges(&mut atn, &mut data, &sets);
-        self.read_decisions(&mut atn, &mut data);
+        self.read_edges(&mut atn, &mut data, &sets)?;
+        self.read_decisions(&mut atn, &mut data)?;
         if atn.grammar_type == ATNType::LEXER {
-            self.read_lexer_actions(&mut atn, &mut data);
+            self.read_lexer_actions(&mut atn, &mut data)?;
         }
         self.mark_precedence_decisions(&mut atn, &mut data);
         if self.deserialization_options.is_verify() {
-            self.verify_atn(&mut atn, &mut data);
+            self.verify_atn(&atn)?;
+        }
+        if self
+            .deserialization_options
+            .is_generate_rule_bypass_transitions()
+            && atn.grammar_type == ATNType::PARSER
+        {
+            self.generate_rule_bypass_transitions(&mut atn)?;
+            if self.deserialization_options.is_verify() {
+                self.verify_atn(&atn)?;
+            }
         }
-        //      TODO parser
-        //        if a.deserializationOptions.generateRuleBypassTransitions && atn.grammarType == ATNTypeParser {
-        //            a.generateRuleBypassTransitions(atn)
-        //            a.verifyATN(atn)
-        //        }
 
-        atn
+        Ok(atn)
     }
 
     // fn reset(&self, _data: Vec<u8>) { unimplemented!() }
 
-    fn check_version(&self, version: isize) {
-        if version != self::SERIALIZED_VERSION {
-            panic!(
-                "Could not deserialize ATN with version {} (expected {})",
-                version, SERIALIZED_VERSION
-            );
-        }
-    }
-
-    fn check_uuid(&self, data: &mut dyn Iterator<Item = isize>) -> Uuid {
+    fn check_uuid(
+        &self, data: &mut dyn Iterator<Item = isize>,
+    ) -> Result<Uuid, ATNDeserializationError> {
         //rust uses UTF-8 encoding so we need explicitly convert unicode
         //codepoint numbers to bytes
         let mut bytes = Vec::new();
-        for i in data.take(8) {
-            bytes.write_u16::<LittleEndian>(i as u16).unwrap();
+        for _ in 0..8 {
+            bytes.write_u16::<LittleEndian>(read(data)? as u16).unwrap();
         }
 
         bytes.reverse();
         let uuid = Uuid::from_slice(&bytes).unwrap();
         if !SUPPORTED_UUIDS.contains(&uuid) {
-            panic!("Could not deserialize ATN with UUID {}", uuid)
+            return Err(ATNDeserializationError::UnsupportedUuid(uuid.to_string()));
         }
-        uuid
+        Ok(uuid)
     }
 
-    fn read_atn(&self, data: &mut dyn Iterator<Item = isize>) -> ATN {
-        let atn = ATN::new_atn(
-            match data.next() {
-                Some(0) => ATNType::LEXER,
-                Some(1) => ATNType::PARSER,
-                _ => panic!("invalid ATN type"),
-            },
-            data.next().unwrap(),
-        );
-
-        atn
+    fn read_atn(
+        &self, data: &mut dyn Iterator<Item = isize>,
+    ) -> Result<ATN, ATNDeserializationError> {
+        let grammar_type = match read(data)? {
+            0 => ATNType::LEXER,
+            1 => ATNType::PARSER,
+            other => return Err(ATNDeserializationError::InvalidGrammarType(other)),
+        };
+        Ok(ATN::new_atn(grammar_type, read(data)?))
     }
 
-    fn read_states(&self, atn: &mut ATN, data: &mut dyn Iterator<Item = isize>) {
+    fn read_states(
+        &self, atn: &mut ATN, data: &mut dyn Iterator<Item = isize>,
+    ) -> Result<(), ATNDeserializationError> {
         //        let loop_back_states = Vec::<(BaseATNState,isize)>::new();
         //        let end_states = Vec::<(BaseATNState,isize)>::new();
-        let states_count = data.next().unwrap() as usize;
+        let states_count = read_count(data, "states")?;
         for i in 0..states_count {
-            let state_type = data.next().unwrap();
-            if state_type == ATNSTATE_INVALID_STATE_NUMBER {
-                atn.add_state(self.state_factory(ATNSTATE_INVALID_TYPE, -1, i));
-                panic!("why invalid state serialized?");
+            let state_type = read(data)?;
+            if state_type == ATNSTATE_INVALID_TYPE {
+                // state was removed by optimizations in the ANTLR tool
+                atn.add_state(self.state_factory(ATNSTATE_INVALID_TYPE, -1, i)?);
+                continue;
             }
 
-            let mut rule_index = data.next().unwrap();
+            let mut rule_index = read(data)?;
             if rule_index == 0xFFFF {
                 rule_index = -1;
             }
-            let mut state = self.state_factory(state_type, rule_index, i);
+            let mut state = self.state_factory(state_type, rule_index, i)?;
 
             match state.get_state_type_mut() {
                 ATNStateType::DecisionState {
                     state: ATNDecisionState::BlockStartState { end_state, .. },
                     ..
-                } => *end_state = data.next().unwrap() as ATNStateRef,
+                } => *end_state = read_state(data, states_count)?,
                 ATNStateType::LoopEndState(loop_back) => {
-                    *loop_back = data.next().unwrap() as ATNStateRef
+                    *loop_back = read_state(data, states_count)?
                 }
                 _ => (),
             }
             atn.add_state(state);
         }
 
-        let num_non_greedy = data.next().unwrap();
+        let num_non_greedy = read_count(data, "non-greedy states")?;
         //println!("num_non_greedy {}", num_non_greedy);
         for _ in 0..num_non_greedy {
-            let st = data.next().unwrap() as usize;
+            let st = read_state(data, states_count)?;
             if let ATNStateType::DecisionState { nongreedy: ng, .. } =
                 atn.states[st].get_state_type_mut()
             {
@@ -180,9 +400,9 @@ impl ATNDeserializer {
 
         //if (supportsPrecedencePredicates)
         if true {
-            let num_precedence_states = data.next().unwrap();
+            let num_precedence_states = read_count(data, "precedence states")?;
             for _ in 0..num_precedence_states {
-                let st = data.next().unwrap() as usize;
+                let st = read_state(data, states_count)?;
                 if let ATNStateType::RuleStartState {
                     is_left_recursive: left_rec,
                     ..
@@ -192,20 +412,32 @@ impl ATNDeserializer {
                 }
             }
         }
+        Ok(())
     }
 
-    fn read_rules(&self, atn: &mut ATN, data: &mut dyn Iterator<Item = isize>) {
-        let nrules = data.next().unwrap() as usize;
+    fn read_rules(
+        &self, atn: &mut ATN, data: &mut dyn Iterator<Item = isize>,
+    ) -> Result<(), ATNDeserializationError> {
+        let nrules = read_count(data, "rules")?;
         //        if atn.grammar_type == ATNType::LEXER {
         //            atn.rule_to_token_type.resize(nrules, 0)
         //        }
 
         atn.rule_to_start_state.resize(nrules, 0);
         for i in 0..nrules {
-            let s = data.next().unwrap() as usize;
+            let s = read_state(data, atn.states.len())?;
+            if !matches!(
+                atn.states[s].get_state_type(),
+                ATNStateType::RuleStartState { .. }
+            ) {
+                return Err(ATNDeserializationError::InvalidReference {
+                    kind: "rule start state",
+                    index: s as isize,
+                });
+            }
             atn.rule_to_start_state[i] = s;
             if atn.grammar_type == ATNType::LEXER {
-                let token_type = data.next().unwrap();
+                let token_type = read(data)?;
 
                 atn.rule_to_token_type.push(token_type);
             }
@@ -218,6 +450,12 @@ impl ATNDeserializer {
             let state = atn.states.get(i).unwrap();
             if let ATNStateType::RuleStopState = state.get_state_type() {
                 let rule_index = state.get_rule_index();
+                if rule_index >= nrules {
+                    return Err(ATNDeserializationError::InvalidReference {
+                        kind: "rule",
+                        index: rule_index as isize,
+                    });
+                }
                 atn.rule_to_stop_state[rule_index] = i;
                 let start_state = atn
                     .states
@@ -231,59 +469,59 @@ impl ATNDeserializer {
                 }
             }
         }
+        Ok(())
     }
 
-    fn read_modes(&self, atn: &mut ATN, data: &mut dyn Iterator<Item = isize>) {
-        let nmodes = data.next().unwrap();
+    fn read_modes(
+        &self, atn: &mut ATN, data: &mut dyn Iterator<Item = isize>,
+    ) -> Result<(), ATNDeserializationError> {
+        let nmodes = read_count(data, "modes")?;
         for _i in 0..nmodes {
-            atn.mode_to_start_state.push(data.next().unwrap() as usize);
+            let start_state = read_state(data, atn.states.len())?;
+            atn.mode_to_start_state.push(start_state);
         }
+        Ok(())
     }
 
     fn read_sets<T: Iterator<Item = isize>>(
-        &self,
-        _atn: &mut ATN,
-        data: &mut T,
-        read_unicode: fn(&mut T) -> isize,
-    ) -> Vec<IntervalSet> {
-        let nsets = data.next().unwrap();
+        &self, _atn: &mut ATN, data: &mut T,
+        read_unicode: fn(&mut T) -> Result<isize, ATNDeserializationError>,
+    ) -> Result<Vec<IntervalSet>, ATNDeserializationError> {
+        let nsets = read_count(data, "sets")?;
         let mut sets = Vec::new();
         for _i in 0..nsets {
-            let intervals = data.next().unwrap();
+            let intervals = read_count(data, "intervals")?;
 
             let mut set = IntervalSet::new();
 
             // check if contains eof
-            if data.next().unwrap() != 0 {
+            if read(data)? != 0 {
                 set.add_one(-1)
             }
 
             for _ in 0..intervals {
-                set.add_range(read_unicode(data), read_unicode(data));
+                set.add_range(read_unicode(data)?, read_unicode(data)?);
             }
             sets.push(set);
         }
 
-        sets
+        Ok(sets)
     }
 
     fn read_edges(
-        &self,
-        atn: &mut ATN,
-        data: &mut dyn Iterator<Item = isize>,
-        sets: &Vec<IntervalSet>,
-    ) {
-        let nedges = data.next().unwrap();
+        &self, atn: &mut ATN, data: &mut dyn Iterator<Item = isize>, sets: &Vec<IntervalSet>,
+    ) -> Result<(), ATNDeserializationError> {
+        let nedges = read_count(data, "edges")?;
 
         for _i in 0..nedges {
-            let src = data.next().unwrap() as usize;
-            let trg = data.next().unwrap() as usize;
-            let ttype = data.next().unwrap();
-            let arg1 = data.next().unwrap();
-            let arg2 = data.next().unwrap();
-            let arg3 = data.next().unwrap();
+            let src = read_state(data, atn.states.len())?;
+            let trg = read_state(data, atn.states.len())?;
+            let ttype = read(data)?;
+            let arg1 = read(data)?;
+            let arg2 = read(data)?;
+            let arg3 = read(data)?;
 
-            let transition = self.edge_factory(atn, ttype, src, trg, arg1, arg2, arg3, sets);
+            let transition = self.edge_factory(atn, ttype, src, trg, arg1, arg2, arg3, sets)?;
 
             atn.states.get_mut(src).unwrap().add_transition(transition);
         }
@@ -296,6 +534,12 @@ impl ATNDeserializer {
                         //                        println!("TRANSITION_RULE");
                         let tr = tr.as_ref().cast::<RuleTransition>();
                         let target = atn.states.get(tr.get_target()).unwrap();
+                        if target.get_rule_index() >= atn.rule_to_start_state.len() {
+                            return Err(ATNDeserializationError::InvalidReference {
+                                kind: "rule",
+                                index: target.get_rule_index() as isize,
+                            });
+                        }
 
                         let outermost_prec_return = if let ATNStateType::RuleStartState {
                             is_left_recursive: true,
@@ -332,70 +576,112 @@ impl ATNDeserializer {
             .drain(..)
             .for_each(|(state, tr)| atn.states[state].add_transition(tr));
 
-        for i in 0..atn.states.len() {
-            let atn_state = atn.states.get(i).unwrap();
-            match atn_state.get_state_type() {
+        // link states that reference each other, serialized data has only one side of the link
+        let mut loop_back_links = Vec::new();
+        let mut plus_loop_back_links = Vec::new();
+        let mut block_end_links = Vec::new();
+        for state in &atn.states {
+            match state.get_state_type() {
+                ATNStateType::StarLoopbackState => {
+                    for tr in state.get_transitions() {
+                        loop_back_links.push((tr.get_target(), state.get_state_number()));
+                    }
+                }
                 ATNStateType::DecisionState {
-                    state:
-                        ATNDecisionState::BlockStartState {
-                            end_state: _,
-                            en: _,
-                        },
+                    state: ATNDecisionState::PlusLoopBack,
                     ..
                 } => {
-
-                    //                    if *end_state == 0 { panic!("invalid state")}
-                    // looks like it is never used during recognition
-                    // todo missed part
+                    for tr in state.get_transitions() {
+                        plus_loop_back_links.push((tr.get_target(), state.get_state_number()));
+                    }
                 }
-                //                ATNStateType::DecisionState {state:ATNDecisionState::PlusLoopBack,..} =>{
-                //                    for tr in atn_state.get_transitions(){
-                //                        if let ATNStateType::DecisionState {
-                //                                        state:ATNDecisionState::BlockStartState {
-                //                                            en:ATNBlockStart::PlusBlockStart(loopBack),..},..}
-                //                        = atn.states.get_mut(tr.get_target()).unwrap().get_state_type_mut(){
-                //                            *loopBack = i;
-                //
-                //                        }
-                //                    }
-                //                }
-                _x => { /*println!("{:?}",x);*/ }
+                ATNStateType::DecisionState {
+                    state: ATNDecisionState::BlockStartState { end_state, .. },
+                    ..
+                } => block_end_links.push((*end_state, state.get_state_number())),
+                _ => {}
             }
         }
+        for (entry, loop_back) in loop_back_links {
+            if let ATNStateType::DecisionState {
+                state:
+                    ATNDecisionState::StarLoopEntry {
+                        loop_back_state, ..
+                    },
+                ..
+            } = atn.states[entry].get_state_type_mut()
+            {
+                *loop_back_state = loop_back
+            }
+        }
+        for (block_start, loop_back) in plus_loop_back_links {
+            if let ATNStateType::DecisionState {
+                state:
+                    ATNDecisionState::BlockStartState {
+                        en: ATNBlockStart::PlusBlockStart(loop_back_state),
+                        ..
+                    },
+                ..
+            } = atn.states[block_start].get_state_type_mut()
+            {
+                *loop_back_state = loop_back
+            }
+        }
+        for (block_end, block_start) in block_end_links {
+            if let Some(ATNStateType::BlockEndState(start_state)) = atn
+                .states
+                .get_mut(block_end)
+                .map(|it| it.get_state_type_mut())
+            {
+                *start_state = block_start
+            }
+        }
+        Ok(())
     }
 
-    fn read_decisions(&self, atn: &mut ATN, _data: &mut dyn Iterator<Item = isize>) {
-        let ndecisions = _data.next().unwrap();
+    fn read_decisions(
+        &self, atn: &mut ATN, _data: &mut dyn Iterator<Item = isize>,
+    ) -> Result<(), ATNDeserializationError> {
+        let ndecisions = read_count(_data, "decisions")?;
         for i in 0..ndecisions {
-            let s = _data.next().unwrap() as usize;
+            let s = read_state(_data, atn.states.len())?;
             let dec_state: &mut Box<dyn ATNState> = atn.states.get_mut(s).unwrap();
             atn.decision_to_state.push(s);
             if let ATNStateType::DecisionState { decision, .. } = dec_state.get_state_type_mut() {
-                *decision = i
+                *decision = i as isize
+            } else {
+                return Err(ATNDeserializationError::InvalidReference {
+                    kind: "decision state",
+                    index: s as isize,
+                });
             }
         }
+        Ok(())
     }
 
-    fn read_lexer_actions(&self, atn: &mut ATN, _data: &mut dyn Iterator<Item = isize>) {
+    fn read_lexer_actions(
+        &self, atn: &mut ATN, _data: &mut dyn Iterator<Item = isize>,
+    ) -> Result<(), ATNDeserializationError> {
         //lexer actions are always supported here
-        let nactions = _data.next().unwrap() as usize;
+        let nactions = read_count(_data, "lexer actions")?;
 
         for _i in 0..nactions {
-            let action_type = _data.next().unwrap();
+            let action_type = read(_data)?;
 
-            let mut data1 = _data.next().unwrap();
+            let mut data1 = read(_data)?;
             if data1 == 0xFFFF {
                 data1 = -1;
             }
-            let mut data2 = _data.next().unwrap();
+            let mut data2 = read(_data)?;
             if data2 == 0xFFFF {
                 data2 = -1;
             }
 
-            let lexer_action = self.lexer_action_factory(action_type, data1, data2);
+            let lexer_action = self.lexer_action_factory(action_type, data1, data2)?;
 
             atn.lexer_actions.push(lexer_action);
         }
+        Ok(())
     }
 
     fn mark_precedence_decisions(&self, _atn: &mut ATN, _data: &mut dyn Iterator<Item = isize>) {
@@ -406,14 +692,18 @@ impl ATNDeserializer {
                 ..
             } = state.get_state_type()
             {
-                if let ATNStateType::RuleStartState {
+                if let Some(ATNStateType::RuleStartState {
                     is_left_recursive: true,
                     ..
-                } =
-                    _atn.states[_atn.rule_to_start_state[state.get_rule_index()]].get_state_type()
+                }) = _atn
+                    .rule_to_start_state
+                    .get(state.get_rule_index())
+                    .map(|&it| _atn.states[it].get_state_type())
                 {
-                    let maybe_loop_end =
-                        state.get_transitions().iter().last().unwrap().get_target();
+                    let maybe_loop_end = match state.get_transitions().last() {
+                        Some(tr) => tr.get_target(),
+                        None => continue,
+                    };
                     let maybe_loop_end = _atn.states[maybe_loop_end].as_ref();
                     if let ATNStateType::LoopEndState(_) = maybe_loop_end.get_state_type() {
                         if maybe_loop_end.has_epsilon_only_transitions() {
@@ -443,11 +733,319 @@ impl ATNDeserializer {
         }
     }
 
-    fn verify_atn(&self, _atn: &mut ATN, _data: &mut dyn Iterator<Item = isize>) {
-        //TODO
+    fn generate_rule_bypass_transitions(
+        &self, atn: &mut ATN,
+    ) -> Result<(), ATNDeserializationError> {
+        let nrules = atn.rule_to_start_state.len();
+        atn.rule_to_token_type = (0..nrules as isize)
+            .map(|i| atn.max_token_type + i + 1)
+            .collect();
+
+        for i in 0..nrules {
+            let bypass_start = atn.states.len();
+            let bypass_stop = bypass_start + 1;
+            let mut start_state =
+                self.state_factory(ATNSTATE_BLOCK_START, i as isize, bypass_start)?;
+            if let ATNStateType::DecisionState {
+                state: ATNDecisionState::BlockStartState { end_state, .. },
+                ..
+            } = start_state.get_state_type_mut()
+            {
+                *end_state = bypass_stop
+            }
+            atn.add_state(start_state);
+            let mut stop_state = self.state_factory(ATNSTATE_BLOCK_END, i as isize, bypass_stop)?;
+            *stop_state.get_state_type_mut() = ATNStateType::BlockEndState(bypass_start);
+            atn.add_state(stop_state);
+            atn.define_decision_state(bypass_start);
+
+            let rule_start = atn.rule_to_start_state[i];
+            // (state, transition index) of the transition that must keep its target
+            let mut exclude_transition = None;
+            let end_state = if let ATNStateType::RuleStartState {
+                is_left_recursive: true,
+                ..
+            } = atn.states[rule_start].get_state_type()
+            {
+                // wrap from the beginning of the rule to the StarLoopEntryState
+                let end_state = atn
+                    .states
+                    .iter()
+                    .filter(|state| state.get_rule_index() == i)
+                    .find(|state| {
+                        matches!(
+                            state.get_state_type(),
+                            ATNStateType::DecisionState {
+                                state: ATNDecisionState::StarLoopEntry {
+                                    is_precedence: true,
+                                    ..
+                                },
+                                ..
+                            }
+                        )
+                    })
+                    .ok_or_else(|| ATNVerificationError {
+                        state: rule_start,
+                        message: "couldn't identify final state of the precedence rule \
+                                  prefix section"
+                            .to_owned(),
+                    })?
+                    .get_state_number();
+                if let ATNStateType::DecisionState {
+                    state:
+                        ATNDecisionState::StarLoopEntry {
+                            loop_back_state, ..
+                        },
+                    ..
+                } = atn.states[end_state].get_state_type()
+                {
+                    exclude_transition = Some((*loop_back_state, 0));
+                }
+                end_state
+            } else {
+                atn.rule_to_stop_state[i]
+            };
+
+            // all non-excluded transitions that currently target end state need to target blockEnd instead
+            for state in atn.states.iter_mut() {
+                let state_number = state.get_state_number();
+                for (index, tr) in state.get_transitions_mut().iter_mut().enumerate() {
+                    if exclude_transition != Some((state_number, index))
+                        && tr.get_target() == end_state
+                    {
+                        tr.set_target(bypass_stop)
+                    }
+                }
+            }
+
+            // all transitions leaving the rule start state need to leave blockStart instead
+            let transitions = std::mem::take(atn.states[rule_start].get_transitions_mut());
+            for tr in transitions {
+                atn.states[bypass_start].add_transition(tr);
+            }
+
+            // link the new states
+            atn.states[rule_start].add_transition(Box::new(EpsilonTransition {
+                target: bypass_start,
+                outermost_precedence_return: 0,
+            }));
+            atn.states[bypass_stop].add_transition(Box::new(EpsilonTransition {
+                target: end_state,
+                outermost_precedence_return: 0,
+            }));
+
+            let match_state = atn.states.len();
+            atn.add_state(self.state_factory(ATNSTATE_BASIC, i as isize, match_state)?);
+            atn.states[match_state].add_transition(Box::new(AtomTransition {
+                target: bypass_stop,
+                label: atn.rule_to_token_type[i],
+            }));
+            atn.states[bypass_start].add_transition(Box::new(EpsilonTransition {
+                target: match_state,
+                outermost_precedence_return: 0,
+            }));
+        }
+        Ok(())
     }
 
-    // fn check_condition(&self, _condition: bool, _message: String) { unimplemented!() }
+    /// Checks structural invariants of the `atn` that ATN simulators rely on.
+    ///
+    /// Performs the same checks as Java runtime: pairing of block start and end states,
+    /// consistency of loop entry, loop back and loop end states, number of transitions
+    /// of decision and non-decision states, and precedence decision flags.
+    /// Returns the first violation found.
+    pub fn verify_atn(&self, atn: &ATN) -> Result<(), ATNVerificationError> {
+        let state_type = |state: ATNStateRef| atn.states.get(state).map(|it| it.get_state_type());
+        for state in &atn.states {
+            let number = state.get_state_number();
+            let check = |condition: bool, message: &str| {
+                if condition {
+                    Ok(())
+                } else {
+                    Err(ATNVerificationError {
+                        state: number,
+                        message: message.to_owned(),
+                    })
+                }
+            };
+            let transitions = state.get_transitions();
+            for tr in transitions {
+                check(
+                    tr.get_target() < atn.states.len(),
+                    "transition target does not exist",
+                )?;
+                if tr.get_serialization_type() == TransitionType::TRANSITION_RULE {
+                    let tr = tr.as_ref().cast::<RuleTransition>();
+                    check(
+                        tr.follow_state < atn.states.len(),
+                        "rule transition follow state does not exist",
+                    )?;
+                }
+            }
+            let target_type = |index: usize| state_type(transitions[index].get_target());
+
+            check(
+                state.has_epsilon_only_transitions() || transitions.len() <= 1,
+                "state with non-epsilon transitions must have only one transition",
+            )?;
+
+            match state.get_state_type() {
+                ATNStateType::DecisionState { decision, .. } => check(
+                    transitions.len() <= 1 || *decision >= 0,
+                    "decision state with multiple transitions must have a decision number",
+                )?,
+                ATNStateType::RuleStopState => {}
+                _ => check(
+                    transitions.len() <= 1,
+                    "non-decision state must have at most one transition",
+                )?,
+            }
+
+            match state.get_state_type() {
+                ATNStateType::RuleStartState { stop_state, .. } => check(
+                    matches!(state_type(*stop_state), Some(ATNStateType::RuleStopState)),
+                    "rule start state must have a rule stop state",
+                )?,
+                ATNStateType::BlockEndState(start_state) => check(
+                    matches!(
+                        state_type(*start_state),
+                        Some(ATNStateType::DecisionState {
+                            state: ATNDecisionState::BlockStartState { end_state, .. },
+                            ..
+                        }) if *end_state == number
+                    ),
+                    "block end state must be paired with a block start state",
+                )?,
+                ATNStateType::LoopEndState(loop_back) => check(
+                    matches!(
+                        state_type(*loop_back),
+                        Some(ATNStateType::StarLoopbackState)
+                            | Some(ATNStateType::DecisionState {
+                                state: ATNDecisionState::PlusLoopBack,
+                                ..
+                            })
+                    ),
+                    "loop end state must have a loop back state",
+                )?,
+                ATNStateType::StarLoopbackState => {
+                    check(
+                        transitions.len() == 1,
+                        "star loop back state must have exactly one transition",
+                    )?;
+                    check(
+                        matches!(
+                            target_type(0),
+                            Some(ATNStateType::DecisionState {
+                                state: ATNDecisionState::StarLoopEntry { .. },
+                                ..
+                            })
+                        ),
+                        "star loop back state must lead to a star loop entry state",
+                    )?;
+                }
+                ATNStateType::DecisionState {
+                    state: ATNDecisionState::BlockStartState { end_state, en },
+                    ..
+                } => {
+                    check(
+                        matches!(
+                            state_type(*end_state),
+                            Some(ATNStateType::BlockEndState(start)) if *start == number
+                        ),
+                        "block start state must be paired with a block end state",
+                    )?;
+                    if let ATNBlockStart::PlusBlockStart(loop_back) = en {
+                        check(
+                            matches!(
+                                state_type(*loop_back),
+                                Some(ATNStateType::DecisionState {
+                                    state: ATNDecisionState::PlusLoopBack,
+                                    ..
+                                })
+                            ),
+                            "plus block start state must have a plus loop back state",
+                        )?;
+                    }
+                }
+                ATNStateType::DecisionState {
+                    nongreedy,
+                    state:
+                        ATNDecisionState::StarLoopEntry {
+                            loop_back_state,
+                            is_precedence,
+                        },
+                    ..
+                } => {
+                    check(
+                        matches!(
+                            state_type(*loop_back_state),
+                            Some(ATNStateType::StarLoopbackState)
+                        ),
+                        "star loop entry state must have a star loop back state",
+                    )?;
+                    check(
+                        transitions.len() == 2,
+                        "star loop entry state must have exactly two transitions",
+                    )?;
+                    let is_star_block_start = |it: Option<&ATNStateType>| {
+                        matches!(
+                            it,
+                            Some(ATNStateType::DecisionState {
+                                state: ATNDecisionState::BlockStartState {
+                                    en: ATNBlockStart::StarBlockStart,
+                                    ..
+                                },
+                                ..
+                            })
+                        )
+                    };
+                    let is_loop_end = |it: Option<&ATNStateType>| {
+                        matches!(it, Some(ATNStateType::LoopEndState(_)))
+                    };
+                    if is_star_block_start(target_type(0)) {
+                        check(
+                            is_loop_end(target_type(1)),
+                            "greedy star loop entry state must lead to a loop end state",
+                        )?;
+                        check(
+                            !*nongreedy,
+                            "star loop entry state that enters loop first must be greedy",
+                        )?;
+                    } else if is_loop_end(target_type(0)) {
+                        check(
+                            is_star_block_start(target_type(1)),
+                            "non-greedy star loop entry state must lead to a star block start state",
+                        )?;
+                        check(
+                            *nongreedy,
+                            "star loop entry state that exits loop first must be non-greedy",
+                        )?;
+                    } else {
+                        check(
+                            false,
+                            "star loop entry state must lead to star block start and loop end states",
+                        )?;
+                    }
+                    if *is_precedence {
+                        check(
+                            matches!(
+                                atn.rule_to_start_state
+                                    .get(state.get_rule_index())
+                                    .and_then(|&it| state_type(it)),
+                                Some(ATNStateType::RuleStartState {
+                                    is_left_recursive: true,
+                                    ..
+                                })
+                            ),
+                            "precedence decision must be in a left recursive rule",
+                        )?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
 
     fn edge_factory(
         &self,
@@ -459,7 +1057,7 @@ impl ATNDeserializer {
         arg2: isize,
         arg3: isize,
         sets: &Vec<IntervalSet>,
-    ) -> Box<dyn Transition> {
+    ) -> Result<Box<dyn Transition>, ATNDeserializationError> {
         //        //        let target = atn.states.get
         //        let mut base = BaseTransition {
         //            target: trg,
@@ -468,7 +1066,12 @@ impl ATNDeserializer {
         //            interval_set: IntervalSet::new_interval_set(),
         //        };
 
-        match type_index {
+        let set = |index: isize| {
+            sets.get(index as usize)
+                .cloned()
+                .ok_or(ATNDeserializationError::InvalidReference { kind: "set", index })
+        };
+        let transition: Box<dyn Transition> = match type_index {
             TRANSITION_EPSILON => Box::new(EpsilonTransition {
                 target,
                 outermost_precedence_return: 0,
@@ -484,6 +1087,12 @@ impl ATNDeserializer {
             }),
             TRANSITION_RULE => {
                 //                base.set_target(arg1 as usize);
+                if arg1 < 0 || arg1 as usize >= _atn.states.len() {
+                    return Err(ATNDeserializationError::InvalidReference {
+                        kind: "state",
+                        index: arg1,
+                    });
+                }
                 Box::new(RuleTransition {
                     target: arg1 as usize,
                     follow_state: target,
@@ -510,19 +1119,20 @@ impl ATNDeserializer {
             }),
             TRANSITION_SET => Box::new(SetTransition {
                 target,
-                set: sets[arg1 as usize].clone(),
+                set: set(arg1)?,
             }),
             TRANSITION_NOTSET => Box::new(NotSetTransition {
                 target,
-                set: sets[arg1 as usize].clone(),
+                set: set(arg1)?,
             }),
             TRANSITION_WILDCARD => Box::new(WildcardTransition { target }),
             TRANSITION_PRECEDENCE => Box::new(PrecedencePredicateTransition {
                 target,
                 precedence: arg1,
             }),
-            _ => panic!("invalid transition type"),
-        }
+            _ => return Err(ATNDeserializationError::InvalidTransitionType(type_index)),
+        };
+        Ok(transition)
     }
 
     fn state_factory(
@@ -530,7 +1140,7 @@ impl ATNDeserializer {
         type_index: isize,
         rule_index: isize,
         state_number: usize,
-    ) -> Box<dyn ATNState> {
+    ) -> Result<Box<dyn ATNState>, ATNDeserializationError> {
         let mut state = BaseATNState::new_base_atnstate();
         state.state_number = state_number;
         state.rule_index = rule_index as usize;
@@ -589,14 +1199,16 @@ impl ATNDeserializer {
             },
             ATNSTATE_LOOP_END => ATNStateType::LoopEndState(0),
 
-            _ => panic!("invalid ATN state type"),
+            _ => return Err(ATNDeserializationError::InvalidStateType(type_index)),
         };
         //        println!("created state {} {:?}", state_number, state.state_type);
-        Box::new(state)
+        Ok(Box::new(state))
     }
 
-    fn lexer_action_factory(&self, action_type: isize, data1: isize, data2: isize) -> LexerAction {
-        match action_type {
+    fn lexer_action_factory(
+        &self, action_type: isize, data1: isize, data2: isize,
+    ) -> Result<LexerAction, ATNDeserializationError> {
+        let action = match action_type {
             LEXER_ACTION_TYPE_CHANNEL => LexerChannelAction(data1),
             LEXER_ACTION_TYPE_CUSTOM => LexerCustomAction {
                 rule_index: data1,
@@ -608,7 +1220,8 @@ impl ATNDeserializer {
             LEXER_ACTION_TYPE_PUSH_MODE => LexerPushModeAction(data1),
             LEXER_ACTION_TYPE_SKIP => LexerSkipAction,
             LEXER_ACTION_TYPE_TYPE => LexerTypeAction(data1),
-            _ => panic!("invalid action type {}", action_type),
-        }
+            _ => return Err(ATNDeserializationError::InvalidLexerActionType(action_type)),
+        };
+        Ok(action)
     }
 }
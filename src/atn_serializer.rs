@@ -0,0 +1,276 @@
+//! Serialization of the ATN
+//!
+//! Produces ATN in the version 4 format of ANTLR 4.10+,
+//! so result can be loaded back with `ATNDeserializer`.
+//! ```text
+//! let data = ATNSerializer::new(&atn).serialize();
+//! let atn = ATNDeserializer::new(None).deserialize_ints(&data);
+//! ```
+use crate::atn::ATN;
+use crate::atn_state::{ATNDecisionState, ATNStateType, ATNSTATE_INVALID_TYPE};
+use crate::atn_type::ATNType;
+use crate::interval_set::IntervalSet;
+use crate::lexer_action::LexerAction::{self, *};
+use crate::lexer_action::*;
+use crate::token::TOKEN_EOF;
+use crate::transition::*;
+
+const SERIALIZED_VERSION: i32 = 4;
+
+/// Converts `ATN` into the serialized form accepted by `ATNDeserializer`
+#[derive(Debug)]
+pub struct ATNSerializer<'a> {
+    atn: &'a ATN,
+}
+
+impl<'a> ATNSerializer<'a> {
+    /// Creates serializer for `atn`
+    pub fn new(atn: &'a ATN) -> Self { Self { atn } }
+
+    /// Serializes ATN as a list of integers, which can be deserialized with
+    /// `ATNDeserializer::deserialize_ints`.
+    ///
+    /// Transitions that lead from rule stop states back to the invoking rules
+    /// are not serialized because they are recreated by `ATNDeserializer`.
+    pub fn serialize(&self) -> Vec<i32> {
+        let atn = self.atn;
+        let mut data = vec![SERIALIZED_VERSION];
+        data.push(match atn.grammar_type {
+            ATNType::LEXER => 0,
+            ATNType::PARSER => 1,
+        });
+        data.push(atn.max_token_type as i32);
+
+        self.serialize_states(&mut data);
+
+        data.push(atn.rule_to_start_state.len() as i32);
+        for (rule, &start_state) in atn.rule_to_start_state.iter().enumerate() {
+            data.push(start_state as i32);
+            if atn.grammar_type == ATNType::LEXER {
+                data.push(atn.rule_to_token_type[rule] as i32);
+            }
+        }
+
+        data.push(atn.mode_to_start_state.len() as i32);
+        data.extend(atn.mode_to_start_state.iter().map(|&it| it as i32));
+
+        let sets = self.collect_sets();
+        data.push(sets.len() as i32);
+        for set in &sets {
+            serialize_set(&mut data, set);
+        }
+
+        self.serialize_edges(&mut data, &sets);
+
+        data.push(atn.decision_to_state.len() as i32);
+        data.extend(atn.decision_to_state.iter().map(|&it| it as i32));
+
+        if atn.grammar_type == ATNType::LEXER {
+            data.push(atn.lexer_actions.len() as i32);
+            for action in &atn.lexer_actions {
+                data.extend_from_slice(&serialize_lexer_action(action));
+            }
+        }
+        data
+    }
+
+    /// Serializes ATN as a string, which can be deserialized with `ATNDeserializer::deserialize`.
+    ///
+    /// Integers are encoded as 16-bit words same way as in Java target,
+    /// words in the surrogate range are shifted by 0x3000 same way as in generated Rust code.
+    pub fn serialize_to_string(&self) -> String {
+        let mut words = Vec::new();
+        for value in self.serialize() {
+            if value == -1 {
+                words.push(0xFFFF);
+                words.push(0xFFFF);
+            } else if value <= 0x7FFF {
+                words.push(value as u32);
+            } else {
+                words.push((value as u32 >> 16) | 0x8000);
+                words.push(value as u32 & 0xFFFF);
+            }
+        }
+        words
+            .into_iter()
+            .map(|word| {
+                let word = if (0xD800..=0xDFFF).contains(&word) {
+                    word + 0x3000
+                } else {
+                    word
+                };
+                std::char::from_u32(word).unwrap()
+            })
+            .collect()
+    }
+
+    fn serialize_states(&self, data: &mut Vec<i32>) {
+        let mut non_greedy_states = Vec::new();
+        let mut precedence_states = Vec::new();
+        data.push(self.atn.states.len() as i32);
+        for state in &self.atn.states {
+            let state_type = state.get_state_type_id();
+            data.push(state_type as i32);
+            if state_type == ATNSTATE_INVALID_TYPE {
+                continue;
+            }
+            data.push(state.get_rule_index() as i32);
+            match state.get_state_type() {
+                ATNStateType::DecisionState {
+                    nongreedy,
+                    state: decision_state,
+                    ..
+                } => {
+                    if *nongreedy {
+                        non_greedy_states.push(state.get_state_number() as i32);
+                    }
+                    if let ATNDecisionState::BlockStartState { end_state, .. } = decision_state {
+                        data.push(*end_state as i32);
+                    }
+                }
+                ATNStateType::LoopEndState(loop_back) => data.push(*loop_back as i32),
+                ATNStateType::RuleStartState {
+                    is_left_recursive: true,
+                    ..
+                } => precedence_states.push(state.get_state_number() as i32),
+                _ => {}
+            }
+        }
+
+        data.push(non_greedy_states.len() as i32);
+        data.extend(non_greedy_states);
+        data.push(precedence_states.len() as i32);
+        data.extend(precedence_states);
+    }
+
+    fn transitions(&self) -> impl Iterator<Item = (usize, &dyn Transition)> + '_ {
+        self.atn
+            .states
+            .iter()
+            .filter(|state| *state.get_state_type() != ATNStateType::RuleStopState)
+            .flat_map(|state| {
+                state
+                    .get_transitions()
+                    .iter()
+                    .map(move |tr| (state.get_state_number(), tr.as_ref()))
+            })
+    }
+
+    fn collect_sets(&self) -> Vec<IntervalSet> {
+        let mut sets = Vec::<IntervalSet>::new();
+        for (_, tr) in self.transitions() {
+            match tr.get_serialization_type() {
+                TransitionType::TRANSITION_SET | TransitionType::TRANSITION_NOTSET => {
+                    let set = tr.get_label().unwrap();
+                    if !sets.contains(&set) {
+                        sets.push(set.into_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+        sets
+    }
+
+    fn serialize_edges(&self, data: &mut Vec<i32>, sets: &[IntervalSet]) {
+        let edges = self.transitions().collect::<Vec<_>>();
+        data.push(edges.len() as i32);
+        for (src, tr) in edges {
+            let mut target = tr.get_target() as i32;
+            let (mut arg1, mut arg2, mut arg3) = (0, 0, 0);
+            let eof_flag = |value: isize| {
+                if value == TOKEN_EOF {
+                    (0, 1)
+                } else {
+                    (value as i32, 0)
+                }
+            };
+            let edge_type = tr.get_serialization_type();
+            match edge_type {
+                TransitionType::TRANSITION_EPSILON | TransitionType::TRANSITION_WILDCARD => {}
+                TransitionType::TRANSITION_RANGE => {
+                    let tr = tr.cast::<RangeTransition>();
+                    let (start, eof) = eof_flag(tr.start);
+                    arg1 = start;
+                    arg2 = tr.stop as i32;
+                    arg3 = eof;
+                }
+                TransitionType::TRANSITION_RULE => {
+                    let tr = tr.cast::<RuleTransition>();
+                    target = tr.follow_state as i32;
+                    arg1 = tr.target as i32;
+                    arg2 = tr.rule_index as i32;
+                    arg3 = tr.precedence as i32;
+                }
+                TransitionType::TRANSITION_PREDICATE => {
+                    let tr = tr.cast::<PredicateTransition>();
+                    arg1 = tr.rule_index as i32;
+                    arg2 = tr.pred_index as i32;
+                    arg3 = tr.is_ctx_dependent as i32;
+                }
+                TransitionType::TRANSITION_ATOM => {
+                    let tr = tr.cast::<AtomTransition>();
+                    let (label, eof) = eof_flag(tr.label);
+                    arg1 = label;
+                    arg3 = eof;
+                }
+                TransitionType::TRANSITION_ACTION => {
+                    let tr = tr.cast::<ActionTransition>();
+                    arg1 = tr.rule_index as i32;
+                    arg2 = tr.action_index as i32;
+                    arg3 = tr.is_ctx_dependent as i32;
+                }
+                TransitionType::TRANSITION_SET | TransitionType::TRANSITION_NOTSET => {
+                    let set = tr.get_label().unwrap();
+                    arg1 = sets.iter().position(|it| *it == *set).unwrap() as i32;
+                }
+                TransitionType::TRANSITION_PRECEDENCE => {
+                    arg1 = tr.cast::<PrecedencePredicateTransition>().precedence as i32;
+                }
+            }
+            data.extend_from_slice(&[src as i32, target, edge_type as i32, arg1, arg2, arg3]);
+        }
+    }
+}
+
+fn serialize_set(data: &mut Vec<i32>, set: &IntervalSet) {
+    let intervals = set.get_intervals();
+    let contains_eof = set.contains(TOKEN_EOF);
+    // EOF is serialized as a flag, so interval that contains only EOF is skipped
+    if contains_eof && intervals[0].b == TOKEN_EOF {
+        data.push(intervals.len() as i32 - 1);
+    } else {
+        data.push(intervals.len() as i32);
+    }
+    data.push(contains_eof as i32);
+    for interval in intervals {
+        if interval.a == TOKEN_EOF {
+            if interval.b == TOKEN_EOF {
+                continue;
+            }
+            data.push(0);
+        } else {
+            data.push(interval.a as i32);
+        }
+        data.push(interval.b as i32);
+    }
+}
+
+fn serialize_lexer_action(action: &LexerAction) -> [i32; 3] {
+    let (action_type, data1, data2) = match action {
+        LexerChannelAction(channel) => (LEXER_ACTION_TYPE_CHANNEL, *channel, 0),
+        LexerCustomAction {
+            rule_index,
+            action_index,
+        } => (LEXER_ACTION_TYPE_CUSTOM, *rule_index, *action_index),
+        LexerModeAction(mode) => (LEXER_ACTION_TYPE_MODE, *mode, 0),
+        LexerMoreAction => (LEXER_ACTION_TYPE_MORE, 0, 0),
+        LexerPopModeAction => (LEXER_ACTION_TYPE_POP_MODE, 0, 0),
+        LexerPushModeAction(mode) => (LEXER_ACTION_TYPE_PUSH_MODE, *mode, 0),
+        LexerSkipAction => (LEXER_ACTION_TYPE_SKIP, 0, 0),
+        LexerTypeAction(token_type) => (LEXER_ACTION_TYPE_TYPE, *token_type, 0),
+        // position is tracked only during lexing, so it is not a part of the ATN
+        LexerIndexedCustomAction { action, .. } => return serialize_lexer_action(action),
+    };
+    [action_type as i32, data1 as i32, data2 as i32]
+}
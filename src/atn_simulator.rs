@@ -1,22 +1,82 @@
+use std::cell::Cell;
 use std::fmt::{Debug, Error, Formatter};
+use std::io::{self, Read, Write};
 use std::ops::Deref;
 use std::sync::Arc;
 
 use crate::atn::ATN;
 use crate::dfa::DFA;
+use crate::dfa_cache;
 use crate::prediction_context::PredictionContextCache;
 use parking_lot::RwLock;
 
+/// Current size of the caches used by `IATNSimulator`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CacheSize {
+    /// Number of states in DFA of all decisions
+    pub dfa_states: usize,
+    /// Number of prediction contexts in the shared context cache
+    pub prediction_contexts: usize,
+    /// Approximate number of bytes used by DFA states and cached prediction contexts
+    pub approximate_bytes: usize,
+}
+
 pub trait IATNSimulator {
     fn shared_context_cache(&self) -> &PredictionContextCache;
     fn atn(&self) -> &ATN;
     fn decision_to_dfa(&self) -> &Vec<RwLock<DFA>>;
+
+    /// Saves DFA built so far by all recognizers of this grammar,
+    /// see `dfa_cache` module for details
+    fn save_dfa(&self, writer: &mut dyn Write) -> io::Result<()> {
+        dfa_cache::save(self.atn(), self.decision_to_dfa(), writer)
+    }
+
+    /// Replaces DFA of this grammar with the one saved by `save_dfa`.
+    ///
+    /// Returns `false` and leaves DFA untouched if it has been saved for a different ATN.
+    fn load_dfa(&self, reader: &mut dyn Read) -> io::Result<bool> {
+        dfa_cache::load(self.atn(), self.decision_to_dfa(), reader)
+    }
+
+    /// Returns current size of the DFA and the shared prediction context cache.
+    ///
+    /// Both are shared by all recognizers of this grammar and only grow during parsing,
+    /// so size has to be controlled for long running processes that see arbitrary input.
+    fn get_cache_size(&self) -> CacheSize {
+        let mut size = CacheSize::default();
+        for dfa in self.decision_to_dfa() {
+            let dfa = dfa.read();
+            // first state is a placeholder for the null reference
+            size.dfa_states += dfa.states.len() - 1;
+            size.approximate_bytes += dfa.states[1..]
+                .iter()
+                .map(|it| it.approximate_size())
+                .sum::<usize>();
+        }
+        size.prediction_contexts = self.shared_context_cache().length();
+        size.approximate_bytes += self.shared_context_cache().approximate_size();
+        size
+    }
+
+    /// Removes all DFA states built so far and all cached prediction contexts
+    /// for all recognizers of this grammar.
+    ///
+    /// Subsequent parsing becomes slower until DFA is built again.
+    fn clear_dfa(&self) {
+        for dfa in self.decision_to_dfa() {
+            dfa.write().clear();
+        }
+        self.shared_context_cache().clear();
+    }
 }
 
 pub struct BaseATNSimulator {
     pub atn: Arc<ATN>,
     pub shared_context_cache: Arc<PredictionContextCache>,
     pub decision_to_dfa: Arc<Vec<RwLock<DFA>>>,
+    dfa_state_limit: Cell<Option<usize>>,
+    dfa_grown: Cell<bool>,
 }
 
 impl Debug for BaseATNSimulator {
@@ -35,6 +95,33 @@ impl BaseATNSimulator {
             atn,
             shared_context_cache,
             decision_to_dfa,
+            dfa_state_limit: Cell::new(None),
+            dfa_grown: Cell::new(false),
+        }
+    }
+
+    pub(crate) fn set_dfa_state_limit(&self, limit: Option<usize>) {
+        self.dfa_state_limit.set(limit);
+    }
+
+    /// Has to be called whenever this simulator adds a new DFA state
+    pub(crate) fn dfa_grown(&self) { self.dfa_grown.set(true) }
+
+    /// Clears caches if DFA has grown beyond the limit,
+    /// has to be called before prediction while no DFA lock is held
+    pub(crate) fn check_dfa_state_limit(&self) {
+        if let Some(limit) = self.dfa_state_limit.get() {
+            if !self.dfa_grown.replace(false) {
+                return;
+            }
+            let states = self
+                .decision_to_dfa
+                .iter()
+                .map(|it| it.read().states.len() - 1)
+                .sum::<usize>();
+            if states > limit {
+                self.clear_dfa();
+            }
         }
     }
 }
@@ -85,6 +85,7 @@ pub trait ATNState: Sync + Send + Debug {
     fn set_state_number(&self, state_number: isize);
 
     fn get_transitions(&self) -> &Vec<Box<dyn Transition>>;
+    fn get_transitions_mut(&mut self) -> &mut Vec<Box<dyn Transition>>;
     fn set_transitions(&self, t: Vec<Box<dyn Transition>>);
     fn add_transition(&mut self, trans: Box<dyn Transition>);
 }
@@ -143,6 +144,8 @@ impl ATNState for BaseATNState {
 
     fn get_transitions(&self) -> &Vec<Box<dyn Transition>> { &self.transitions }
 
+    fn get_transitions_mut(&mut self) -> &mut Vec<Box<dyn Transition>> { &mut self.transitions }
+
     fn set_transitions(&self, _t: Vec<Box<dyn Transition>>) { unimplemented!() }
 
     fn add_transition(&mut self, trans: Box<dyn Transition>) {
@@ -0,0 +1,359 @@
+//! Equivalent of ANTLR's `TestRig` (`grun`) that works with interpreter data
+//! instead of generated code.
+//!
+//! ```text
+//! grun <grammar> <startRuleName> [-tokens] [-tree] [-trace] [-diagnostics] [-SLL]
+//!      [-encoding encodingname] [input-filename(s)]
+//! ```
+//! `<grammar>` is a path to the `.interp` files generated by ANTLR tool without extension,
+//! e.g. for `gen/CSV` lexer is loaded from `gen/CSVLexer.interp` and parser is loaded
+//! from `gen/CSVParser.interp` or `gen/CSV.interp`.
+//! Use `tokens` as a start rule name to run only lexer. For pure lexer grammars,
+//! which don't have `Lexer` suffix, lexer is loaded from `gen/CSV.interp`.
+//! If no input files are given, input is read from stdin.
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+
+use antlr_rust::common_token_stream::CommonTokenStream;
+use antlr_rust::error_listener::DiagnosticErrorListener;
+use antlr_rust::int_stream::IntStream;
+use antlr_rust::interpreter_data::InterpreterData;
+use antlr_rust::lexer_interpreter::LexerInterpreter;
+use antlr_rust::parser::Parser;
+use antlr_rust::recognizer::Recognizer;
+use antlr_rust::token::TOKEN_EOF;
+use antlr_rust::token_stream::TokenStream;
+use antlr_rust::trace_listener::TraceListener;
+use antlr_rust::utf8_char_stream::Utf8CharStream;
+use antlr_rust::PredictionMode;
+
+const USAGE: &str =
+    "TestRig equivalent that runs grammars from ANTLR interpreter data (.interp files)
+Usage: grun <grammar> <startRuleName> [-tokens] [-tree] [-trace] [-diagnostics] [-SLL]
+            [-encoding encodingname] [input-filename(s)]
+Use startRuleName='tokens' if <grammar> is a lexer grammar.
+Omitting input-filename makes rig read from stdin.";
+
+#[derive(Debug, Default)]
+struct Options {
+    grammar: String,
+    start_rule: String,
+    show_tokens: bool,
+    show_tree: bool,
+    trace: bool,
+    diagnostics: bool,
+    sll: bool,
+    encoding: Option<String>,
+    input_files: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-tokens" => options.show_tokens = true,
+            "-tree" => options.show_tree = true,
+            "-trace" => options.trace = true,
+            "-diagnostics" => options.diagnostics = true,
+            "-SLL" => options.sll = true,
+            "-encoding" => {
+                let encoding = args.next().ok_or("missing encoding on -encoding")?;
+                options.encoding = Some(encoding);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() < 2 {
+        return Err(USAGE.to_owned());
+    }
+    let mut positional = positional.into_iter();
+    options.grammar = positional.next().unwrap();
+    options.start_rule = positional.next().unwrap();
+    options.input_files = positional.collect();
+    Ok(options)
+}
+
+fn decode(data: Vec<u8>, encoding: Option<&str>) -> Result<String, String> {
+    let utf16 = |big_endian: bool| {
+        let units = data
+            .chunks(2)
+            .map(|it| match (it, big_endian) {
+                ([a, b], false) => Ok(u16::from_le_bytes([*a, *b])),
+                ([a, b], true) => Ok(u16::from_be_bytes([*a, *b])),
+                _ => Err("odd number of bytes in UTF-16 input".to_owned()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        String::from_utf16(&units).map_err(|e| e.to_string())
+    };
+    match encoding.map(str::to_ascii_lowercase).as_deref() {
+        None | Some("utf-8") | Some("utf8") => String::from_utf8(data).map_err(|e| e.to_string()),
+        Some("utf-16le") | Some("utf-16") => utf16(false),
+        Some("utf-16be") => utf16(true),
+        Some("iso-8859-1") | Some("latin1") => Ok(data.into_iter().map(char::from).collect()),
+        Some("us-ascii") | Some("ascii") => {
+            if data.is_ascii() {
+                Ok(data.into_iter().map(char::from).collect())
+            } else {
+                Err("input is not a valid ASCII".to_owned())
+            }
+        }
+        Some(other) => Err(format!("unsupported encoding {}", other)),
+    }
+}
+
+fn read_interp(path: &Path) -> Result<InterpreterData, String> {
+    InterpreterData::read(path).map_err(|e| format!("can't load {}: {}", path.display(), e))
+}
+
+#[derive(Debug)]
+struct Grammar {
+    name: String,
+    lexer_name: String,
+    lexer: InterpreterData,
+    parser: Option<InterpreterData>,
+}
+
+fn load_grammar(options: &Options) -> Result<Grammar, String> {
+    let base = Path::new(&options.grammar);
+    let name = base
+        .file_name()
+        .map(|it| it.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("invalid grammar path {}", options.grammar))?;
+    let sibling = |suffix: &str| base.with_file_name(format!("{}{}.interp", name, suffix));
+
+    // lexer of the combined grammar has `Lexer` suffix, pure lexer grammar doesn't
+    let (lexer_name, lexer_path) = match Some(sibling("Lexer")).filter(|it| it.exists()) {
+        Some(path) => (format!("{}Lexer", name), path),
+        None => (name.clone(), sibling("")),
+    };
+    let lexer = read_interp(&lexer_path)?;
+    if options.start_rule == "tokens" {
+        return Ok(Grammar {
+            name,
+            lexer_name,
+            lexer,
+            parser: None,
+        });
+    }
+    let parser_path = Some(sibling("Parser"))
+        .filter(|it| it.exists())
+        .unwrap_or_else(|| sibling(""));
+    let parser = read_interp(&parser_path)?;
+    if parser.mode_names.is_some() {
+        return Err(format!("Can't load {} as parser", parser_path.display()));
+    }
+    if !parser.rule_names.contains(&options.start_rule) {
+        return Err(format!("Can't find start rule {}", options.start_rule));
+    }
+    Ok(Grammar {
+        name,
+        lexer_name,
+        lexer,
+        parser: Some(parser),
+    })
+}
+
+fn process(
+    grammar: &Grammar, options: &Options, input: Utf8CharStream<'_>, out: &mut impl Write,
+) -> io::Result<()> {
+    let lexer: LexerInterpreter<'_, _> = grammar
+        .lexer
+        .create_lexer(&format!("{}.g4", grammar.lexer_name), input);
+    let mut tokens = CommonTokenStream::new(lexer);
+
+    if options.show_tokens {
+        while tokens.la(1) != TOKEN_EOF {
+            tokens.consume();
+        }
+        for i in 0..tokens.size() {
+            writeln!(out, "{}", tokens.get(i))?;
+        }
+        tokens.reset();
+    }
+
+    let parser_data = match &grammar.parser {
+        Some(parser_data) => parser_data,
+        None => return Ok(()),
+    };
+    let mut parser = parser_data.create_parser(&format!("{}.g4", grammar.name), tokens);
+    if options.diagnostics {
+        parser.add_error_listener(Box::new(DiagnosticErrorListener::new(false)));
+        parser
+            .get_interpreter()
+            .set_prediction_mode(PredictionMode::LL_EXACT_AMBIG_DETECTION);
+    }
+    if options.sll {
+        parser
+            .get_interpreter()
+            .set_prediction_mode(PredictionMode::SLL);
+    }
+    if options.trace {
+        let listener = TraceListener::new(&*parser);
+        parser.add_parse_listener(Box::new(listener));
+    }
+
+    let start_rule = parser
+        .get_rule_names()
+        .iter()
+        .position(|&it| it == options.start_rule)
+        .unwrap();
+    match parser.parse(start_rule) {
+        Ok(tree) if options.show_tree => writeln!(out, "{}", tree.to_string_tree(&*parser))?,
+        Ok(_) => {}
+        Err(e) => eprintln!("{}", e),
+    }
+    Ok(())
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args(env::args().skip(1))?;
+    let grammar = load_grammar(&options)?;
+    let encoding = options.encoding.as_deref();
+    let mut out = io::stdout();
+    if options.input_files.is_empty() {
+        let mut data = Vec::new();
+        io::stdin()
+            .read_to_end(&mut data)
+            .map_err(|e| e.to_string())?;
+        let input = decode(data, encoding)?;
+        process(&grammar, &options, Utf8CharStream::new(&input), &mut out)
+            .map_err(|e| e.to_string())?;
+    }
+    for file in &options.input_files {
+        let data = fs::read(file).map_err(|e| format!("can't read {}: {}", file, e))?;
+        let input = decode(data, encoding).map_err(|e| format!("can't read {}: {}", file, e))?;
+        // source name makes file name appear in error messages
+        let input = Utf8CharStream::new(&input).with_source_name(file.clone());
+        process(&grammar, &options, input, &mut out).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args<'a>(args: &'a [&str]) -> impl Iterator<Item = String> + 'a {
+        args.iter().map(|&it| it.to_owned())
+    }
+
+    fn run_grun(arguments: &[&str], input: &str) -> String {
+        let mut options = parse_args(args(arguments)).unwrap();
+        options.grammar = format!(
+            "{}/tests/interp/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            options.grammar
+        );
+        let grammar = load_grammar(&options).unwrap();
+        let mut out = Vec::new();
+        process(&grammar, &options, Utf8CharStream::new(input), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(args(&[
+            "CSV",
+            "csvFile",
+            "-tree",
+            "-SLL",
+            "-encoding",
+            "utf-16",
+            "a.csv",
+        ]))
+        .unwrap();
+        assert_eq!(options.grammar, "CSV");
+        assert_eq!(options.start_rule, "csvFile");
+        assert!(options.show_tree && options.sll);
+        assert!(!options.show_tokens && !options.trace && !options.diagnostics);
+        assert_eq!(options.encoding.as_deref(), Some("utf-16"));
+        assert_eq!(options.input_files, vec!["a.csv"]);
+
+        assert_eq!(parse_args(args(&["CSV"])).unwrap_err(), USAGE);
+        assert_eq!(
+            parse_args(args(&["CSV", "tokens", "-gui"])).unwrap_err(),
+            "unknown option -gui"
+        );
+        assert_eq!(
+            parse_args(args(&["CSV", "tokens", "-encoding"])).unwrap_err(),
+            "missing encoding on -encoding"
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let text = "aé\u{1F600}";
+        assert_eq!(decode(text.as_bytes().to_vec(), None).unwrap(), text);
+        let utf16 = text.encode_utf16().collect::<Vec<_>>();
+        let le = utf16
+            .iter()
+            .flat_map(|it| it.to_le_bytes().to_vec())
+            .collect();
+        assert_eq!(decode(le, Some("UTF-16LE")).unwrap(), text);
+        let be = utf16
+            .iter()
+            .flat_map(|it| it.to_be_bytes().to_vec())
+            .collect();
+        assert_eq!(decode(be, Some("utf-16be")).unwrap(), text);
+        assert_eq!(decode(vec![b'a', 0xE9], Some("latin1")).unwrap(), "aé");
+        assert!(decode(vec![b'a', 0xE9], Some("ascii")).is_err());
+        assert!(decode(vec![b'a'], Some("utf-16")).is_err());
+        assert!(decode(vec![0xE9], None).is_err());
+        assert_eq!(
+            decode(vec![], Some("koi8-r")).unwrap_err(),
+            "unsupported encoding koi8-r"
+        );
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = run_grun(&["CSV", "tokens", "-tokens"], "é,\"b\"\n");
+        // indexes are in code points, same as in Java
+        assert_eq!(
+            tokens,
+            "[@0,0:0='é',<5>,1:0]\n\
+             [@1,1:1=',',<1>,1:1]\n\
+             [@2,2:4='\"b\"',<6>,1:2]\n\
+             [@3,5:5='\\n',<3>,1:5]\n\
+             [@4,6:5='<EOF>',<-1>,2:0]\n"
+        );
+        // pure lexer grammar is loaded from `CSVLexer.interp`
+        assert_eq!(
+            run_grun(&["CSVLexer", "tokens", "-tokens"], "é,\"b\"\n"),
+            tokens
+        );
+    }
+
+    #[test]
+    fn test_parse_tree() {
+        assert_eq!(
+            run_grun(&["CSV", "csvFile", "-tree"], "a,b\nc,d\n"),
+            "(csvFile (hdr (row (field a) , (field b) \\n)) (row (field c) , (field d) \\n))\n"
+        );
+        assert_eq!(run_grun(&["CSV", "row"], "a,b\n"), "");
+
+        let mut options = parse_args(args(&["CSVLexer", "row"])).unwrap();
+        options.grammar = format!("{}/tests/interp/CSVLexer", env!("CARGO_MANIFEST_DIR"));
+        assert!(load_grammar(&options)
+            .unwrap_err()
+            .starts_with("Can't load"));
+        options.grammar = format!("{}/tests/interp/CSV", env!("CARGO_MANIFEST_DIR"));
+        options.start_rule = "value".to_owned();
+        assert_eq!(
+            load_grammar(&options).unwrap_err(),
+            "Can't find start rule value"
+        );
+    }
+}
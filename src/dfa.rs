@@ -78,20 +78,35 @@ impl DFA {
         } = atn.states[atn_start_state].get_state_type()
         {
             dfa.is_precedence_dfa = true;
-            let mut precedence_state = DFAState::new_dfastate(
-                dfa.states.len(),
-                Box::new(ATNConfigSet::new_base_atnconfig_set(true)),
-            );
-            precedence_state.edges = vec![];
-            precedence_state.is_accept_state = false;
-            precedence_state.requires_full_context = false;
-
-            dfa.s0 = Some(precedence_state.state_number);
-            dfa.states.push(precedence_state)
+            dfa.add_precedence_state();
         }
         dfa
     }
 
+    fn add_precedence_state(&mut self) {
+        let mut precedence_state = DFAState::new_dfastate(
+            self.states.len(),
+            Box::new(ATNConfigSet::new_base_atnconfig_set(true)),
+        );
+        precedence_state.edges = vec![];
+        precedence_state.is_accept_state = false;
+        precedence_state.requires_full_context = false;
+
+        self.s0 = Some(precedence_state.state_number);
+        self.states.push(precedence_state)
+    }
+
+    /// Removes all states, so DFA becomes the same as just created one
+    pub fn clear(&mut self) {
+        self.states.truncate(1);
+        self.states.shrink_to_fit();
+        self.states_map = Default::default();
+        self.s0 = None;
+        if self.is_precedence_dfa {
+            self.add_precedence_state();
+        }
+    }
+
     pub fn get_precedence_start_state(&self, _precedence: isize) -> Option<DFAStateRef> {
         if !self.is_precedence_dfa {
             panic!("dfa is supposed to be precedence here");
@@ -151,12 +166,25 @@ impl DFA {
         if self.s0.is_none() {
             return String::new();
         }
-        format!(
-            "{}",
-            DFASerializer::new(self, &|x| format!(
-                "'{}'",
-                char::try_from(x as u32).unwrap()
-            ))
-        )
+        format!("{}", DFASerializer::new(self, &lexer_edge_label))
+    }
+
+    /// Graphviz DOT representation of this DFA, edges are labeled with
+    /// display names of the tokens from `vocabulary`
+    pub fn to_dot(&self, vocabulary: &dyn Vocabulary) -> String {
+        DFASerializer::new(self, &|x| {
+            vocabulary.get_display_name(x as isize - 1).into_owned()
+        })
+        .to_dot()
+    }
+
+    /// Graphviz DOT representation of this DFA if it is a lexer DFA
+    pub fn to_lexer_dot(&self) -> String { DFASerializer::new(self, &lexer_edge_label).to_dot() }
+}
+
+fn lexer_edge_label(x: usize) -> String {
+    match char::try_from(x as u32) {
+        Ok(ch) => format!("'{}'", ch),
+        Err(_) => x.to_string(),
     }
 }
@@ -0,0 +1,588 @@
+//! Persistent cache of the prediction DFA
+//!
+//! Parser and lexer build their DFA lazily while processing input, so every new process
+//! starts with an empty DFA and the first inputs are processed noticeably slower.
+//! DFA accumulated so far can be saved and loaded back by the next process:
+//! ```text
+//! // before exit
+//! parser.get_interpreter().save_dfa(&mut File::create("parser.dfa")?)?;
+//! // on startup, before parsing
+//! if let Ok(mut file) = File::open("parser.dfa") {
+//!     parser.get_interpreter().load_dfa(&mut file)?;
+//! }
+//! ```
+//! Lexer DFA is saved and loaded the same way via `lexer.get_interpreter()`.
+//! DFA is shared between all recognizers of the same grammar, so it is enough to load it
+//! once per process.
+//!
+//! Saved data is keyed by the hash of the serialized ATN, so if the grammar has been changed
+//! `load_dfa` returns `false` and leaves DFA untouched.
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use murmur3::murmur3_32::MurmurHasher;
+use parking_lot::RwLock;
+
+use crate::atn::ATN;
+use crate::atn_config::{ATNConfig, ATNConfigType};
+use crate::atn_config_set::ATNConfigSet;
+use crate::atn_serializer::ATNSerializer;
+use crate::atn_type::ATNType;
+use crate::dfa::DFA;
+use crate::dfa_state::{DFAState, DFAStateRef, PredPrediction};
+use crate::lexer_action::LexerAction;
+use crate::lexer_action_executor::LexerActionExecutor;
+use crate::lexer_atn_simulator::{ERROR_DFA_STATE_REF, MAX_DFA_EDGE, MIN_DFA_EDGE};
+use crate::prediction_context::{
+    PredictionContext, EMPTY_PREDICTION_CONTEXT, PREDICTION_CONTEXT_EMPTY_RETURN_STATE,
+};
+use crate::semantic_context::SemanticContext;
+use crate::transition::{RuleTransition, TransitionType};
+
+const MAGIC: &[u8] = b"ANTLRDFA";
+/// Has to be changed with every change of the layout, so older files are ignored
+/// instead of failing to load. Version 2 adds edges for code points above `MAX_DFA_EDGE`.
+const FORMAT_VERSION: i32 = 2;
+
+fn atn_hash(atn: &ATN) -> i32 {
+    let mut hasher = MurmurHasher::default();
+    for value in ATNSerializer::new(atn).serialize() {
+        hasher.write_i32(value);
+    }
+    hasher.finish() as i32
+}
+
+fn invalid_data(msg: String) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg) }
+
+fn check_index(value: i32, len: usize, what: &str) -> io::Result<usize> {
+    if value < 0 || value as usize >= len {
+        return Err(invalid_data(format!("Invalid {} index {}", what, value)));
+    }
+    Ok(value as usize)
+}
+
+/// Upper bound for the length of `DFAState::edges`, same as the simulators resize them to
+fn max_edges(atn: &ATN) -> usize {
+    match atn.grammar_type {
+        ATNType::LEXER => (MAX_DFA_EDGE - MIN_DFA_EDGE + 1) as usize,
+        // edges are indexed by token type + 1 to make room for EOF
+        ATNType::PARSER => atn.max_token_type as usize + 2,
+    }
+}
+
+/// Upper bound for the length of precedence DFA start state edges, which are indexed by
+/// the precedence a left-recursive rule is invoked with
+fn max_precedence_edges(atn: &ATN) -> usize {
+    atn.states
+        .iter()
+        .flat_map(|state| state.get_transitions())
+        .filter(|it| it.get_serialization_type() == TransitionType::TRANSITION_RULE)
+        .map(|it| it.cast::<RuleTransition>().precedence.max(0) as usize)
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+pub(crate) fn save(
+    atn: &ATN, decision_to_dfa: &[RwLock<DFA>], writer: &mut dyn Write,
+) -> io::Result<()> {
+    // locks are held until the end so prediction contexts can be identified by address
+    let dfas = decision_to_dfa
+        .iter()
+        .map(|it| it.read())
+        .collect::<Vec<_>>();
+    let mut encoder = Encoder {
+        atn,
+        contexts: Vec::new(),
+        context_ids: HashMap::new(),
+        data: Vec::new(),
+    };
+    for dfa in &dfas {
+        encoder.dfa(dfa)?;
+    }
+
+    let header = [
+        FORMAT_VERSION,
+        atn_hash(atn),
+        dfas.len() as i32,
+        encoder.context_ids.len() as i32,
+    ];
+    let mut bytes = MAGIC.to_vec();
+    for &value in header.iter().chain(&encoder.contexts).chain(&encoder.data) {
+        bytes.write_i32::<LittleEndian>(value)?;
+    }
+    writer.write_all(&bytes)
+}
+
+pub(crate) fn load(
+    atn: &ATN, decision_to_dfa: &[RwLock<DFA>], reader: &mut dyn Read,
+) -> io::Result<bool> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(MAGIC) {
+        return Err(invalid_data("Not a DFA cache".to_owned()));
+    }
+    let mut decoder = Decoder {
+        atn,
+        data: &bytes[MAGIC.len()..],
+        contexts: Vec::new(),
+        max_edges: max_edges(atn),
+        max_precedence_edges: max_precedence_edges(atn),
+    };
+    if decoder.next()? != FORMAT_VERSION || decoder.next()? != atn_hash(atn) {
+        return Ok(false);
+    }
+    let decisions = decoder.len()?;
+    if decisions != decision_to_dfa.len() {
+        return Err(invalid_data(format!(
+            "DFA cache has {} decisions, but ATN has {}",
+            decisions,
+            decision_to_dfa.len()
+        )));
+    }
+    for _ in 0..decoder.len()? {
+        let context = decoder.context()?;
+        decoder.contexts.push(context);
+    }
+    let dfas = decision_to_dfa
+        .iter()
+        .map(|dfa| decoder.dfa(dfa.read().is_precedence_dfa()))
+        .collect::<io::Result<Vec<_>>>()?;
+    if !decoder.data.is_empty() {
+        return Err(invalid_data(
+            "Unexpected data after the last DFA".to_owned(),
+        ));
+    }
+
+    for (dfa, (s0, states)) in decision_to_dfa.iter().zip(dfas) {
+        let mut dfa = dfa.write();
+        dfa.states.truncate(1);
+        dfa.states_map.clear();
+        for state in states {
+            // precedence DFA start state holds start states for each precedence
+            // and is never looked up by its configurations
+            if !dfa.is_precedence_dfa() || Some(state.state_number) != s0 {
+                dfa.states_map
+                    .entry(state.default_hash())
+                    .or_insert_with(Vec::new)
+                    .push(state.state_number);
+            }
+            dfa.states.push(state);
+        }
+        dfa.s0 = s0;
+    }
+    Ok(true)
+}
+
+struct Encoder<'a> {
+    atn: &'a ATN,
+    /// Prediction contexts of all configurations, parents go before children
+    contexts: Vec<i32>,
+    context_ids: HashMap<*const PredictionContext, i32>,
+    data: Vec<i32>,
+}
+
+impl Encoder<'_> {
+    fn dfa(&mut self, dfa: &DFA) -> io::Result<()> {
+        self.data.push(dfa.s0.map_or(-1, |it| it as i32));
+        self.data.push(dfa.states.len() as i32);
+        // first state is a placeholder for the null reference
+        for state in &dfa.states[1..] {
+            self.state(state)?;
+        }
+        Ok(())
+    }
+
+    fn state(&mut self, state: &DFAState) -> io::Result<()> {
+        self.config_set(&state.configs)?;
+
+        let edges = state
+            .edges
+            .iter()
+            .enumerate()
+            .filter(|(_, &target)| target != 0)
+            .collect::<Vec<_>>();
+        self.data.push(state.edges.len() as i32);
+        self.data.push(edges.len() as i32);
+        for (symbol, &target) in edges {
+            // ERROR_DFA_STATE_REF becomes -1
+            self.data.extend_from_slice(&[symbol as i32, target as i32]);
+        }
+        self.data.push(state.unicode_edges.len() as i32);
+        for (&symbol, &target) in &state.unicode_edges {
+            self.data.extend_from_slice(&[symbol as i32, target as i32]);
+        }
+
+        self.data.extend_from_slice(&[
+            state.is_accept_state as i32,
+            state.prediction as i32,
+            state.requires_full_context as i32,
+        ]);
+        self.executor(state.lexer_action_executor.as_deref())?;
+        self.data.push(state.predicates.len() as i32);
+        for pred in &state.predicates {
+            self.data.push(pred.alt as i32);
+            self.semantic_context(&pred.pred);
+        }
+        Ok(())
+    }
+
+    fn config_set(&mut self, configs: &ATNConfigSet) -> io::Result<()> {
+        self.data.extend_from_slice(&[
+            configs.full_context() as i32,
+            configs.read_only() as i32,
+            configs.has_semantic_context() as i32,
+            configs.get_dips_into_outer_context() as i32,
+            configs.get_unique_alt() as i32,
+            configs.conflicting_alts.len() as i32,
+        ]);
+        self.data
+            .extend(configs.conflicting_alts.iter().map(|it| it as i32));
+        self.data.push(configs.length() as i32);
+        for config in configs.get_items() {
+            self.config(config)?;
+        }
+        Ok(())
+    }
+
+    fn config(&mut self, config: &ATNConfig) -> io::Result<()> {
+        let context = config.get_context().map_or(-1, |it| self.context(it));
+        self.data.extend_from_slice(&[
+            config.get_state() as i32,
+            config.get_alt() as i32,
+            context,
+            config.get_reaches_into_outer_context() as i32,
+            config.is_precedence_filter_suppressed() as i32,
+        ]);
+        self.semantic_context(&config.semantic_context);
+        match config.get_type() {
+            ATNConfigType::BaseATNConfig => self.data.push(0),
+            ATNConfigType::LexerATNConfig {
+                lexer_action_executor,
+                passed_through_non_greedy_decision,
+            } => {
+                self.data
+                    .extend_from_slice(&[1, *passed_through_non_greedy_decision as i32]);
+                self.executor(lexer_action_executor.as_deref())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn context(&mut self, context: &Arc<PredictionContext>) -> i32 {
+        if let Some(&id) = self.context_ids.get(&Arc::as_ptr(context)) {
+            return id;
+        }
+        let parents = (0..context.length())
+            .map(|i| context.get_parent(i).map_or(-1, |it| self.context(it)))
+            .collect::<Vec<_>>();
+        self.contexts.push(match **context {
+            PredictionContext::Singleton(_) => 0,
+            PredictionContext::Array(_) => 1,
+        });
+        self.contexts.push(parents.len() as i32);
+        for (i, parent) in parents.into_iter().enumerate() {
+            self.contexts
+                .extend_from_slice(&[context.get_return_state(i) as i32, parent]);
+        }
+        let id = self.context_ids.len() as i32;
+        self.context_ids.insert(Arc::as_ptr(context), id);
+        id
+    }
+
+    fn semantic_context(&mut self, semctx: &SemanticContext) {
+        let (kind, operands) = match semctx {
+            SemanticContext::Predicate {
+                rule_index,
+                pred_index,
+                is_ctx_dependent,
+            } => {
+                self.data.extend_from_slice(&[
+                    0,
+                    *rule_index as i32,
+                    *pred_index as i32,
+                    *is_ctx_dependent as i32,
+                ]);
+                return;
+            }
+            SemanticContext::Precedence(precedence) => {
+                self.data.extend_from_slice(&[1, *precedence as i32]);
+                return;
+            }
+            SemanticContext::AND(operands) => (2, operands),
+            SemanticContext::OR(operands) => (3, operands),
+        };
+        self.data.extend_from_slice(&[kind, operands.len() as i32]);
+        for operand in operands {
+            self.semantic_context(operand);
+        }
+    }
+
+    /// Lexer actions are saved as indexes into `ATN::lexer_actions`
+    fn executor(&mut self, executor: Option<&LexerActionExecutor>) -> io::Result<()> {
+        let actions = match executor {
+            None => {
+                self.data.push(-1);
+                return Ok(());
+            }
+            Some(executor) => executor.get_lexer_actions(),
+        };
+        self.data.push(actions.len() as i32);
+        for action in actions {
+            let (offset, action) = match action {
+                LexerAction::LexerIndexedCustomAction { offset, action } => {
+                    (*offset as i32, action.as_ref())
+                }
+                _ => (-1, action),
+            };
+            let index = self
+                .atn
+                .lexer_actions
+                .iter()
+                .position(|it| it == action)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Lexer action {:?} is not a part of the ATN", action),
+                    )
+                })?;
+            self.data.extend_from_slice(&[offset, index as i32]);
+        }
+        Ok(())
+    }
+}
+
+struct Decoder<'a> {
+    atn: &'a ATN,
+    data: &'a [u8],
+    contexts: Vec<Arc<PredictionContext>>,
+    max_edges: usize,
+    max_precedence_edges: usize,
+}
+
+impl Decoder<'_> {
+    fn next(&mut self) -> io::Result<i32> { self.data.read_i32::<LittleEndian>() }
+
+    fn flag(&mut self) -> io::Result<bool> { Ok(self.next()? != 0) }
+
+    fn len(&mut self) -> io::Result<usize> {
+        let len = self.next()?;
+        if len < 0 {
+            return Err(invalid_data(format!("Invalid length {}", len)));
+        }
+        Ok(len as usize)
+    }
+
+    fn index(&mut self, len: usize, what: &str) -> io::Result<usize> {
+        check_index(self.next()?, len, what)
+    }
+
+    fn context_ref(&mut self) -> io::Result<Option<Arc<PredictionContext>>> {
+        match self.next()? {
+            -1 => Ok(None),
+            id => {
+                let id = check_index(id, self.contexts.len(), "prediction context")?;
+                Ok(Some(self.contexts[id].clone()))
+            }
+        }
+    }
+
+    fn context(&mut self) -> io::Result<Arc<PredictionContext>> {
+        let is_array = self.flag()?;
+        let mut parents = Vec::new();
+        let mut return_states = Vec::new();
+        for _ in 0..self.len()? {
+            return_states.push(self.next()? as isize);
+            parents.push(self.context_ref()?);
+        }
+        if is_array {
+            return Ok(PredictionContext::new_array(parents, return_states).alloc());
+        }
+        match (parents.pop(), return_states.pop()) {
+            (Some(None), Some(PREDICTION_CONTEXT_EMPTY_RETURN_STATE)) if parents.is_empty() => {
+                Ok(EMPTY_PREDICTION_CONTEXT.clone())
+            }
+            (Some(parent), Some(return_state)) if parents.is_empty() => {
+                Ok(PredictionContext::new_singleton(parent, return_state).alloc())
+            }
+            _ => Err(invalid_data(
+                "Singleton prediction context must have exactly one parent".to_owned(),
+            )),
+        }
+    }
+
+    fn dfa(&mut self, is_precedence_dfa: bool) -> io::Result<(Option<DFAStateRef>, Vec<DFAState>)> {
+        let s0 = self.next()?;
+        let len = self.len()?;
+        if len == 0 {
+            return Err(invalid_data("DFA without placeholder state".to_owned()));
+        }
+        let s0 = match s0 {
+            // precedence DFA start state is created along with the DFA and is never removed
+            -1 if is_precedence_dfa => {
+                return Err(invalid_data(
+                    "Precedence DFA without start state".to_owned(),
+                ))
+            }
+            -1 => None,
+            0 => return Err(invalid_data("Invalid DFA start state 0".to_owned())),
+            s0 => Some(check_index(s0, len, "DFA state")?),
+        };
+        let states = (1..len)
+            .map(|state_number| {
+                let precedence_start = is_precedence_dfa && Some(state_number) == s0;
+                self.state(state_number, len, precedence_start)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok((s0, states))
+    }
+
+    /// `precedence_start` is true for the start state of the precedence DFA,
+    /// which has start states for each precedence as edges
+    fn state(
+        &mut self, state_number: usize, states: usize, precedence_start: bool,
+    ) -> io::Result<DFAState> {
+        let configs = self.config_set()?;
+        let mut state = DFAState::new_dfastate(state_number, Box::new(configs));
+
+        let max_edges = if precedence_start {
+            self.max_precedence_edges
+        } else {
+            self.max_edges
+        };
+        let edges = self.len()?;
+        if edges > max_edges {
+            return Err(invalid_data(format!(
+                "Invalid number of DFA edges {}",
+                edges
+            )));
+        }
+        state.edges = vec![0; edges];
+        for _ in 0..self.len()? {
+            let symbol = self.index(state.edges.len(), "DFA edge")?;
+            let target = self.edge_target(states)?;
+            if precedence_start && target == ERROR_DFA_STATE_REF {
+                return Err(invalid_data(
+                    "Error edge in precedence DFA start state".to_owned(),
+                ));
+            }
+            state.edges[symbol] = target;
+        }
+        for _ in 0..self.len()? {
+            let symbol = self.next()? as isize;
+            let target = self.edge_target(states)?;
+            state.unicode_edges.insert(symbol, target);
+        }
+
+        state.is_accept_state = self.flag()?;
+        state.prediction = self.next()? as isize;
+        state.requires_full_context = self.flag()?;
+        state.lexer_action_executor = self.executor()?.map(Box::new);
+        for _ in 0..self.len()? {
+            let alt = self.next()? as isize;
+            let pred = self.semantic_context()?;
+            state.predicates.push(PredPrediction { alt, pred });
+        }
+        Ok(state)
+    }
+
+    fn edge_target(&mut self, states: usize) -> io::Result<DFAStateRef> {
+        match self.next()? {
+            -1 => Ok(ERROR_DFA_STATE_REF),
+            target => check_index(target, states, "DFA state"),
+        }
+    }
+
+    fn config_set(&mut self) -> io::Result<ATNConfigSet> {
+        let full_ctx = self.flag()?;
+        let mut configs = match self.atn.grammar_type {
+            ATNType::LEXER => ATNConfigSet::new_ordered(),
+            ATNType::PARSER => ATNConfigSet::new_base_atnconfig_set(full_ctx),
+        };
+        let read_only = self.flag()?;
+        let has_semantic_context = self.flag()?;
+        let dips_into_outer_context = self.flag()?;
+        let unique_alt = self.next()? as isize;
+        for _ in 0..self.len()? {
+            let alt = self.len()?;
+            configs.conflicting_alts.insert(alt);
+        }
+        for _ in 0..self.len()? {
+            let config = self.config()?;
+            configs.add(Box::new(config));
+        }
+        configs.set_has_semantic_context(has_semantic_context);
+        configs.set_dips_into_outer_context(dips_into_outer_context);
+        configs.set_unique_alt(unique_alt);
+        configs.set_read_only(read_only);
+        Ok(configs)
+    }
+
+    fn config(&mut self) -> io::Result<ATNConfig> {
+        let state = self.index(self.atn.states.len(), "ATN state")?;
+        let alt = self.next()? as isize;
+        let context = self.context_ref()?;
+        let mut config = ATNConfig::new(state, alt, context);
+        config.set_reaches_into_outer_context(self.next()? as isize);
+        config.set_precedence_filter_suppressed(self.flag()?);
+        config.semantic_context = Box::new(self.semantic_context()?);
+        if self.flag()? {
+            let passed_through_non_greedy_decision = self.flag()?;
+            let lexer_action_executor = self.executor()?.map(Box::new);
+            config.config_type = ATNConfigType::LexerATNConfig {
+                lexer_action_executor,
+                passed_through_non_greedy_decision,
+            };
+        }
+        Ok(config)
+    }
+
+    fn semantic_context(&mut self) -> io::Result<SemanticContext> {
+        let semctx = match self.next()? {
+            0 => SemanticContext::Predicate {
+                rule_index: self.next()? as isize,
+                pred_index: self.next()? as isize,
+                is_ctx_dependent: self.flag()?,
+            },
+            1 => SemanticContext::Precedence(self.next()? as isize),
+            2 => SemanticContext::AND(self.operands()?),
+            3 => SemanticContext::OR(self.operands()?),
+            kind => {
+                return Err(invalid_data(format!(
+                    "Invalid semantic context type {}",
+                    kind
+                )))
+            }
+        };
+        Ok(semctx)
+    }
+
+    fn operands(&mut self) -> io::Result<Vec<SemanticContext>> {
+        (0..self.len()?).map(|_| self.semantic_context()).collect()
+    }
+
+    fn executor(&mut self) -> io::Result<Option<LexerActionExecutor>> {
+        let len = match self.next()? {
+            -1 => return Ok(None),
+            len if len >= 0 => len as usize,
+            len => return Err(invalid_data(format!("Invalid length {}", len))),
+        };
+        let mut actions = Vec::new();
+        for _ in 0..len {
+            let offset = self.next()?;
+            let index = self.index(self.atn.lexer_actions.len(), "lexer action")?;
+            let action = self.atn.lexer_actions[index].clone();
+            actions.push(match offset {
+                -1 => action,
+                offset => LexerAction::LexerIndexedCustomAction {
+                    offset: offset as isize,
+                    action: Box::new(action),
+                },
+            });
+        }
+        Ok(Some(LexerActionExecutor::new(actions)))
+    }
+}
@@ -1,35 +1,56 @@
-use std::fmt::{Display, Formatter};
+//! Text and Graphviz DOT representation of the prediction DFA
+//!
+//! Text format is the same as in the Java runtime, one edge per line:
+//! ```text
+//! s0-ID->s1
+//! s1-'+'->:s2=>1
+//! ```
+//! where `:` marks accept states, `^` marks states that require full context prediction,
+//! and `=>` is followed by predicted alternative or by predicates with their alternatives.
+use std::fmt::{Display, Formatter, Write};
 
 use crate::dfa::DFA;
-use crate::dfa_state::DFAState;
+use crate::dfa_state::{DFAState, DFAStateRef};
 use crate::lexer_atn_simulator::ERROR_DFA_STATE_REF;
+use crate::utils::escape_dot;
 
+/// Serializes `DFA` using `get_edge_label` to create labels for edges.
+///
+/// Usually created via `DFA::to_string`/`DFA::to_lexer_string`
+/// or `DFA::to_dot`/`DFA::to_lexer_dot`.
+/// `Display` implementation outputs text format.
 pub struct DFASerializer<'a, 'b> {
     dfa: &'a DFA,
     get_edge_label: &'b dyn Fn(usize) -> String,
 }
 
+impl std::fmt::Debug for DFASerializer<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DFASerializer")
+            .field("decision", &self.dfa.decision)
+            .finish()
+    }
+}
+
 impl Display for DFASerializer<'_, '_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let dfa = &self.dfa.states;
-        for source in dfa.iter() {
-            for (i, edge) in source.edges.iter().copied().enumerate() {
-                if edge != 0 && edge != ERROR_DFA_STATE_REF {
-                    let target = &dfa[edge];
-                    f.write_fmt(format_args!(
-                        "{}-{}->{}\n",
-                        self.get_state_string(source),
-                        (self.get_edge_label)(i),
-                        self.get_state_string(target)
-                    ))?;
-                }
-            }
+        for (source, edge, target) in self.edges() {
+            f.write_fmt(format_args!(
+                "{}-{}->{}\n",
+                self.get_state_string(source),
+                (self.get_edge_label)(edge),
+                self.get_state_string(target)
+            ))?;
         }
         Ok(())
     }
 }
 
 impl DFASerializer<'_, '_> {
+    /// Creates serializer for `dfa`.
+    ///
+    /// `get_edge_label` receives symbol of the edge,
+    /// which is token type + 1 for parser and character code point for lexer.
     pub fn new<'a, 'b>(
         dfa: &'a DFA,
         get_edge_label: &'b dyn Fn(usize) -> String,
@@ -40,6 +61,79 @@ impl DFASerializer<'_, '_> {
         }
     }
 
+    /// Returns representation of the DFA as a Graphviz DOT graph
+    pub fn to_dot(&self) -> String {
+        let mut result = String::new();
+        let _ = writeln!(result, "digraph DFA{} {{", self.dfa.decision);
+        let _ = writeln!(result, "rankdir=LR;");
+        for state in self.states() {
+            let name = format!("s{}", state.state_number - 1);
+            if state.is_accept_state {
+                let _ = writeln!(
+                    result,
+                    "{}[fontsize=11, label=\"{}\", shape=doublecircle, fixedsize=false, \
+                     peripheries=1];",
+                    name,
+                    escape_dot(&self.get_state_string(state)[1..])
+                );
+            } else {
+                let _ = writeln!(
+                    result,
+                    "{}[fontsize=11, label=\"{}\", shape=circle, fixedsize=true, width=.55, \
+                     peripheries=1];",
+                    name,
+                    escape_dot(&self.get_state_string(state))
+                );
+            }
+        }
+        for (source, edge, target) in self.edges() {
+            let _ = writeln!(
+                result,
+                "s{} -> s{} [fontsize=11, fontname=\"Courier\", arrowsize=.7, label = \"{}\"];",
+                source.state_number - 1,
+                target.state_number - 1,
+                escape_dot(&(self.get_edge_label)(edge))
+            );
+        }
+        result.push_str("}\n");
+        result
+    }
+
+    fn is_real_state(&self, state: DFAStateRef) -> bool {
+        // first state is a placeholder for `null`, and in precedence DFA
+        // s0 is not a real state, its edges lead to start states for each precedence
+        state != 0 && !(self.dfa.is_precedence_dfa() && Some(state) == self.dfa.s0)
+    }
+
+    fn states(&self) -> impl Iterator<Item = &DFAState> + '_ {
+        self.dfa
+            .states
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| self.is_real_state(*i))
+            .map(|(_, state)| state)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (&DFAState, usize, &DFAState)> + '_ {
+        let dfa = &self.dfa.states;
+        self.states().flat_map(move |source| {
+            let mut unicode_edges = source
+                .unicode_edges
+                .iter()
+                .map(|(&symbol, &edge)| (symbol as usize, edge))
+                .collect::<Vec<_>>();
+            unicode_edges.sort_unstable();
+            source
+                .edges
+                .iter()
+                .copied()
+                .enumerate()
+                .chain(unicode_edges)
+                .filter(|&(_, edge)| edge != 0 && edge != ERROR_DFA_STATE_REF)
+                .map(move |(i, edge)| (source, i, &dfa[edge]))
+        })
+    }
+
     fn get_state_string(&self, state: &DFAState) -> String {
         let mut base_str = format!(
             "{}s{}{}",
@@ -49,8 +143,12 @@ impl DFASerializer<'_, '_> {
         );
         if state.is_accept_state {
             base_str = if !state.predicates.is_empty() {
-                unimplemented!()
-            //                format!("{}=>{:?}", base_str, state.predicates)
+                let predicates = state
+                    .predicates
+                    .iter()
+                    .map(|it| it.to_string())
+                    .collect::<Vec<_>>();
+                format!("{}=>[{}]", base_str, predicates.join(", "))
             } else {
                 format!("{}=>{}", base_str, state.prediction)
             };
@@ -1,10 +1,13 @@
+use std::collections::HashMap;
 use std::fmt::{Display, Error, Formatter};
 use std::hash::{Hash, Hasher};
+use std::mem::size_of;
 
 use murmur3::murmur3_32::MurmurHasher;
 
 use crate::atn_config_set::ATNConfigSet;
 use crate::lexer_action_executor::LexerActionExecutor;
+use crate::prediction_context::MurmurHasherBuilder;
 use crate::semantic_context::SemanticContext;
 
 #[derive(Eq, PartialEq, Debug)]
@@ -15,7 +18,7 @@ pub struct PredPrediction {
 
 impl Display for PredPrediction {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
-        f.write_fmt(format_args!("({},{:?})", self.alt, self.pred))
+        f.write_fmt(format_args!("({}, {})", self.pred, self.alt))
     }
 }
 
@@ -31,6 +34,9 @@ pub struct DFAState {
     /// - usize::MAX => error edge
     /// - _ => actual edge
     pub edges: Vec<DFAStateRef>,
+    /// Lexer DFA edges for code points above `MAX_DFA_EDGE`,
+    /// same values as in `edges`
+    pub(crate) unicode_edges: HashMap<isize, DFAStateRef, MurmurHasherBuilder>,
     pub is_accept_state: bool,
 
     pub prediction: isize,
@@ -54,12 +60,22 @@ impl DFAState {
         hasher.finish()
     }
 
+    /// Approximate number of bytes used by this state, prediction contexts are not included
+    pub(crate) fn approximate_size(&self) -> usize {
+        size_of::<DFAState>()
+            + self.edges.capacity() * size_of::<DFAStateRef>()
+            + self.unicode_edges.capacity() * (size_of::<(isize, DFAStateRef)>() + 1)
+            + self.predicates.capacity() * size_of::<PredPrediction>()
+            + self.configs.approximate_size()
+    }
+
     pub fn new_dfastate(state_number: usize, configs: Box<ATNConfigSet>) -> DFAState {
         DFAState {
             state_number,
             configs,
             //            edges: Vec::with_capacity((MAX_DFA_EDGE - MIN_DFA_EDGE + 1) as usize),
             edges: Vec::new(),
+            unicode_edges: HashMap::with_hasher(MurmurHasherBuilder {}),
             is_accept_state: false,
             prediction: 0,
             lexer_action_executor: None,
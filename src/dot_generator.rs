@@ -0,0 +1,213 @@
+//! Graphviz DOT representation of the ATN
+//!
+//! Each rule is rendered as a separate graph starting at its `RuleStartState`.
+//! Rule invocations are not expanded, instead they are shown as a single edge labeled
+//! `<rule>` that leads to the state parsing continues from after the invoked rule returns.
+//! Graphs can be rendered with Graphviz, e.g. `dot -Tsvg expr.dot -o expr.svg`.
+//!
+//! Labels of the states and edges:
+//!  - `s5\nd=2` - state 5 which is a decision state for decision 2,
+//!    `precedence` and `nongreedy` are added for such kinds of decisions
+//!  - `'a'..'z'`, `{ID, INT}` - characters or tokens matched by the transition
+//!  - `<expr>[3]` - invocation of rule `expr` with precedence 3, precedence is shown
+//!    only for left recursive rules
+//!  - `{1:0}?` - semantic predicate 0 in rule 1
+//!  - `{2>=prec}?` - precedence predicate of the left recursive rule
+//!  - `action_1:0` - action 0 in rule 1
+//!  - `ε` - epsilon transition
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Debug, Formatter, Write};
+
+use crate::atn::ATN;
+use crate::atn_state::{ATNDecisionState, ATNState, ATNStateRef, ATNStateType};
+use crate::atn_type::ATNType;
+use crate::transition::{ActionTransition, RuleTransition, Transition, TransitionType};
+use crate::utils::escape_dot;
+use crate::vocabulary::Vocabulary;
+
+/// Creates Graphviz DOT graphs of the rules of the `ATN`
+///
+/// ```text
+/// let generator = DOTGenerator::new(parser.get_atn(), parser.get_rule_names(), parser.get_vocabulary());
+/// std::fs::write("expr.dot", generator.get_rule_dot(RULE_expr))?;
+/// ```
+pub struct DOTGenerator<'a> {
+    atn: &'a ATN,
+    rule_names: &'a [&'a str],
+    vocabulary: &'a dyn Vocabulary,
+}
+
+impl Debug for DOTGenerator<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DOTGenerator")
+            .field("atn", self.atn)
+            .field("rule_names", &self.rule_names)
+            .finish()
+    }
+}
+
+impl<'a> DOTGenerator<'a> {
+    /// Creates generator for `atn`.
+    ///
+    /// `rule_names` and `vocabulary` are used to create labels,
+    /// they have to be the ones `atn` was created for.
+    pub fn new(atn: &'a ATN, rule_names: &'a [&'a str], vocabulary: &'a dyn Vocabulary) -> Self {
+        Self {
+            atn,
+            rule_names,
+            vocabulary,
+        }
+    }
+
+    /// Returns DOT graph for each rule, index in result is the rule index
+    pub fn get_dot_graphs(&self) -> Vec<String> {
+        (0..self.atn.rule_to_start_state.len())
+            .map(|rule_index| self.get_rule_dot(rule_index))
+            .collect()
+    }
+
+    /// Returns DOT graph of the ATN of the rule with `rule_index`
+    ///
+    /// ### Panics
+    /// If there is no rule with `rule_index` in the ATN
+    pub fn get_rule_dot(&self, rule_index: usize) -> String {
+        let start = self.atn.rule_to_start_state[rule_index];
+        let mut title = self.rule_name(rule_index);
+        if let ATNStateType::RuleStartState {
+            is_left_recursive: true,
+            ..
+        } = self.atn.states[start].get_state_type()
+        {
+            title += " (left recursive)";
+        }
+
+        let mut result = String::new();
+        let _ = writeln!(
+            result,
+            "digraph \"{}\" {{",
+            escape_dot(&self.rule_name(rule_index))
+        );
+        result.push_str("rankdir=LR;\nranksep=.25;\n");
+        let _ = writeln!(result, "label=\"{}\";", escape_dot(&title));
+        result.push_str("node [shape=circle, fontsize=11, fixedsize=true, width=.55];\n");
+        result.push_str("edge [fontsize=11, fontname=\"Courier\", arrowsize=.7];\n");
+
+        let mut edges = String::new();
+        let mut visited = HashSet::new();
+        let mut work = VecDeque::new();
+        visited.insert(start);
+        work.push_back(start);
+        while let Some(state) = work.pop_front() {
+            let state = self.atn.states[state].as_ref();
+            let _ = writeln!(result, "{}", self.node(state));
+            if *state.get_state_type() == ATNStateType::RuleStopState {
+                // transitions of the stop state lead back to the invoking rules
+                continue;
+            }
+            for transition in state.get_transitions() {
+                let (target, attributes) = self.edge(transition.as_ref());
+                let _ = writeln!(
+                    edges,
+                    "s{} -> s{} [{}];",
+                    state.get_state_number(),
+                    target,
+                    attributes
+                );
+                if visited.insert(target) {
+                    work.push_back(target);
+                }
+            }
+        }
+        result += &edges;
+        result.push_str("}\n");
+        result
+    }
+
+    fn rule_name(&self, rule_index: usize) -> String {
+        self.rule_names
+            .get(rule_index)
+            .map(|&it| it.to_owned())
+            .unwrap_or_else(|| format!("rule{}", rule_index))
+    }
+
+    fn node(&self, state: &dyn ATNState) -> String {
+        let number = state.get_state_number();
+        match state.get_state_type() {
+            ATNStateType::RuleStopState => format!("s{}[shape=doublecircle, width=.45];", number),
+            ATNStateType::DecisionState {
+                decision,
+                nongreedy,
+                state,
+            } if *decision >= 0 => {
+                let mut label = format!("s{}\\nd={}", number, decision);
+                if let ATNDecisionState::StarLoopEntry {
+                    is_precedence: true,
+                    ..
+                } = state
+                {
+                    label += "\\nprecedence";
+                }
+                if *nongreedy {
+                    label += "\\nnongreedy";
+                }
+                format!("s{}[label=\"{}\", fixedsize=false];", number, label)
+            }
+            _ => format!("s{}[label=\"s{}\"];", number, number),
+        }
+    }
+
+    fn edge(&self, transition: &dyn Transition) -> (ATNStateRef, String) {
+        let is_lexer = self.atn.grammar_type == ATNType::LEXER;
+        let set_label = || {
+            let set = transition.get_label().unwrap();
+            if is_lexer {
+                set.to_char_string()
+            } else {
+                set.to_token_string(self.vocabulary)
+            }
+        };
+        let label = match transition.get_serialization_type() {
+            TransitionType::TRANSITION_EPSILON => "ε".to_owned(),
+            TransitionType::TRANSITION_RULE => {
+                let transition = transition.cast::<RuleTransition>();
+                let mut label = format!("<{}>", self.rule_name(transition.rule_index as usize));
+                let target_start = self.atn.states[transition.target].get_state_type();
+                if let ATNStateType::RuleStartState {
+                    is_left_recursive: true,
+                    ..
+                } = target_start
+                {
+                    let _ = write!(label, "[{}]", transition.precedence);
+                }
+                return (
+                    transition.follow_state,
+                    format!("label=\"{}\", style=bold", escape_dot(&label)),
+                );
+            }
+            TransitionType::TRANSITION_PREDICATE | TransitionType::TRANSITION_PRECEDENCE => {
+                transition.get_predicate().unwrap().to_string()
+            }
+            TransitionType::TRANSITION_ACTION => {
+                let transition = transition.cast::<ActionTransition>();
+                format!(
+                    "action_{}:{}",
+                    transition.rule_index, transition.action_index
+                )
+            }
+            TransitionType::TRANSITION_ATOM
+            | TransitionType::TRANSITION_RANGE
+            | TransitionType::TRANSITION_SET => set_label(),
+            TransitionType::TRANSITION_NOTSET => format!("~{}", set_label()),
+            TransitionType::TRANSITION_WILDCARD => ".".to_owned(),
+        };
+        let style = if transition.is_epsilon() {
+            ", style=dashed"
+        } else {
+            ""
+        };
+        (
+            transition.get_target(),
+            format!("label=\"{}\"{}", escape_dot(&label), style),
+        )
+    }
+}
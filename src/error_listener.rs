@@ -7,13 +7,20 @@ use bit_set::BitSet;
 use crate::atn_config_set::ATNConfigSet;
 use crate::dfa::DFA;
 use crate::errors::ANTLRError;
+use crate::int_stream::UNKNOWN_SOURCE_NAME;
 
 use crate::parser::Parser;
 use crate::recognizer::Recognizer;
 
 use crate::token_factory::TokenFactory;
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fmt::Debug;
+use std::rc::Rc;
+
+use crate::char_stream::{CharStream, InputData};
+use crate::lexer::{BaseLexer, LexerRecog};
+use crate::token::{Token, TOKEN_INVALID_TYPE};
 
 /// Describes interface for listening on parser/lexer errors.
 /// Should only listen for errors, for processing/recovering from errors use `ErrorStrategy`
@@ -77,20 +84,28 @@ pub trait ErrorListener<'a, T: Recognizer<'a>> {
 }
 
 /// Default error listener that outputs errors to stderr
+///
+/// If input source name is known (e.g. when parsing `FileStream`),
+/// messages are prefixed with it.
 #[derive(Debug)]
 pub struct ConsoleErrorListener {}
 
 impl<'a, T: Recognizer<'a>> ErrorListener<'a, T> for ConsoleErrorListener {
     fn syntax_error(
         &self,
-        _recognizer: &T,
+        recognizer: &T,
         _offending_symbol: Option<&<T::TF as TokenFactory<'a>>::Inner>,
         line: isize,
         column: isize,
         msg: &str,
         _e: Option<&ANTLRError>,
     ) {
-        eprintln!("line {}:{} {}", line, column, msg);
+        let source_name = recognizer.get_input_source_name();
+        if source_name == UNKNOWN_SOURCE_NAME {
+            eprintln!("line {}:{} {}", line, column, msg);
+        } else {
+            eprintln!("{}: line {}:{} {}", source_name, line, column, msg);
+        }
     }
 }
 
@@ -299,6 +314,236 @@ impl<'a, T: Parser<'a>> ErrorListener<'a, T> for DiagnosticErrorListener {
         recognizer.notify_error_listeners(msg, None, None);
     }
 }
+
+/// Severity of the `Diagnostic`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Severity {
+    /// Syntax error reported by lexer or parser
+    Error,
+    /// Potential problem that doesn't prevent parsing, e.g. an ambiguity
+    Warning,
+}
+
+/// Error reported to `ErrorListener` along with its location
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// Severity of the diagnostic
+    pub severity: Severity,
+    /// Error message
+    pub message: String,
+    /// Text of the offending token, or of the input that lexer failed to recognize
+    pub token_text: Option<String>,
+    /// Type of the offending token, `TOKEN_INVALID_TYPE` if there is none
+    pub token_type: isize,
+    /// Index of the first character of the offending input, or -1 if it is unknown
+    pub start: isize,
+    /// Index of the last character of the offending input, or -1 if it is unknown
+    pub stop: isize,
+    /// Line of the error
+    pub line: isize,
+    /// Column of the error
+    pub column: isize,
+    /// Name of the input source, see `Recognizer::get_input_source_name`
+    pub source_name: String,
+    /// Rules parser was in at the time of the error, innermost first, empty for lexer errors
+    pub rule_stack: Vec<String>,
+    /// Error that caused this diagnostic, if any
+    pub error: Option<ANTLRError>,
+}
+
+/// Error listener that stores errors of lexer and parser as `Diagnostic`s,
+/// so they can be retrieved after the parse.
+///
+/// Clones share the same storage, so the same collector can be added to both lexer and parser
+/// to get all diagnostics in the order they have been reported.
+/// ```text
+/// let diagnostics = DiagnosticCollector::new();
+/// lexer.remove_error_listeners();
+/// lexer.add_error_listener(Box::new(diagnostics.clone()));
+/// let mut parser = CSVParser::new(CommonTokenStream::new(lexer));
+/// parser.remove_error_listeners();
+/// parser.add_error_listener(Box::new(diagnostics.clone()));
+/// let result = parser.csvFile();
+/// for diagnostic in diagnostics.take_diagnostics() { ... }
+/// ```
+/// Exact ambiguities are collected as warnings, they are reported only if parser uses
+/// `PredictionMode::LL_EXACT_AMBIG_DETECTION`.
+#[derive(Clone, Debug, Default)]
+pub struct DiagnosticCollector {
+    diagnostics: Rc<RefCell<Vec<Diagnostic>>>,
+}
+
+impl DiagnosticCollector {
+    /// Creates collector without diagnostics
+    pub fn new() -> Self { Self::default() }
+
+    /// Returns diagnostics collected so far
+    pub fn get_diagnostics(&self) -> Vec<Diagnostic> { self.diagnostics.borrow().clone() }
+
+    /// Returns diagnostics collected so far and removes them from the collector
+    pub fn take_diagnostics(&self) -> Vec<Diagnostic> { self.diagnostics.replace(Vec::new()) }
+
+    /// Whether any diagnostic with `Severity::Error` has been collected
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .borrow()
+            .iter()
+            .any(|it| it.severity == Severity::Error)
+    }
+}
+
+impl<'a, T: Recognizer<'a>> ErrorListener<'a, T> for DiagnosticCollector {
+    fn syntax_error(
+        &self,
+        recognizer: &T,
+        offending_symbol: Option<&<T::TF as TokenFactory<'a>>::Inner>,
+        line: isize,
+        column: isize,
+        msg: &str,
+        error: Option<&ANTLRError>,
+    ) {
+        let (token_text, token_type, start, stop) = match offending_symbol {
+            Some(token) => (
+                Some(token.get_text().to_display()),
+                token.get_token_type(),
+                token.get_start(),
+                token.get_stop(),
+            ),
+            None => match recognizer.get_error_input() {
+                Some((text, start, stop)) => (Some(text), TOKEN_INVALID_TYPE, start, stop),
+                None => (None, TOKEN_INVALID_TYPE, -1, -1),
+            },
+        };
+        self.diagnostics.borrow_mut().push(Diagnostic {
+            severity: Severity::Error,
+            message: msg.to_owned(),
+            token_text,
+            token_type,
+            start,
+            stop,
+            line,
+            column,
+            source_name: recognizer.get_input_source_name(),
+            rule_stack: recognizer.get_rule_stack(),
+            error: error.cloned(),
+        })
+    }
+
+    fn report_ambiguity(
+        &self,
+        recognizer: &T,
+        dfa: &DFA,
+        start_index: isize,
+        stop_index: isize,
+        exact: bool,
+        ambig_alts: &BitSet,
+        _configs: &ATNConfigSet,
+    ) {
+        if !exact {
+            return;
+        }
+        if let Some(diagnostic) = recognizer.get_ambiguity(dfa, start_index, stop_index, ambig_alts)
+        {
+            self.diagnostics.borrow_mut().push(diagnostic)
+        }
+    }
+}
+
+/// Recognizer specific parts of the `Diagnostic`
+trait DiagnosticSource<'a>: Recognizer<'a> {
+    /// Text and character interval of the input that caused the error without offending token
+    fn get_error_input(&self) -> Option<(String, isize, isize)>;
+    fn get_rule_stack(&self) -> Vec<String>;
+    fn get_ambiguity(
+        &self,
+        dfa: &DFA,
+        start_index: isize,
+        stop_index: isize,
+        ambig_alts: &BitSet,
+    ) -> Option<Diagnostic>;
+}
+
+impl<'a, T: Recognizer<'a>> DiagnosticSource<'a> for T {
+    default fn get_error_input(&self) -> Option<(String, isize, isize)> { None }
+
+    default fn get_rule_stack(&self) -> Vec<String> { Vec::new() }
+
+    default fn get_ambiguity(
+        &self,
+        _dfa: &DFA,
+        _start_index: isize,
+        _stop_index: isize,
+        _ambig_alts: &BitSet,
+    ) -> Option<Diagnostic> {
+        None
+    }
+}
+
+impl<'a, T: Parser<'a>> DiagnosticSource<'a> for T {
+    fn get_error_input(&self) -> Option<(String, isize, isize)> { None }
+
+    fn get_rule_stack(&self) -> Vec<String> { self.get_rule_invocation_stack() }
+
+    fn get_ambiguity(
+        &self,
+        dfa: &DFA,
+        start_index: isize,
+        stop_index: isize,
+        ambig_alts: &BitSet,
+    ) -> Option<Diagnostic> {
+        use std::borrow::Borrow;
+
+        let input = self.get_input_stream();
+        let text = input.get_text_from_interval(start_index, stop_index);
+        let start = input.get(start_index).borrow();
+        Some(Diagnostic {
+            severity: Severity::Warning,
+            message: format!(
+                "reportAmbiguity d={}: ambigAlts={:?}, input='{}'",
+                DiagnosticErrorListener::new(true).get_decision_description(self, dfa),
+                ambig_alts,
+                text
+            ),
+            token_text: Some(text),
+            token_type: TOKEN_INVALID_TYPE,
+            start: start.get_start(),
+            stop: input.get(stop_index).borrow().get_stop(),
+            line: start.get_line(),
+            column: start.get_column(),
+            source_name: self.get_input_source_name(),
+            rule_stack: self.get_rule_invocation_stack(),
+            error: None,
+        })
+    }
+}
+
+impl<'a, T, Input, TF> DiagnosticSource<'a> for BaseLexer<'a, T, Input, TF>
+where
+    T: LexerRecog<'a, Self> + 'static,
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'a>,
+{
+    fn get_error_input(&self) -> Option<(String, isize, isize)> {
+        let input = self.input.as_ref()?;
+        let start = self.token_start_char_index;
+        let stop = self.get_char_index().min(input.size() - 1);
+        let text = TF::get_data(input.get_text(start, stop)).to_display();
+        Some((text, start, stop))
+    }
+
+    fn get_rule_stack(&self) -> Vec<String> { Vec::new() }
+
+    fn get_ambiguity(
+        &self,
+        _dfa: &DFA,
+        _start_index: isize,
+        _stop_index: isize,
+        _ambig_alts: &BitSet,
+    ) -> Option<Diagnostic> {
+        None
+    }
+}
+
 /*
 impl DefaultErrorListener {
     fn new_default_error_listener() -> * DefaultErrorListener { unimplemented!() }
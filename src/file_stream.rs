@@ -1,14 +1,150 @@
-use std;
+//! `CharStream` that reads its data from a file
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::Path;
 
-pub struct FileStream {
-    base: InputStream,
+use better_any::{impl_tid, TidAble};
 
-    filename: String,
+use crate::char_stream::{CharStream, InputData};
+use crate::input_stream::InputStream;
+use crate::int_stream::IntStream;
+
+/// Lexer input loaded from a file.
+///
+/// Works the same way as `InputStream` over owned data, but also remembers the path
+/// it has been read from, so `IntStream::get_source_name` (and consequently
+/// `TokenSource::get_source_name` of the lexer and token streams)
+/// reports actual file name.
+///
+/// `FileStream<str>`, created with `FileStream::new`, keeps file as UTF-8 text
+/// and has same index behavior as `InputStream<&str>`.
+/// `FileStream<[u32]>`, created with `FileStream::new_code_points`, indexes input by code points,
+/// so indexes are the same as in Java runtime.
+#[derive(Debug)]
+pub struct FileStream<Data: ?Sized + InputData = str> {
+    base: InputStream<Box<Data>>,
+    file_name: String,
+}
+
+#[impl_tid]
+impl<'a, T: ?Sized + InputData + 'static> TidAble<'a> for FileStream<T> {}
+
+impl FileStream<str> {
+    /// Reads UTF-8 file at `path`.
+    ///
+    /// Returns `io::ErrorKind::InvalidData` error if file is not a valid UTF-8
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)?;
+        Ok(Self::with_name(data.into_boxed_str(), path))
+    }
+}
+
+impl FileStream<[u32]> {
+    /// Reads UTF-8 file at `path` and converts it to code points.
+    ///
+    /// Returns `io::ErrorKind::InvalidData` error if file is not a valid UTF-8
+    pub fn new_code_points(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path)?;
+        let data = data.chars().map(|it| it as u32).collect::<Vec<_>>();
+        Ok(Self::with_name(data.into_boxed_slice(), path))
+    }
+}
+
+impl<Data: ?Sized + InputData> FileStream<Data> {
+    fn with_name(data: Box<Data>, path: &Path) -> Self {
+        Self {
+            base: InputStream::new_owned(data),
+            file_name: path.to_string_lossy().into_owned(),
+        }
+    }
+
+    /// Path this stream has been loaded from
+    pub fn get_file_name(&self) -> &str { &self.file_name }
+
+    /// Resets input stream to start from the beginning of the file
+    #[inline]
+    pub fn reset(&mut self) { self.base.reset() }
+}
+
+impl<T: From<String>> CharStream<T> for FileStream<str> {
+    #[inline]
+    fn get_text(&self, a: isize, b: isize) -> T { self.base.get_text(a, b) }
+}
+
+impl CharStream<String> for FileStream<[u32]> {
+    fn get_text(&self, a: isize, b: isize) -> String {
+        CharStream::<Vec<u32>>::get_text(&self.base, a, b).to_display()
+    }
+}
+
+impl<'a> CharStream<Cow<'a, str>> for FileStream<[u32]> {
+    fn get_text(&self, a: isize, b: isize) -> Cow<'a, str> {
+        CharStream::<String>::get_text(self, a, b).into()
+    }
 }
 
-impl FileStream {
-    fn new(fileName: String) -> Result<FileStream, std::io::Error> { unimplemented!() }
+impl<Data: ?Sized + InputData> IntStream for FileStream<Data> {
+    #[inline]
+    fn consume(&mut self) { self.base.consume() }
+
+    #[inline]
+    fn la(&mut self, i: isize) -> isize { self.base.la(i) }
+
+    #[inline]
+    fn mark(&mut self) -> isize { self.base.mark() }
+
+    #[inline]
+    fn release(&mut self, marker: isize) { self.base.release(marker) }
+
+    #[inline]
+    fn index(&self) -> isize { self.base.index() }
+
+    #[inline]
+    fn seek(&mut self, index: isize) { self.base.seek(index) }
+
+    #[inline]
+    fn size(&self) -> isize { self.base.size() }
+
+    fn get_source_name(&self) -> String { self.file_name.clone() }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::char_stream::CharStream;
+    use crate::int_stream::{IntStream, EOF};
+
+    use super::FileStream;
+
+    #[test]
+    fn test_file_stream() {
+        let path = std::env::temp_dir()
+            .join(format!("antlr_rust_test_file_stream_{}.txt", std::process::id()));
+        fs::write(&path, "V1は3").unwrap();
+
+        let mut input = FileStream::new(&path).unwrap();
+        assert_eq!(input.get_source_name(), path.to_string_lossy());
+        assert_eq!(input.la(1), 'V' as isize);
+        input.seek(2);
+        assert_eq!(input.la(1), 0x306F);
+        input.consume();
+        assert_eq!(input.index(), 5);
+        assert_eq!(CharStream::<String>::get_text(&input, 2, 5), "は3");
+
+        let mut input = FileStream::new_code_points(&path).unwrap();
+        assert_eq!(input.get_source_name(), path.to_string_lossy());
+        input.seek(2);
+        assert_eq!(input.la(1), 0x306F);
+        input.consume();
+        assert_eq!(input.index(), 3);
+        assert_eq!(input.la(2), EOF);
+        assert_eq!(CharStream::<String>::get_text(&input, 2, 3), "は3");
 
-    fn get_source_name(&self) -> String { unimplemented!() }
+        fs::remove_file(&path).unwrap();
+        assert!(FileStream::new(&path).is_err());
+    }
 }
- 
\ No newline at end of file
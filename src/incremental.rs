@@ -0,0 +1,391 @@
+//! Incremental relexing and reparsing for editor integrations
+//!
+//! After a text edit only tokens whose lexing examined the changed text are lexed again,
+//! the rest of the previous tokens are reused with positions shifted by the edit.
+//! Then `ParserInterpreter` can reuse subtrees of the previous parse tree
+//! that have been parsed without examining the relexed tokens:
+//! ```text
+//! let mut lexer = CSVLexer::new(InputStream::new(text));
+//! let tokens = lex_all(&mut lexer);
+//! let source = ListTokenSource::new(get_tokens(&tokens), lexer.get_token_factory());
+//! let mut parser = ParserInterpreter::new(..., CommonTokenStream::new(source));
+//! parser.set_track_lookahead(true);
+//! let tree = parser.parse(RULE_csvFile)?;
+//!
+//! // after the edit
+//! let mut lexer = CSVLexer::new(InputStream::new(new_text));
+//! let relexed = relex(&mut lexer, &tokens, &edit);
+//! let source = ListTokenSource::new(get_tokens(&relexed.tokens), lexer.get_token_factory());
+//! let mut new_parser = ParserInterpreter::new(..., CommonTokenStream::new(source));
+//! new_parser.set_track_lookahead(true);
+//! new_parser.set_reusable_subtrees(&*tree, parser.get_rule_lookahead().unwrap(), &relexed);
+//! let new_tree = new_parser.parse(RULE_csvFile)?;
+//! ```
+//! Generated parsers don't reuse subtrees, because they can't rebuild generated contexts
+//! over the new tokens. For them `find_reusable_subtrees` only reports which subtrees
+//! of the previous tree were parsed without examining the edited tokens, so information
+//! derived from them (e.g. symbols or diagnostics) doesn't need to be recomputed.
+//!
+//! Custom state of the lexer and parser used by embedded actions and predicates
+//! is not tracked, so grammars that rely on it should relex everything.
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::char_stream::CharStream;
+use crate::lexer::{BaseLexer, LexerRecog, LexerState};
+use crate::parser::ParserNodeType;
+use crate::parser_rule_context::ParserRuleContext;
+use crate::rule_context::CustomRuleContext;
+use crate::token::{Token, TOKEN_EOF};
+use crate::token_factory::TokenFactory;
+use crate::token_source::TokenSource;
+use crate::tree::Tree;
+
+/// Change of the text, positions are character indexes in `CharStream`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TextEdit {
+    /// Index of the first changed character
+    pub start: isize,
+    /// End of the replaced text in the old input, exclusive
+    pub old_end: isize,
+    /// End of the inserted text in the new input, exclusive
+    pub new_end: isize,
+}
+
+impl TextEdit {
+    /// Replacement of the `old_len` characters at `start` with `new_len` characters
+    pub fn new(start: isize, old_len: isize, new_len: isize) -> Self {
+        TextEdit {
+            start,
+            old_end: start + old_len,
+            new_end: start + new_len,
+        }
+    }
+
+    /// Difference between the positions of the text after the edit in the new and old input
+    pub fn delta(&self) -> isize { self.new_end - self.old_end }
+}
+
+/// Token along with the information required to lex it again
+#[derive(Clone, Debug)]
+pub struct LexedToken<Tok> {
+    /// Token itself
+    pub token: Tok,
+    /// State of the lexer before this token, so it includes skipped text before the token
+    pub state: LexerState,
+    /// Index of the last character examined while lexing this token, see `BaseLexer::get_lookahead_index`
+    pub lookahead: isize,
+}
+
+/// Tokens after the relexing
+#[derive(Clone, Debug)]
+pub struct Relexed<Tok> {
+    /// All tokens of the new input
+    pub tokens: Vec<LexedToken<Tok>>,
+    /// Range of the old tokens that have been lexed again
+    pub old_range: Range<usize>,
+    /// Range of the new tokens that replaced `old_range`,
+    /// tokens outside of it are the same as the old ones shifted by the edit
+    pub new_range: Range<usize>,
+}
+
+impl<Tok> Relexed<Tok> {
+    /// Index in `tokens` of the old token at `index`, which must be outside of `old_range`
+    pub fn new_index(&self, index: isize) -> isize {
+        if index >= self.old_range.end as isize {
+            index + self.new_range.len() as isize - self.old_range.len() as isize
+        } else {
+            index
+        }
+    }
+}
+
+/// Returns tokens from `lexed`, e.g. to create `ListTokenSource`
+pub fn get_tokens<Tok: Clone>(lexed: &[LexedToken<Tok>]) -> Vec<Tok> {
+    lexed.iter().map(|it| it.token.clone()).collect()
+}
+
+fn next_lexed<'input, T, Input, TF>(
+    lexer: &mut BaseLexer<'input, T, Input, TF>,
+) -> LexedToken<TF::Tok>
+where
+    T: LexerRecog<'input, BaseLexer<'input, T, Input, TF>> + 'static,
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    let state = lexer.save_state();
+    let token = lexer.next_token();
+    LexedToken {
+        token,
+        state,
+        lookahead: lexer.get_lookahead_index(),
+    }
+}
+
+fn is_eof<Tok: Borrow<impl Token + ?Sized>>(lexed: &LexedToken<Tok>) -> bool {
+    lexed.token.borrow().get_token_type() == TOKEN_EOF
+}
+
+/// Lexes the rest of the input up to and including EOF token
+pub fn lex_all<'input, T, Input, TF>(
+    lexer: &mut BaseLexer<'input, T, Input, TF>,
+) -> Vec<LexedToken<TF::Tok>>
+where
+    T: LexerRecog<'input, BaseLexer<'input, T, Input, TF>> + 'static,
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    let mut tokens = Vec::new();
+    loop {
+        let lexed = next_lexed(lexer);
+        let eof = is_eof(&lexed);
+        tokens.push(lexed);
+        if eof {
+            return tokens;
+        }
+    }
+}
+
+/// Lexes again only tokens of `old` that could have been affected by `edit`.
+///
+/// `lexer` must be created over the input after the edit, and `old` must be the result of
+/// `lex_all` or `relex` for the input before the edit.
+/// Tokens that examined only text before the edit are reused as is. Lexing starts from
+/// the first token that examined the edited text, and stops as soon as lexer reaches
+/// the start of some old token after the edit in the same mode, tokens from there on are
+/// reused with positions shifted by the edit.
+pub fn relex<'input, T, Input, TF>(
+    lexer: &mut BaseLexer<'input, T, Input, TF>, old: &[LexedToken<TF::Tok>], edit: &TextEdit,
+) -> Relexed<TF::Tok>
+where
+    T: LexerRecog<'input, BaseLexer<'input, T, Input, TF>> + 'static,
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+    TF::Data: PartialEq,
+{
+    let first = old
+        .iter()
+        .position(|it| it.lookahead >= edit.start)
+        .unwrap_or(old.len());
+    let mut tokens = old[..first].to_vec();
+    if let Some(it) = old.get(first) {
+        lexer.restore_state(&it.state);
+    }
+
+    let delta = edit.delta();
+    let tab_width = lexer.get_position_config().tab_width;
+    let mut next_old = first;
+    loop {
+        let state = lexer.save_state();
+        while next_old < old.len()
+            && (old[next_old].state.index < edit.old_end
+                || old[next_old].state.index + delta < state.index)
+        {
+            next_old += 1;
+        }
+        if let Some(sync) = old.get(next_old) {
+            if sync.state.index + delta == state.index
+                && sync.state.mode == state.mode
+                && sync.state.mode_stack == state.mode_stack
+                && sync.state.hit_eof == state.hit_eof
+                // tab stops in the rest of the line stay the same only if it is moved by whole tabs
+                && (tab_width <= 0 || (state.column - sync.state.column) % tab_width == 0)
+            {
+                let new_end = tokens.len();
+                let factory = lexer.get_token_factory();
+                shift_tokens(
+                    factory,
+                    lexer.input.as_mut().unwrap(),
+                    &old[next_old..],
+                    &state,
+                    &mut tokens,
+                );
+                return Relexed {
+                    tokens,
+                    old_range: first..next_old,
+                    new_range: first..new_end,
+                };
+            }
+        }
+
+        let lexed = next_lexed(lexer);
+        let eof = is_eof(&lexed);
+        tokens.push(lexed);
+        if eof {
+            let new_end = tokens.len();
+            return Relexed {
+                tokens,
+                old_range: first..old.len(),
+                new_range: first..new_end,
+            };
+        }
+    }
+}
+
+/// Appends `old` tokens to `tokens` moved to start at `state`.
+///
+/// Tokens are created again from the new `input`, so they refer to it the same way
+/// as freshly lexed ones, only text that has been changed by the lexer is copied.
+fn shift_tokens<'input, TF, Input>(
+    factory: &'input TF, input: &mut Input, old: &[LexedToken<TF::Tok>], state: &LexerState,
+    tokens: &mut Vec<LexedToken<TF::Tok>>,
+) where
+    TF: TokenFactory<'input>,
+    TF::Data: PartialEq,
+    Input: CharStream<TF::From>,
+{
+    let from = &old[0].state;
+    let delta = state.index - from.index;
+    // only the rest of the first line is moved horizontally
+    let shift = |line: isize, column: isize| {
+        let column = if line == from.line {
+            column + state.column - from.column
+        } else {
+            column
+        };
+        (line + state.line - from.line, column)
+    };
+    tokens.extend(old.iter().map(|it| {
+        let token = it.token.borrow();
+        let (line, column) = shift(token.get_line(), token.get_column());
+        let (start, stop) = (token.get_start() + delta, token.get_stop() + delta);
+        let token = if token.get_token_type() == TOKEN_EOF {
+            factory.create(
+                None::<&mut Input>,
+                TOKEN_EOF,
+                None,
+                token.get_channel(),
+                start,
+                stop,
+                line,
+                column,
+            )
+        } else {
+            let text = TF::get_data(input.get_text(start, stop));
+            let text = if *text == *token.get_text() {
+                None
+            } else {
+                Some(token.get_text().to_owned())
+            };
+            factory.create(
+                Some(&mut *input),
+                token.get_token_type(),
+                text,
+                token.get_channel(),
+                start,
+                stop,
+                line,
+                column,
+            )
+        };
+        let (line, column) = shift(it.state.line, it.state.column);
+        LexedToken {
+            token,
+            state: LexerState {
+                index: it.state.index + delta,
+                line,
+                column,
+                ..it.state.clone()
+            },
+            lookahead: it.lookahead + delta,
+        }
+    }));
+}
+
+/// Lookahead of the rule invocations recorded by the parser,
+/// see `BaseParser::set_track_lookahead`.
+///
+/// Lookahead is the index of the last token that could have been examined
+/// while parsing the rule. It is bounded by the number of tokens fetched from the token source,
+/// so it is exact only for token streams that fetch tokens lazily, like `CommonTokenStream`.
+#[derive(Clone, Debug, Default)]
+pub struct RuleLookahead {
+    // (rule index, start token index, stop token index) -> lookahead
+    lookahead: HashMap<(usize, isize, isize), isize>,
+}
+
+impl RuleLookahead {
+    pub(crate) fn record<'input>(
+        &mut self, ctx: &(impl ParserRuleContext<'input> + ?Sized), lookahead: isize,
+    ) {
+        let interval = ctx.get_source_interval();
+        let entry = self
+            .lookahead
+            .entry((ctx.get_rule_index(), interval.a, interval.b))
+            .or_insert(lookahead);
+        *entry = lookahead.max(*entry);
+    }
+
+    /// Lookahead of the invocation that produced `ctx`
+    pub fn get<'input>(&self, ctx: &(impl ParserRuleContext<'input> + ?Sized)) -> Option<isize> {
+        let interval = ctx.get_source_interval();
+        self.lookahead
+            .get(&(ctx.get_rule_index(), interval.a, interval.b))
+            .copied()
+    }
+
+    /// Whether `ctx` has been parsed without examining tokens in the `damaged` range
+    pub fn is_unaffected<'input>(
+        &self, ctx: &(impl ParserRuleContext<'input> + ?Sized), damaged: &Range<usize>,
+    ) -> bool {
+        let interval = ctx.get_source_interval();
+        if interval.a < 0 {
+            return false;
+        }
+        interval.a >= damaged.end as isize
+            || matches!(self.get(ctx), Some(lookahead) if lookahead < damaged.start as isize)
+    }
+}
+
+/// Finds the largest subtrees below `tree` that have been parsed without examining
+/// tokens in the `damaged` range, usually `Relexed::old_range`.
+///
+/// Subtrees before the damaged range will be the same in the new tree. Subtrees after it
+/// have been parsed from the same tokens, which are at `Relexed::new_index` in the new
+/// token list, but they are the same in the new tree only if parser reaches them
+/// in the same state, i.e. invokes the same rule from the same ATN state
+/// with the same rule invocation stack.
+/// `ParserInterpreter::set_reusable_subtrees` checks that before reusing them.
+pub fn find_reusable_subtrees<'input, T>(
+    tree: &T, lookahead: &RuleLookahead, damaged: &Range<usize>,
+) -> Vec<Rc<<T::Ctx as ParserNodeType<'input>>::Type>>
+where
+    T: ParserRuleContext<'input> + ?Sized,
+{
+    find_reusable_subtrees_by(tree, lookahead, damaged, |_| true)
+}
+
+/// Same as `find_reusable_subtrees`, but looks for smaller subtrees below the unaffected ones
+/// that don't pass the `filter`
+pub(crate) fn find_reusable_subtrees_by<'input, T>(
+    tree: &T, lookahead: &RuleLookahead, damaged: &Range<usize>,
+    filter: impl Fn(&<T::Ctx as ParserNodeType<'input>>::Type) -> bool,
+) -> Vec<Rc<<T::Ctx as ParserNodeType<'input>>::Type>>
+where
+    T: ParserRuleContext<'input> + ?Sized,
+{
+    fn collect<'input, Ctx: ParserNodeType<'input>>(
+        node: &Rc<Ctx::Type>, lookahead: &RuleLookahead, damaged: &Range<usize>,
+        filter: &dyn Fn(&Ctx::Type) -> bool, result: &mut Vec<Rc<Ctx::Type>>,
+    ) {
+        // terminal nodes don't have a rule index
+        if node.get_rule_index() == usize::MAX {
+            return;
+        }
+        if lookahead.is_unaffected(&**node, damaged) && filter(node) {
+            result.push(node.clone());
+            return;
+        }
+        for child in node.get_children() {
+            collect::<Ctx>(&child, lookahead, damaged, filter, result);
+        }
+    }
+
+    let mut result = Vec::new();
+    for child in tree.get_children() {
+        collect::<T::Ctx>(&child, lookahead, damaged, &filter, &mut result);
+    }
+    result
+}
@@ -2,7 +2,7 @@
 use std::borrow::Cow;
 
 use crate::char_stream::{CharStream, InputData};
-use crate::int_stream::IntStream;
+use crate::int_stream::{IntStream, UNKNOWN_SOURCE_NAME};
 use std::ops::Deref;
 
 use better_any::{impl_tid, TidAble};
@@ -96,7 +96,7 @@ impl<Data: ?Sized + InputData> InputStream<Box<Data>> {
     /// Creates new `InputStream` over owned data   
     pub fn new_owned(data: Box<Data>) -> Self {
         Self {
-            name: "<empty>".to_string(),
+            name: UNKNOWN_SOURCE_NAME.to_string(),
             data_raw: data.into(),
             index: 0,
         }
@@ -127,7 +127,7 @@ where
         // let data_raw = data_raw.as_ref();
         // let data = data_raw.to_indexed_vec();
         Self {
-            name: "<empty>".to_string(),
+            name: UNKNOWN_SOURCE_NAME.to_string(),
             data_raw,
             index: 0,
             // phantom: Default::default(),
@@ -3,6 +3,9 @@
 /// `IntStream::la` must return EOF in the end of stream
 pub const EOF: isize = -1;
 
+/// Value returned by `IntStream::get_source_name` when stream does not know its source
+pub const UNKNOWN_SOURCE_NAME: &str = "<unknown>";
+
 /// A simple stream of symbols whose values are represented as integers. This
 /// interface provides *marked ranges* with support for a minimum level
 /// of buffering necessary to implement arbitrary lookahead during prediction.
@@ -0,0 +1,208 @@
+//! Reader for `.interp` files generated by ANTLR tool
+//!
+//! Along with the generated code ANTLR tool emits `<Grammar>.interp` file for each
+//! lexer and parser. It contains everything required to run `LexerInterpreter` and
+//! `ParserInterpreter` for that grammar, so tools can work with any grammar without
+//! generated Rust code.
+//!
+//! ```text
+//! let lexer_data = InterpreterData::read("CSVLexer.interp")?;
+//! let parser_data = InterpreterData::read("CSV.interp")?;
+//! let lexer = lexer_data.create_lexer("CSVLexer.g4", InputStream::new(text));
+//! let mut parser = parser_data.create_parser("CSV.g4", CommonTokenStream::new(lexer));
+//! let tree = parser.parse(0)?;
+//! ```
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use better_any::TidAble;
+
+use crate::atn::ATN;
+use crate::atn_deserializer::ATNDeserializer;
+use crate::char_stream::CharStream;
+use crate::error_strategy::DefaultErrorStrategy;
+use crate::lexer_interpreter::LexerInterpreter;
+use crate::parser_interpreter::{InterpreterContextType, ParserInterpreter};
+use crate::token_factory::TokenFactory;
+use crate::token_stream::TokenStream;
+use crate::vocabulary::VocabularyImpl;
+
+/// Grammar information loaded from `.interp` file
+#[derive(Debug)]
+pub struct InterpreterData {
+    /// Deserialized ATN of the grammar
+    pub atn: Arc<ATN>,
+    /// Literal and symbolic token names
+    pub vocabulary: VocabularyImpl,
+    /// Names of the grammar rules, index in this list is the rule index
+    pub rule_names: Vec<String>,
+    /// Names of the token channels, only present for lexer grammars
+    pub channel_names: Option<Vec<String>>,
+    /// Names of the lexer modes, only present for lexer grammars
+    pub mode_names: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Section {
+    LiteralNames,
+    SymbolicNames,
+    RuleNames,
+    ChannelNames,
+    ModeNames,
+    Atn,
+}
+
+impl Section {
+    fn from_header(line: &str) -> Option<Section> {
+        let section = match line {
+            "token literal names:" => Section::LiteralNames,
+            "token symbolic names:" => Section::SymbolicNames,
+            "rule names:" => Section::RuleNames,
+            "channel names:" => Section::ChannelNames,
+            "mode names:" => Section::ModeNames,
+            "atn:" => Section::Atn,
+            _ => return None,
+        };
+        Some(section)
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg) }
+
+fn parse_atn(line: &str) -> io::Result<Vec<i32>> {
+    let values = line
+        .trim()
+        .strip_prefix('[')
+        .and_then(|it| it.strip_suffix(']'))
+        .ok_or_else(|| invalid_data(format!("Invalid ATN entry: {}", line)))?;
+    values
+        .split(',')
+        .map(|value| {
+            value
+                .trim()
+                .parse::<i32>()
+                .map_err(|e| invalid_data(format!("Invalid ATN value {:?}: {}", value, e)))
+        })
+        .collect()
+}
+
+impl InterpreterData {
+    /// Reads `.interp` file at `path`
+    ///
+    /// Returns `io::ErrorKind::InvalidData` error if file is not a valid `.interp` file.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> { fs::read_to_string(path)?.parse() }
+
+    /// Creates `LexerInterpreter` for this grammar that will tokenize `input`.
+    ///
+    /// ### Panics
+    /// If this is not a lexer grammar
+    pub fn create_lexer<'input, Input, TF>(
+        &self, grammar_file_name: &str, input: Input,
+    ) -> LexerInterpreter<'input, Input, TF>
+    where
+        Input: CharStream<TF::From>,
+        TF: TokenFactory<'input>,
+        &'input TF: Default,
+    {
+        LexerInterpreter::new(
+            grammar_file_name,
+            Box::new(self.vocabulary.clone()),
+            self.rule_names.clone(),
+            self.mode_names.clone().unwrap_or_default(),
+            self.atn.clone(),
+            input,
+        )
+    }
+
+    /// Creates `ParserInterpreter` for this grammar that will parse tokens from `input`.
+    pub fn create_parser<'input, I>(
+        &self, grammar_file_name: &str, input: I,
+    ) -> ParserInterpreter<
+        'input,
+        I,
+        DefaultErrorStrategy<'input, InterpreterContextType<'input, I::TF>>,
+    >
+    where
+        I: TokenStream<'input> + TidAble<'input>,
+    {
+        ParserInterpreter::new(
+            grammar_file_name,
+            Box::new(self.vocabulary.clone()),
+            self.rule_names.clone(),
+            self.atn.clone(),
+            input,
+        )
+    }
+}
+
+impl FromStr for InterpreterData {
+    type Err = io::Error;
+
+    /// Parses content of `.interp` file
+    fn from_str(s: &str) -> io::Result<Self> {
+        let mut literal_names = None;
+        let mut symbolic_names = None;
+        let mut rule_names = None;
+        let mut channel_names = None;
+        let mut mode_names = None;
+        let mut atn = None;
+
+        let mut lines = s.lines().peekable();
+        while let Some(line) = lines.next() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let section = Section::from_header(line.trim())
+                .ok_or_else(|| invalid_data(format!("Unexpected data entry: {}", line)))?;
+            if section == Section::Atn {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| invalid_data("Missing ATN".to_owned()))?;
+                atn = Some(parse_atn(line)?);
+                continue;
+            }
+
+            let mut entries = Vec::new();
+            while let Some(line) = lines.peek() {
+                let line = line.trim();
+                if line.is_empty() || Section::from_header(line).is_some() {
+                    break;
+                }
+                entries.push(line.to_owned());
+                lines.next();
+            }
+            let optional = |entries: Vec<String>| -> Vec<Option<String>> {
+                entries
+                    .into_iter()
+                    .map(|it| if it == "null" { None } else { Some(it) })
+                    .collect()
+            };
+            match section {
+                Section::LiteralNames => literal_names = Some(optional(entries)),
+                Section::SymbolicNames => symbolic_names = Some(optional(entries)),
+                Section::RuleNames => rule_names = Some(entries),
+                Section::ChannelNames => channel_names = Some(entries),
+                Section::ModeNames => mode_names = Some(entries),
+                Section::Atn => unreachable!(),
+            }
+        }
+
+        let missing = |name: &str| invalid_data(format!("Missing {} section", name));
+        let literal_names = literal_names.ok_or_else(|| missing("token literal names"))?;
+        let symbolic_names = symbolic_names.ok_or_else(|| missing("token symbolic names"))?;
+        let atn = atn.ok_or_else(|| missing("atn"))?;
+        let atn = ATNDeserializer::new(None)
+            .try_deserialize_ints(&atn)
+            .map_err(|e| invalid_data(format!("Invalid ATN: {}", e)))?;
+        Ok(InterpreterData {
+            atn: Arc::new(atn),
+            vocabulary: VocabularyImpl::new(literal_names.iter(), symbolic_names.iter(), None),
+            rule_names: rule_names.ok_or_else(|| missing("rule names"))?,
+            channel_names,
+            mode_names,
+        })
+    }
+}
@@ -99,6 +99,9 @@ impl IntervalSet {
 
     pub fn get_min(&self) -> Option<isize> { self.intervals.first().map(|x| x.a) }
 
+    /// Sorted disjoint intervals this set consists of
+    pub fn get_intervals(&self) -> &[Interval] { &self.intervals }
+
     pub fn add_one(&mut self, _v: isize) { self.add_range(_v, _v) }
 
     pub fn add_range(&mut self, l: isize, h: isize) { self.add_interval(Interval { a: l, b: h }) }
@@ -283,10 +286,35 @@ impl IntervalSet {
     //        unimplemented!()
     //    }
     //
-    //    fn to_char_String(&self) -> String {
-    //        unimplemented!()
-    //    }
-    //
+    /// String representation of the set where elements are characters, e.g. `{'0'..'9', '_'}`
+    pub fn to_char_string(&self) -> String {
+        if self.intervals.is_empty() {
+            return "{}".to_owned();
+        }
+        let char_name = |v: isize| match std::char::from_u32(v as u32) {
+            _ if v == TOKEN_EOF => "<EOF>".to_owned(),
+            Some(ch) => format!("'{}'", ch),
+            None => v.to_string(),
+        };
+        let elements = self
+            .intervals
+            .iter()
+            .map(|int| {
+                if int.a == int.b {
+                    char_name(int.a)
+                } else {
+                    format!("{}..{}", char_name(int.a), char_name(int.b))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if self.length() > 1 {
+            format!("{{{}}}", elements)
+        } else {
+            elements
+        }
+    }
+
     pub fn to_index_string(&self) -> String { self.to_token_string(&DUMMY_VOCAB) }
 
     pub fn to_token_string(&self, vocabulary: &dyn Vocabulary) -> String {
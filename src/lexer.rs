@@ -5,10 +5,12 @@ use std::cell::{Cell, RefCell};
 
 use std::rc::Rc;
 
+use crate::atn::ATN;
+use crate::atn_simulator::IATNSimulator;
 use crate::char_stream::{CharStream, InputData};
 use crate::error_listener::{ConsoleErrorListener, ErrorListener};
 use crate::errors::ANTLRError;
-use crate::int_stream::IntStream;
+use crate::int_stream::{IntStream, UNKNOWN_SOURCE_NAME};
 use crate::lexer_atn_simulator::{ILexerATNSimulator, LexerATNSimulator};
 use crate::parser::ParserNodeType;
 
@@ -17,6 +19,7 @@ use crate::rule_context::EmptyContextType;
 use crate::token::TOKEN_INVALID_TYPE;
 use crate::token_factory::{CommonTokenFactory, TokenAware, TokenFactory};
 use crate::token_source::TokenSource;
+use crate::vocabulary::Vocabulary;
 use std::ops::{Deref, DerefMut};
 
 ///  Lexer functionality required by `LexerATNSimulator` to work properly
@@ -100,6 +103,7 @@ pub struct BaseLexer<
     /// Make it `Some` to override token that is currently being generated by lexer
     pub token: Option<TF::Tok>,
     hit_eof: bool,
+    lookahead_index: isize,
     /// Channel lexer is currently assigning tokens to
     pub channel: isize,
     mode_stack: Vec<usize>,
@@ -109,10 +113,89 @@ pub struct BaseLexer<
     pub text: Option<<TF::Data as ToOwned>::Owned>,
 }
 
+/// State required to continue lexing from some position in the input,
+/// see `BaseLexer::save_state`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LexerState {
+    /// Index of the next character in the input
+    pub index: isize,
+    /// Line of the next character
+    pub line: isize,
+    /// Position of the next character in the line
+    pub column: isize,
+    /// Current lexer mode
+    pub mode: usize,
+    /// Modes saved by `Lexer::push_mode`
+    pub mode_stack: Vec<usize>,
+    /// Whether lexer has reached the end of the input
+    pub hit_eof: bool,
+}
+
 #[derive(Debug)]
 crate struct LexerPosition {
     crate line: Cell<isize>,
     crate char_position_in_line: Cell<isize>,
+    crate config: Cell<PositionConfig>,
+}
+
+/// Units in which lexer counts columns
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PositionEncoding {
+    /// Column is the number of code points before the character, same as in other ANTLR targets
+    CodePoint,
+    /// Column is the number of UTF-16 code units before the character,
+    /// e.g. default position encoding of Language Server Protocol
+    Utf16,
+    /// Column is the number of UTF-8 bytes before the character
+    Utf8,
+}
+
+/// Defines how lexer computes line and column of the tokens, see `BaseLexer::set_position_config`.
+///
+/// Lexer input is expected to return code points from `IntStream::la`.
+/// ```text
+/// lexer.set_position_config(PositionConfig {
+///     encoding: PositionEncoding::Utf16,
+///     tab_width: 4,
+///     crlf: true,
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PositionConfig {
+    /// Units of the column
+    pub encoding: PositionEncoding,
+    /// If greater than 0, tab moves column to the next multiple of `tab_width`
+    pub tab_width: isize,
+    /// Whether `\r\n` and lone `\r` start a new line in addition to `\n`
+    pub crlf: bool,
+}
+
+impl Default for PositionConfig {
+    fn default() -> Self {
+        PositionConfig {
+            encoding: PositionEncoding::CodePoint,
+            tab_width: 0,
+            crlf: false,
+        }
+    }
+}
+
+impl PositionConfig {
+    /// Column after the character `ch` that is at `column`
+    pub fn advance(&self, column: isize, ch: isize) -> isize {
+        if ch == '\t' as isize && self.tab_width > 0 {
+            return (column / self.tab_width + 1) * self.tab_width;
+        }
+        let width = match self.encoding {
+            PositionEncoding::CodePoint => 1,
+            PositionEncoding::Utf16 if ch > 0xFFFF => 2,
+            PositionEncoding::Utf16 => 1,
+            PositionEncoding::Utf8 => {
+                std::char::from_u32(ch as u32).map_or(1, |ch| ch.len_utf8() as isize)
+            }
+        };
+        column + width
+    }
 }
 
 impl<'input, T, Input, TF> Deref for BaseLexer<'input, T, Input, TF>
@@ -160,6 +243,22 @@ where
     ) {
         <T as Actions<'input, Self>>::action(_localctx, rule_index, action_index, self)
     }
+
+    fn get_rule_names(&self) -> &[&str] {
+        <T as Actions<'input, Self>>::get_rule_names(&self.recog)
+    }
+
+    fn get_vocabulary(&self) -> &dyn Vocabulary {
+        <T as Actions<'input, Self>>::get_vocabulary(&self.recog)
+    }
+
+    fn get_grammar_file_name(&self) -> &str {
+        <T as Actions<'input, Self>>::get_grammar_file_name(&self.recog)
+    }
+
+    fn get_atn(&self) -> &ATN { self.interpreter.as_ref().unwrap().atn() }
+
+    fn get_input_source_name(&self) -> String { TokenSource::get_source_name(self) }
 }
 
 /// Default lexer mode id
@@ -244,6 +343,68 @@ where
     /// Used from lexer actions to override text of the token that will be emitted next
     pub fn set_text(&mut self, _text: <TF::Data as ToOwned>::Owned) { self.text = Some(_text); }
 
+    /// Text representation of DFA of each lexer mode, index in result is the mode id
+    pub fn get_dfa_strings(&self) -> Vec<String> {
+        self.interpreter
+            .iter()
+            .flat_map(|it| it.decision_to_dfa().iter().take(it.atn().mode_to_start_state.len()))
+            .map(|dfa| dfa.read().to_lexer_string())
+            .collect()
+    }
+
+    /// Graphviz DOT graph of DFA of each lexer mode, index in result is the mode id
+    pub fn get_dfa_dot_graphs(&self) -> Vec<String> {
+        self.interpreter
+            .iter()
+            .flat_map(|it| it.decision_to_dfa().iter().take(it.atn().mode_to_start_state.len()))
+            .map(|dfa| dfa.read().to_lexer_dot())
+            .collect()
+    }
+
+    /// Index of the last character examined while producing the latest token,
+    /// including characters of skipped tokens and lookahead past the end of the token.
+    ///
+    /// Token would stay the same if only the input after this index changes.
+    pub fn get_lookahead_index(&self) -> isize { self.lookahead_index }
+
+    /// Returns state of the lexer between tokens, so lexing can be continued from the
+    /// current position later with `restore_state`
+    pub fn save_state(&self) -> LexerState {
+        LexerState {
+            index: self.get_char_index(),
+            line: self.get_line(),
+            column: self.get_char_position_in_line(),
+            mode: self.mode,
+            mode_stack: self.mode_stack.clone(),
+            hit_eof: self.hit_eof,
+        }
+    }
+
+    /// Continues lexing from `state` returned by `save_state`.
+    ///
+    /// Input can be a different stream, but text before `state.index` must be the same.
+    /// Custom state of the lexer, i.e. fields used by embedded actions, is not restored.
+    pub fn restore_state(&mut self, state: &LexerState) {
+        self.input.as_mut().unwrap().seek(state.index);
+        self.current_pos.line.set(state.line);
+        self.current_pos.char_position_in_line.set(state.column);
+        self.mode = state.mode;
+        self.mode_stack = state.mode_stack.clone();
+        self.hit_eof = state.hit_eof;
+        self.token = None;
+        self.text = None;
+        self.interpreter.as_mut().unwrap().reset();
+    }
+
+    /// Sets how lines and columns are computed for the tokens and error messages
+    /// of the subsequent input
+    pub fn set_position_config(&mut self, config: PositionConfig) {
+        self.current_pos.config.set(config)
+    }
+
+    /// Returns current `PositionConfig`
+    pub fn get_position_config(&self) -> PositionConfig { self.current_pos.config.get() }
+
     // fn get_all_tokens(&mut self) -> Vec<TF::Tok> { unimplemented!() }
 
     // fn get_char_error_display(&self, _c: char) -> String { unimplemented!() }
@@ -275,11 +436,13 @@ where
             current_pos: Rc::new(LexerPosition {
                 line: Cell::new(1),
                 char_position_in_line: Cell::new(0),
+                config: Cell::new(PositionConfig::default()),
             }),
             token_type: super::token::TOKEN_INVALID_TYPE,
             text: None,
             token: None,
             hit_eof: false,
+            lookahead_index: -1,
             channel: super::token::TOKEN_DEFAULT_CHANNEL,
             //            token_factory_source_pair: None,
             mode_stack: Vec::new(),
@@ -313,6 +476,7 @@ where
         assert!(self.input.is_some());
 
         let _marker = self.input().mark();
+        self.lookahead_index = self.input().index();
         'outer: loop {
             if self.hit_eof {
                 self.emit_eof();
@@ -336,6 +500,7 @@ where
                 let mut interpreter = self.interpreter.take().unwrap();
                 //                    let mut input = self.input.take().unwrap();
                 let result = interpreter.match_token(self.mode, self);
+                self.lookahead_index = self.lookahead_index.max(interpreter.get_lookahead_index());
                 self.interpreter = Some(interpreter);
 
                 let ttype = result.unwrap_or_else(|err| {
@@ -352,6 +517,7 @@ where
                 if self.input().la(1) == super::int_stream::EOF {
                     self.hit_eof = true;
                 }
+                self.lookahead_index = self.lookahead_index.max(self.input().index());
 
                 if self.token_type == TOKEN_INVALID_TYPE {
                     self.token_type = ttype;
@@ -390,7 +556,7 @@ where
         self.input
             .as_ref()
             .map(|it| it.get_source_name())
-            .unwrap_or("<none>".to_string())
+            .unwrap_or_else(|| UNKNOWN_SOURCE_NAME.to_string())
     }
 
     //    fn set_token_factory<'c: 'b>(&mut self, f: &'c TokenFactory) {
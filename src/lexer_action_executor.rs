@@ -37,15 +37,18 @@ impl LexerActionExecutor {
         old: Option<&Self>,
         lexer_action: LexerAction,
     ) -> LexerActionExecutor {
-        let mut new = old
-            .cloned()
-            .unwrap_or_else(|| LexerActionExecutor::new(Vec::new()));
-        new.lexer_actions.push(lexer_action);
-        new
+        let mut lexer_actions = old
+            .map(|it| it.lexer_actions.clone())
+            .unwrap_or_default();
+        lexer_actions.push(lexer_action);
+        LexerActionExecutor::new(lexer_actions)
     }
 
-    pub fn fix_offset_before_match(mut self, offset: isize) -> LexerActionExecutor {
-        for action in self.lexer_actions.iter_mut() {
+    pub(crate) fn get_lexer_actions(&self) -> &[LexerAction] { &self.lexer_actions }
+
+    pub fn fix_offset_before_match(self, offset: isize) -> LexerActionExecutor {
+        let mut lexer_actions = self.lexer_actions;
+        for action in lexer_actions.iter_mut() {
             match action {
                 LexerAction::LexerIndexedCustomAction { .. } => {}
                 _ => {
@@ -58,7 +61,7 @@ impl LexerActionExecutor {
                 }
             }
         }
-        self
+        LexerActionExecutor::new(lexer_actions)
     }
 
     pub fn execute<'input>(&self, lexer: &mut impl Lexer<'input>, start_index: isize) {
@@ -18,7 +18,9 @@ use crate::dfa_state::{DFAState, DFAStateRef};
 use crate::errors::ANTLRError;
 use crate::errors::ANTLRError::LexerNoAltError;
 use crate::int_stream::{IntStream, EOF};
-use crate::lexer::{Lexer, LexerPosition, LEXER_MAX_CHAR_VALUE, LEXER_MIN_CHAR_VALUE};
+use crate::lexer::{
+    Lexer, LexerPosition, PositionConfig, LEXER_MAX_CHAR_VALUE, LEXER_MIN_CHAR_VALUE,
+};
 use crate::lexer_action_executor::LexerActionExecutor;
 use crate::prediction_context::EMPTY_PREDICTION_CONTEXT;
 use crate::prediction_context::{
@@ -66,6 +68,7 @@ pub struct LexerATNSimulator {
     pub(crate) current_pos: Rc<LexerPosition>,
     mode: usize,
     prev_accept: SimState,
+    lookahead_index: isize,
     // lexer_action_executor: Option<Box<LexerActionExecutor>>,
 }
 
@@ -78,6 +81,7 @@ impl ILexerATNSimulator for LexerATNSimulator {
         //        input:&mut dyn CharStream,
         lexer: &mut impl Lexer<'input>,
     ) -> Result<isize, ANTLRError> {
+        self.base.check_dfa_state_limit();
         self.mode = mode;
         let mark = lexer.input().mark();
         //        println!("start matching on mode {}",mode);
@@ -113,11 +117,17 @@ impl ILexerATNSimulator for LexerATNSimulator {
 
     fn consume<T: IntStream + ?Sized>(&self, _input: &mut T) {
         let ch = _input.la(1);
-        if ch == '\n' as isize {
+        let config = self.current_pos.config.get();
+        // in `\r\n` only `\n` starts a new line
+        if ch == '\n' as isize
+            || (config.crlf && ch == '\r' as isize && _input.la(2) != '\n' as isize)
+        {
             self.current_pos.line.update(|x| x + 1);
             self.current_pos.char_position_in_line.set(0);
         } else {
-            self.current_pos.char_position_in_line.update(|x| x + 1);
+            self.current_pos
+                .char_position_in_line
+                .update(|x| config.advance(x, ch));
         }
         _input.consume();
     }
@@ -159,9 +169,11 @@ impl LexerATNSimulator {
             current_pos: Rc::new(LexerPosition {
                 line: Cell::new(0),
                 char_position_in_line: Cell::new(0),
+                config: Cell::new(PositionConfig::default()),
             }),
             mode: 0,
             prev_accept: SimState::new(),
+            lookahead_index: -1,
             // lexer_action_executor: None,
         }
     }
@@ -242,14 +254,15 @@ impl LexerATNSimulator {
         }
         // let _last = self.get_dfa().states.read().get(s).unwrap();
 
+        self.lookahead_index = lexer.input().index();
         self.fail_or_accept(symbol, lexer, dfa.unwrap())
     }
 
     #[inline(always)]
     fn get_existing_target_state(dfa: &DFA, _s: DFAStateRef, t: isize) -> Option<DFAStateRef> {
-        // if t < MIN_DFA_EDGE || t > MAX_DFA_EDGE {
-        //     return None;
-        // }
+        if t > MAX_DFA_EDGE {
+            return dfa.states[_s].unicode_edges.get(&t).copied();
+        }
 
         dfa.states[_s]
             .edges
@@ -634,7 +647,11 @@ impl LexerATNSimulator {
     }
 
     fn add_dfaedge(&self, _from: &mut DFAState, t: isize, _to: DFAStateRef) {
-        if t < MIN_DFA_EDGE || t > MAX_DFA_EDGE {
+        if t < MIN_DFA_EDGE {
+            return;
+        }
+        if t > MAX_DFA_EDGE {
+            _from.unicode_edges.insert(t, _to);
             return;
         }
 
@@ -685,6 +702,7 @@ impl LexerATNSimulator {
                 let i = dfastate.state_number;
                 //println!("inserting new DFA state {} with size {}", i, dfastate.configs.length());
                 states.push(dfastate);
+                self.base.dfa_grown();
                 vec![i]
             })
             .first()
@@ -696,6 +714,18 @@ impl LexerATNSimulator {
         dfastate_index
     }
 
+    /// Sets maximal number of DFA states of the grammar, `None` means no limit.
+    ///
+    /// Once this lexer has grown DFA beyond the limit, DFA and prediction context cache
+    /// are cleared before the next token, see `IATNSimulator::clear_dfa`.
+    pub fn set_dfa_state_limit(&self, limit: Option<usize>) {
+        self.base.set_dfa_state_limit(limit)
+    }
+
+    /// Index of the last character examined by the latest `match_token` call,
+    /// it can be past the end of the matched token.
+    pub fn get_lookahead_index(&self) -> isize { self.lookahead_index }
+
     /// Returns current DFA that is currently used.
     pub fn get_dfa(&self) -> &RwLock<DFA> { &self.decision_to_dfa()[self.mode] }
 
@@ -0,0 +1,193 @@
+//! Lexer that works directly with ATN, without generated code
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use better_any::{Tid, TidAble};
+
+use crate::atn::ATN;
+use crate::atn_type::ATNType;
+use crate::char_stream::CharStream;
+use crate::dfa::DFA;
+use crate::int_stream::IntStream;
+use crate::lexer::{BaseLexer, LexerRecog};
+use crate::lexer_atn_simulator::LexerATNSimulator;
+use crate::parser_interpreter::NameList;
+use crate::prediction_context::PredictionContextCache;
+use crate::recognizer::Actions;
+use crate::token_factory::{CommonTokenFactory, TokenFactory};
+use crate::token_source::TokenSource;
+use crate::vocabulary::Vocabulary;
+
+/// Part of `LexerInterpreter` that provides grammar information to the `BaseLexer`
+pub struct LexerInterpreterExt {
+    grammar_file_name: String,
+    rule_names: NameList,
+    mode_names: Vec<String>,
+    vocabulary: Box<dyn Vocabulary>,
+}
+
+impl std::fmt::Debug for LexerInterpreterExt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LexerInterpreterExt")
+            .field("grammar_file_name", &self.grammar_file_name)
+            .field("rule_names", &self.rule_names.as_slice())
+            .field("mode_names", &self.mode_names)
+            .finish()
+    }
+}
+
+impl LexerInterpreterExt {
+    /// Names of the lexer modes, index in this list is the mode id
+    pub fn get_mode_names(&self) -> &[String] { &self.mode_names }
+}
+
+impl<'input, Input, TF> Actions<'input, BaseLexerType<'input, Input, TF>> for LexerInterpreterExt
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    fn get_rule_names(&self) -> &[&str] { self.rule_names.as_slice() }
+
+    fn get_vocabulary(&self) -> &dyn Vocabulary { self.vocabulary.as_ref() }
+
+    fn get_grammar_file_name(&self) -> &str { &self.grammar_file_name }
+}
+
+impl<'input, Input, TF> LexerRecog<'input, BaseLexerType<'input, Input, TF>> for LexerInterpreterExt
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+}
+
+/// `BaseLexer` type used by `LexerInterpreter`
+pub type BaseLexerType<'input, Input, TF> = BaseLexer<'input, LexerInterpreterExt, Input, TF>;
+
+/// Lexer that recognizes input by walking ATN directly.
+///
+/// Equivalent of Java `LexerInterpreter`, allows to tokenize input without generated lexer,
+/// given only serialized lexer ATN and names of the rules and tokens.
+/// Lexer commands like `skip`, `channel` or `pushMode` are a part of ATN so they work as usual,
+/// but custom actions are ignored and semantic predicates are considered to be always true.
+#[derive(Tid)]
+pub struct LexerInterpreter<'input, Input, TF = CommonTokenFactory>
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    base: BaseLexerType<'input, Input, TF>,
+}
+
+impl<'input, Input, TF> std::fmt::Debug for LexerInterpreter<'input, Input, TF>
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LexerInterpreter")
+            .field("ext", &*self.base)
+            .field("mode", &self.base.mode)
+            .finish()
+    }
+}
+
+impl<'input, Input, TF> Deref for LexerInterpreter<'input, Input, TF>
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    type Target = BaseLexerType<'input, Input, TF>;
+
+    fn deref(&self) -> &Self::Target { &self.base }
+}
+
+impl<'input, Input, TF> DerefMut for LexerInterpreter<'input, Input, TF>
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.base }
+}
+
+impl<'input, Input, TF> LexerInterpreter<'input, Input, TF>
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    /// Creates interpreter for lexer grammar with `atn` that will tokenize `input`
+    /// creating tokens with `tf`.
+    ///
+    /// `rule_names`, `mode_names` and `vocabulary` have to be the ones `atn` was created for.
+    ///
+    /// ### Panics
+    /// If `atn` is not a lexer ATN
+    pub fn new_with_token_factory(
+        grammar_file_name: &str, vocabulary: Box<dyn Vocabulary>, rule_names: Vec<String>,
+        mode_names: Vec<String>, atn: Arc<ATN>, input: Input, tf: &'input TF,
+    ) -> Self {
+        assert!(
+            atn.grammar_type == ATNType::LEXER,
+            "The ATN must be a lexer ATN."
+        );
+        let decision_to_dfa = (0..atn.decision_to_state.len())
+            .map(|i| DFA::new(atn.clone(), atn.get_decision_state(i), i as isize).into())
+            .collect();
+        let interpreter = LexerATNSimulator::new_lexer_atnsimulator(
+            atn,
+            Arc::new(decision_to_dfa),
+            Arc::new(PredictionContextCache::new()),
+        );
+        let ext = LexerInterpreterExt {
+            grammar_file_name: grammar_file_name.to_owned(),
+            rule_names: NameList::new(rule_names),
+            mode_names,
+            vocabulary,
+        };
+        Self {
+            base: BaseLexer::new_base_lexer(input, interpreter, ext, tf),
+        }
+    }
+}
+
+impl<'input, Input, TF> LexerInterpreter<'input, Input, TF>
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+    &'input TF: Default,
+{
+    /// Same as `LexerInterpreter::new_with_token_factory` but with default token factory
+    pub fn new(
+        grammar_file_name: &str, vocabulary: Box<dyn Vocabulary>, rule_names: Vec<String>,
+        mode_names: Vec<String>, atn: Arc<ATN>, input: Input,
+    ) -> Self {
+        Self::new_with_token_factory(
+            grammar_file_name,
+            vocabulary,
+            rule_names,
+            mode_names,
+            atn,
+            input,
+            <&TF as Default>::default(),
+        )
+    }
+}
+
+impl<'input, Input, TF> TokenSource<'input> for LexerInterpreter<'input, Input, TF>
+where
+    Input: CharStream<TF::From>,
+    TF: TokenFactory<'input>,
+{
+    type TF = TF;
+
+    fn next_token(&mut self) -> TF::Tok { self.base.next_token() }
+
+    fn get_line(&self) -> isize { self.base.get_line() }
+
+    fn get_char_position_in_line(&self) -> isize { self.base.get_char_position_in_line() }
+
+    fn get_input_stream(&mut self) -> Option<&mut dyn IntStream> { self.base.get_input_stream() }
+
+    fn get_source_name(&self) -> String { self.base.get_source_name() }
+
+    fn get_token_factory(&self) -> &'input TF { self.base.get_token_factory() }
+}
@@ -105,7 +105,7 @@ pub use lexer::{BaseLexer, Lexer};
 #[doc(inline)]
 pub use parser::{BaseParser, ListenerId, Parser};
 #[doc(inline)]
-pub use token_source::TokenSource;
+pub use token_source::{ListTokenSource, TokenSource};
 //extern crate uuid;
 #[doc(hidden)]
 pub use prediction_context::PredictionContextCache;
@@ -123,7 +123,7 @@ mod ll1_analyzer;
 #[doc(hidden)]
 pub mod recognizer;
 pub mod token_factory;
-//pub mod tokenstream_rewriter;
+pub mod tokenstream_rewriter;
 #[doc(hidden)]
 pub mod atn_deserialization_options;
 #[doc(hidden)]
@@ -139,36 +139,48 @@ mod prediction_context;
 pub mod semantic_context;
 mod token_source;
 pub mod token_stream;
-//pub mod trace_listener;
+pub mod trace_listener;
 #[doc(hidden)]
 pub mod dfa;
 #[doc(hidden)]
 pub mod transition;
 pub mod tree;
-//pub mod file_stream;
+pub mod file_stream;
+pub mod unbuffered_char_stream;
+pub mod utf8_char_stream;
+pub mod incremental;
 #[doc(hidden)]
 pub mod atn;
 #[doc(hidden)]
 pub mod atn_config_set;
 #[doc(hidden)]
 pub mod atn_deserializer;
+pub mod atn_serializer;
 pub mod common_token_stream;
-mod dfa_serializer;
+pub mod dfa_cache;
+pub mod dfa_serializer;
+pub mod dot_generator;
 pub mod error_listener;
 pub mod error_strategy;
 pub mod errors;
 pub mod input_stream;
+pub mod interpreter_data;
 pub mod lexer;
 #[doc(hidden)]
 pub mod lexer_action_executor;
 pub mod lexer_atn_simulator;
+pub mod lexer_interpreter;
 pub mod parser;
 pub mod parser_atn_simulator;
+pub mod parser_interpreter;
+pub mod profiling;
 mod prediction_mode;
 pub mod token;
+pub mod tree_pattern;
 pub mod trees;
+pub mod two_stage;
+pub mod xpath;
 mod utils;
-//pub mod tokenstream_rewriter_test;
 mod atn_type;
 // mod context_factory;
 pub mod rule_context;
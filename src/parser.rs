@@ -11,6 +11,7 @@ use crate::atn_simulator::IATNSimulator;
 use crate::error_listener::{ConsoleErrorListener, ErrorListener, ProxyErrorListener};
 use crate::error_strategy::ErrorStrategy;
 use crate::errors::ANTLRError;
+use crate::incremental::RuleLookahead;
 use crate::interval_set::IntervalSet;
 use crate::parser_atn_simulator::ParserATNSimulator;
 use crate::parser_rule_context::ParserRuleContext;
@@ -144,6 +145,7 @@ pub struct BaseParser<
 
     parse_listeners: Vec<Box<T>>,
     _syntax_errors: Cell<isize>,
+    lookahead: Option<RuleLookahead>,
     error_listeners: RefCell<Vec<Box<dyn ErrorListener<'input, Self>>>>,
 
     ext: Ext,
@@ -203,6 +205,8 @@ where
     fn get_grammar_file_name(&self) -> &str { self.ext.get_grammar_file_name() }
 
     fn get_atn(&self) -> &ATN { self.interp.atn() }
+
+    fn get_input_source_name(&self) -> String { self.input.get_source_name() }
 }
 
 impl<'input, Ext, I, Ctx, T> TokenAware<'input> for BaseParser<'input, Ext, I, Ctx, T>
@@ -373,14 +377,62 @@ where
             precedence_stack: vec![0],
             parse_listeners: vec![],
             _syntax_errors: Cell::new(0),
+            lookahead: None,
             error_listeners: RefCell::new(vec![Box::new(ConsoleErrorListener {})]),
             ext,
             pd: PhantomData,
         }
     }
 
-    //
-    //    fn reset(&self) { unimplemented!() }
+    /// Resets parser so it can parse input again from the beginning.
+    ///
+    /// Rewinds input to the first token and discards current rule context,
+    /// error and parse listeners are kept.
+    pub fn reset(&mut self) {
+        self.input.seek(0);
+        self.ctx = None;
+        self._syntax_errors.set(0);
+        self.matched_eof = false;
+        self.precedence_stack = vec![0];
+        self.state = -1;
+        if let Some(lookahead) = &mut self.lookahead {
+            *lookahead = RuleLookahead::default();
+        }
+    }
+
+    /// Enables or disables recording of the lookahead of each rule invocation,
+    /// see `incremental::RuleLookahead`
+    pub fn set_track_lookahead(&mut self, track: bool) {
+        self.lookahead = if track {
+            Some(RuleLookahead::default())
+        } else {
+            None
+        };
+    }
+
+    /// Lookahead of the rule invocations recorded since tracking has been enabled
+    /// or since the last `reset`
+    pub fn get_rule_lookahead(&self) -> Option<&RuleLookahead> { self.lookahead.as_ref() }
+
+    pub(crate) fn get_rule_lookahead_mut(&mut self) -> Option<&mut RuleLookahead> {
+        self.lookahead.as_mut()
+    }
+
+    fn record_lookahead(&mut self, ctx: &Ctx::Type) {
+        if let Some(lookahead) = &mut self.lookahead {
+            lookahead.record(ctx, self.input.size() - 1);
+        }
+    }
+
+    /// Number of syntax errors reported since parser creation or last `reset`
+    pub fn get_number_of_syntax_errors(&self) -> isize { self._syntax_errors.get() }
+
+    pub(crate) fn replace_error_listeners(
+        &mut self,
+        listeners: Vec<Box<dyn ErrorListener<'input, Self>>>,
+    ) -> Vec<Box<dyn ErrorListener<'input, Self>>> {
+        std::mem::replace(self.error_listeners.get_mut(), listeners)
+    }
 
     #[inline]
     pub fn match_token(
@@ -521,6 +573,10 @@ where
                 .unwrap()
                 .set_stop(self.input.lt(-1).cloned())
         }
+        if self.lookahead.is_some() {
+            let ctx = self.ctx.clone().unwrap();
+            self.record_lookahead(&ctx);
+        }
         self.trigger_exit_rule_event();
         self.set_state(self.get_parser_rule_context().get_invoking_state());
         let parent = self.ctx.as_ref().unwrap().get_parent_ctx();
@@ -572,6 +628,7 @@ where
         prev.set_parent(&Some(localctx.clone()));
         prev.set_invoking_state(state);
         prev.set_stop(self.input.lt(-1).cloned());
+        self.record_lookahead(&prev);
 
         //        println!("{}",prev.get_start().unwrap());
         localctx.set_start(Some(prev.start_mut().clone()));
@@ -587,6 +644,7 @@ where
         self.precedence_stack.pop();
         let retctx = self.ctx.clone().unwrap();
         retctx.set_stop(self.input.lt(-1).cloned());
+        self.record_lookahead(&retctx);
         if !self.parse_listeners.is_empty() {
             while self.ctx.as_ref().map(|x| Rc::as_ptr(x))
                 != parent_ctx.as_ref().map(|x| Rc::as_ptr(x))
@@ -638,6 +696,27 @@ where
         }
     }
 
+    /// Text representation of DFA of each decision, index in result is the decision number.
+    ///
+    /// Same format as `dump_dfa`, but empty string is returned for decisions
+    /// that have not been used for prediction yet.
+    pub fn get_dfa_strings(&self) -> Vec<String> {
+        self.interp
+            .decision_to_dfa()
+            .iter()
+            .map(|dfa| dfa.read().to_string(self.get_vocabulary()))
+            .collect()
+    }
+
+    /// Graphviz DOT graph of DFA of each decision, index in result is the decision number
+    pub fn get_dfa_dot_graphs(&self) -> Vec<String> {
+        self.interp
+            .decision_to_dfa()
+            .iter()
+            .map(|dfa| dfa.read().to_dot(self.get_vocabulary()))
+            .collect()
+    }
+
     //    fn get_invoking_context(&self, ruleIndex: isize) -> ParserRuleContext { unimplemented!() }
     //
     //    fn in_context(&self, context: ParserRuleContext) -> bool { unimplemented!() }
@@ -1,12 +1,13 @@
 //! Base parser implementation
 use std::borrow::Borrow;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
 
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Instant;
 use std::{ptr, usize};
 
 use bit_set::BitSet;
@@ -30,6 +31,7 @@ use crate::prediction_context::{
     PREDICTION_CONTEXT_EMPTY_RETURN_STATE,
 };
 use crate::prediction_mode::*;
+use crate::profiling::{ParseInfo, Profiler};
 use crate::semantic_context::SemanticContext;
 use crate::token::{Token, TOKEN_EOF, TOKEN_EPSILON};
 
@@ -84,6 +86,7 @@ pub struct ParserATNSimulator {
     base: BaseATNSimulator,
     prediction_mode: Cell<PredictionMode>,
     start_index: Cell<isize>,
+    profiler: RefCell<Option<Profiler>>,
     // pd:PhantomData<P>
 }
 
@@ -139,6 +142,7 @@ impl ParserATNSimulator {
             ),
             prediction_mode: Cell::new(PredictionMode::LL),
             start_index: Cell::new(0),
+            profiler: RefCell::new(None),
         }
     }
 
@@ -148,6 +152,39 @@ impl ParserATNSimulator {
     /// Sets current prediction mode
     pub fn set_prediction_mode(&self, v: PredictionMode) { self.prediction_mode.set(v) }
 
+    /// Enables or disables collection of per decision statistics, see `profiling` module.
+    ///
+    /// Statistics collected so far are kept if profiling is already enabled
+    /// and discarded if it is disabled.
+    pub fn set_profile(&self, profile: bool) {
+        let mut profiler = self.profiler.borrow_mut();
+        if !profile {
+            *profiler = None;
+        } else if profiler.is_none() {
+            *profiler = Some(Profiler::new(self.atn().decision_to_state.len()));
+        }
+    }
+
+    /// Returns statistics collected since profiling was enabled,
+    /// or `None` if profiling is disabled
+    pub fn get_parse_info(&self) -> Option<ParseInfo> {
+        self.profiler.borrow().as_ref().map(Profiler::parse_info)
+    }
+
+    /// Sets maximal number of DFA states of the grammar, `None` means no limit.
+    ///
+    /// Once this parser has grown DFA beyond the limit, DFA and prediction context cache
+    /// are cleared before the next prediction, see `IATNSimulator::clear_dfa`.
+    pub fn set_dfa_state_limit(&self, limit: Option<usize>) {
+        self.base.set_dfa_state_limit(limit)
+    }
+
+    fn profile(&self, f: impl FnOnce(&mut Profiler)) {
+        if let Some(profiler) = self.profiler.borrow_mut().as_mut() {
+            f(profiler)
+        }
+    }
+
     // fn reset(&self) { unimplemented!() }
 
     /// Called by generated parser to choose an alternative when LL(1) parsing is not enough
@@ -156,7 +193,9 @@ impl ParserATNSimulator {
         decision: isize,
         parser: &mut T,
     ) -> Result<isize, ANTLRError> {
+        self.base.check_dfa_state_limit();
         self.start_index.set(parser.get_input_stream_mut().index());
+        self.profile(|p| p.start_prediction(decision as usize, self.start_index.get()));
         let mut merge_cache: MergeCache = HashMap::with_hasher(MurmurHasherBuilder {});
         let mut local = Local {
             outer_context: parser.get_parser_rule_context().clone(),
@@ -218,13 +257,15 @@ impl ParserATNSimulator {
                 s0
             });
 
-            self.exec_atn(&mut local, s0)?
+            self.exec_atn(&mut local, s0)
         };
 
         local.input().seek(self.start_index.get());
         local.input().release(m);
-        //        println!("result = {}", result);
-        Ok(result)
+        //        println!("result = {:?}", result);
+        // failed predictions are recorded too, so each call is counted in `invocations`
+        self.profile(|p| p.end_prediction(*result.as_ref().unwrap_or(&INVALID_ALT)));
+        result
     }
 
     #[allow(non_snake_case)]
@@ -239,8 +280,12 @@ impl ParserATNSimulator {
 
         loop {
             //            println!("exec atn loop previous D {}",previousD as isize -1);
-            let D = Self::get_existing_target_state(local.dfa(), previousD, token)
-                .unwrap_or_else(|| self.compute_target_state(previousD, token, local));
+            let existing = Self::get_existing_target_state(local.dfa(), previousD, token);
+            self.profile(|p| {
+                let error = existing == Some(ERROR_DFA_STATE_REF);
+                p.sll_step(local.input().index(), existing.is_some(), error)
+            });
+            let D = existing.unwrap_or_else(|| self.compute_target_state(previousD, token, local));
             debug_assert!(D > 0);
 
             let dfa = local.dfa.take().unwrap();
@@ -378,6 +423,7 @@ impl ParserATNSimulator {
             let closure = closure.states[previousD].configs.as_ref();
             self.compute_reach_set(closure, t, false, local)
         };
+        self.profile(|p| p.atn_transition(false, reach.is_some()));
         local.upgrade_lock();
         let dfa = local.dfa_mut();
         let reach = match reach {
@@ -464,7 +510,9 @@ impl ParserATNSimulator {
         loop {
             //            println!("full_ctx loop");
 
+            self.profile(|p| p.ll_step(local.input().index()));
             let reach = self.compute_reach_set(&prev, t, full_ctx, local);
+            self.profile(|p| p.atn_transition(full_ctx, reach.is_some()));
             prev = match reach {
                 None => {
                     local.input().seek(self.start_index.get());
@@ -930,10 +978,17 @@ impl ParserATNSimulator {
         &self,
         local: &mut Local<'_, 'a, T>,
         pred: impl Borrow<SemanticContext>,
-        _alt: isize,
-        _full_ctx: bool,
+        alt: isize,
+        full_ctx: bool,
     ) -> bool {
-        pred.borrow().evaluate(local.parser, &*local.outer_context)
+        if self.profiler.borrow().is_none() {
+            return pred.borrow().evaluate(local.parser, &*local.outer_context);
+        }
+        let start = Instant::now();
+        let result = pred.borrow().evaluate(local.parser, &*local.outer_context);
+        let eval_time = start.elapsed();
+        self.profile(|p| p.predicate_evaluated(pred.borrow(), alt, full_ctx, result, eval_time));
+        result
     }
 
     fn closure<'a, T: Parser<'a>>(
@@ -1441,6 +1496,7 @@ impl ParserATNSimulator {
         }
 
         states.push(dfastate);
+        self.base.dfa_grown();
 
         //        if key != new_hash {
         dfa.states_map
@@ -1461,6 +1517,7 @@ impl ParserATNSimulator {
         parser: &mut T,
     ) {
         //        let ambig_index = parser.get_current_token().get_token_index();
+        self.profile(|p| p.attempting_full_context(conflicting_alts, configs));
         parser
             .get_error_lister_dispatch()
             .report_attempting_full_context(
@@ -1482,6 +1539,7 @@ impl ParserATNSimulator {
         stop_index: isize,
         parser: &mut T,
     ) {
+        self.profile(|p| p.context_sensitivity(prediction, stop_index));
         parser
             .get_error_lister_dispatch()
             .report_context_sensitivity(parser, dfa, start_index, stop_index, prediction, configs)
@@ -1497,6 +1555,7 @@ impl ParserATNSimulator {
         configs: &ATNConfigSet,
         parser: &mut T,
     ) {
+        self.profile(|p| p.ambiguity(ambig_alts, configs, stop_index));
         parser.get_error_lister_dispatch().report_ambiguity(
             parser,
             dfa,
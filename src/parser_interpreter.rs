@@ -0,0 +1,722 @@
+//! Parser that works directly with ATN, without generated code
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use better_any::{impl_tid, Tid, TidAble, TidExt};
+
+use crate::atn::ATN;
+use crate::atn_state::{ATNDecisionState, ATNState, ATNStateRef, ATNStateType};
+use crate::dfa::DFA;
+use crate::error_strategy::{DefaultErrorStrategy, ErrorStrategy};
+use crate::errors::{ANTLRError, FailedPredicateError};
+use crate::incremental::{self, Relexed, RuleLookahead};
+use crate::parser::{BaseParser, Parser, ParserNodeType, ParserRecog};
+use crate::parser_atn_simulator::ParserATNSimulator;
+use crate::parser_rule_context::{BaseParserRuleContext, ParserRuleContext};
+use crate::prediction_context::PredictionContextCache;
+use crate::recognizer::{Actions, Recognizer};
+use crate::rule_context::CustomRuleContext;
+use crate::token::{Token, TOKEN_EOF, TOKEN_MIN_USER_TOKEN_TYPE};
+use crate::token_factory::TokenFactory;
+use crate::token_stream::TokenStream;
+use crate::transition::{
+    AtomTransition, PrecedencePredicateTransition, PredicateTransition, RuleTransition,
+    TransitionType,
+};
+use crate::tree::{ErrorNode, Listenable, ParseTreeListener, TerminalNode};
+use crate::vocabulary::Vocabulary;
+
+/// Trait object type of the parse tree nodes created by `ParserInterpreter`
+pub trait InterpreterContext<'input, TF: TokenFactory<'input> + 'input>:
+    for<'x> Listenable<dyn ParseTreeListener<'input, InterpreterContextType<'input, TF>> + 'x>
+    + ParserRuleContext<'input, TF = TF, Ctx = InterpreterContextType<'input, TF>>
+{
+}
+
+impl<'input, TF: TokenFactory<'input> + 'input> InterpreterContext<'input, TF>
+    for TerminalNode<'input, InterpreterContextType<'input, TF>>
+{
+}
+
+impl<'input, TF: TokenFactory<'input> + 'input> InterpreterContext<'input, TF>
+    for ErrorNode<'input, InterpreterContextType<'input, TF>>
+{
+}
+
+#[impl_tid]
+impl<'input, TF: TokenFactory<'input> + 'input> TidAble<'input>
+    for dyn InterpreterContext<'input, TF> + 'input
+{
+}
+
+#[impl_tid]
+impl<'input, TF: TokenFactory<'input> + 'input> TidAble<'input>
+    for dyn ParseTreeListener<'input, InterpreterContextType<'input, TF>> + 'input
+{
+}
+
+/// `ParserNodeType` of the parse trees created by `ParserInterpreter`
+#[derive(Tid, Debug)]
+pub struct InterpreterContextType<'input, TF: TokenFactory<'input>>(PhantomData<&'input TF>);
+
+impl<'input, TF: TokenFactory<'input> + 'input> ParserNodeType<'input>
+    for InterpreterContextType<'input, TF>
+{
+    type TF = TF;
+    type Type = dyn InterpreterContext<'input, TF> + 'input;
+}
+
+/// Rule node of the parse tree created by `ParserInterpreter`
+pub type InterpreterRuleContext<'input, TF> =
+    BaseParserRuleContext<'input, InterpreterRuleContextExt<'input, TF>>;
+
+/// Rule context extension of `InterpreterRuleContext`,
+/// since there is no generated context type for each rule it just keeps rule index
+/// and whether there have been syntax errors while parsing it
+#[derive(Tid, Debug)]
+pub struct InterpreterRuleContextExt<'input, TF: TokenFactory<'input>> {
+    rule_index: usize,
+    // set if error has been reported inside of the rule or it has been entered
+    // in error recovery mode, such rules are not reused by incremental parsing
+    has_errors: Cell<bool>,
+    ph: PhantomData<&'input TF>,
+}
+
+impl<'input, TF: TokenFactory<'input> + 'input> CustomRuleContext<'input>
+    for InterpreterRuleContextExt<'input, TF>
+{
+    type TF = TF;
+    type Ctx = InterpreterContextType<'input, TF>;
+
+    fn get_rule_index(&self) -> usize { self.rule_index }
+}
+
+impl<'input, TF: TokenFactory<'input> + 'input> InterpreterContext<'input, TF>
+    for InterpreterRuleContext<'input, TF>
+{
+}
+
+impl<'input, 'x, TF: TokenFactory<'input> + 'input>
+    Listenable<dyn ParseTreeListener<'input, InterpreterContextType<'input, TF>> + 'x>
+    for InterpreterRuleContext<'input, TF>
+{
+    fn enter(
+        &self,
+        listener: &mut (dyn ParseTreeListener<'input, InterpreterContextType<'input, TF>> + 'x),
+    ) {
+        listener.enter_every_rule(self)
+    }
+
+    fn exit(
+        &self,
+        listener: &mut (dyn ParseTreeListener<'input, InterpreterContextType<'input, TF>> + 'x),
+    ) {
+        listener.exit_every_rule(self)
+    }
+}
+
+type InterpreterNode<'input, TF> = dyn InterpreterContext<'input, TF> + 'input;
+
+// context of the left-recursive rule invocation and its invoking state
+type ParentContext<'input, TF> = (Option<Rc<InterpreterNode<'input, TF>>>, isize);
+
+fn new_interpreter_ctx<'input, TF: TokenFactory<'input> + 'input>(
+    parent: Option<Rc<InterpreterNode<'input, TF>>>, invoking_state: isize, rule_index: usize,
+) -> Rc<InterpreterNode<'input, TF>> {
+    Rc::new(BaseParserRuleContext::new_parser_ctx(
+        parent,
+        invoking_state,
+        InterpreterRuleContextExt {
+            rule_index,
+            has_errors: Cell::new(false),
+            ph: PhantomData,
+        },
+    ))
+}
+
+fn has_errors<'input, TF: TokenFactory<'input> + 'input>(
+    node: &InterpreterNode<'input, TF>,
+) -> bool {
+    matches!(
+        node.downcast_ref::<InterpreterRuleContext<'input, TF>>(),
+        Some(it) if it.has_errors.get()
+    )
+}
+
+fn set_has_errors<'input, TF: TokenFactory<'input> + 'input>(node: &InterpreterNode<'input, TF>) {
+    if let Some(node) = node.downcast_ref::<InterpreterRuleContext<'input, TF>>() {
+        node.has_errors.set(true)
+    }
+}
+
+// subtree of the previous parse tree that can be reused by `ParserInterpreter`
+struct ReusableSubtree<'input, TF: TokenFactory<'input>> {
+    node: Rc<InterpreterNode<'input, TF>>,
+    // invoking states of the ancestors of `node`
+    invoking_states: Vec<isize>,
+    // difference between new and old token indexes of `node`
+    shift: isize,
+}
+
+struct ReusableSubtrees<'input, TF: TokenFactory<'input>> {
+    // new index of the start token -> subtrees starting there
+    subtrees: HashMap<isize, Vec<ReusableSubtree<'input, TF>>>,
+    // lookahead of the previous parse
+    lookahead: RuleLookahead,
+}
+
+/// Owned names that can be viewed as `&[&str]` as required by `Recognizer::get_rule_names`
+crate struct NameList {
+    names: Vec<String>,
+    // point into the heap data of `names`, which is never modified after creation,
+    // so it stays in place even if `NameList` is moved
+    refs: Vec<*const str>,
+}
+
+impl NameList {
+    crate fn new(names: Vec<String>) -> Self {
+        let refs = names.iter().map(|it| it.as_str() as *const str).collect();
+        Self { names, refs }
+    }
+
+    crate fn as_slice(&self) -> &[&str] {
+        debug_assert_eq!(self.names.len(), self.refs.len());
+        // SAFETY: `*const str` has the same layout as `&str`, and pointers are valid
+        // while `names` is alive, which is at least as long as the returned borrow
+        unsafe { &*(self.refs.as_slice() as *const [*const str] as *const [&str]) }
+    }
+}
+
+/// Part of `ParserInterpreter` that provides grammar information to the `BaseParser`
+pub struct ParserInterpreterExt {
+    grammar_file_name: String,
+    rule_names: NameList,
+    vocabulary: Box<dyn Vocabulary>,
+}
+
+impl std::fmt::Debug for ParserInterpreterExt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParserInterpreterExt")
+            .field("grammar_file_name", &self.grammar_file_name)
+            .field("rule_names", &self.rule_names.as_slice())
+            .finish()
+    }
+}
+
+impl<'input, I> ParserRecog<'input, BaseParserType<'input, I>> for ParserInterpreterExt where
+    I: TokenStream<'input> + TidAble<'input>
+{
+}
+
+impl<'input, I> Actions<'input, BaseParserType<'input, I>> for ParserInterpreterExt
+where
+    I: TokenStream<'input> + TidAble<'input>,
+{
+    fn get_rule_names(&self) -> &[&str] { self.rule_names.as_slice() }
+
+    fn get_vocabulary(&self) -> &dyn Vocabulary { self.vocabulary.as_ref() }
+
+    fn get_grammar_file_name(&self) -> &str { &self.grammar_file_name }
+}
+
+/// `BaseParser` type used by `ParserInterpreter` over token stream `I`
+pub type BaseParserType<'input, I> = BaseParser<
+    'input,
+    ParserInterpreterExt,
+    I,
+    InterpreterContextType<'input, <I as TokenStream<'input>>::TF>,
+    dyn ParseTreeListener<'input, InterpreterContextType<'input, <I as TokenStream<'input>>::TF>>
+        + 'input,
+>;
+
+/// Parser that recognizes input by walking ATN directly.
+///
+/// Equivalent of Java `ParserInterpreter`, allows to parse input without generated parser,
+/// given only serialized ATN and names of the rules and tokens.
+/// Resulting parse tree consists of `InterpreterRuleContext` nodes
+/// that only know their rule index.
+///
+/// Semantic predicates and actions are not available to interpreter,
+/// so predicates are considered to be always true and actions are ignored.
+/// Precedence predicates of left-recursive rules work as usual.
+pub struct ParserInterpreter<'input, I, H>
+where
+    I: TokenStream<'input> + TidAble<'input>,
+    H: ErrorStrategy<'input, BaseParserType<'input, I>>,
+{
+    base: BaseParserType<'input, I>,
+    interpreter: Arc<ParserATNSimulator>,
+    atn: Arc<ATN>,
+    /// Error strategy used by this parser
+    pub err_handler: H,
+    parent_context_stack: Vec<ParentContext<'input, I::TF>>,
+    reusable: Option<ReusableSubtrees<'input, I::TF>>,
+}
+
+impl<'input, I, H> std::fmt::Debug for ParserInterpreter<'input, I, H>
+where
+    I: TokenStream<'input> + TidAble<'input>,
+    H: ErrorStrategy<'input, BaseParserType<'input, I>>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParserInterpreter")
+            .field("ext", &*self.base)
+            .field("atn", &self.atn)
+            .finish()
+    }
+}
+
+impl<'input, I>
+    ParserInterpreter<
+        'input,
+        I,
+        DefaultErrorStrategy<'input, InterpreterContextType<'input, I::TF>>,
+    >
+where
+    I: TokenStream<'input> + TidAble<'input>,
+{
+    /// Creates interpreter for grammar with `atn` that will parse tokens from `input`.
+    ///
+    /// `rule_names` and `vocabulary` are used for error messages and parse tree
+    /// string representation, they have to be the ones `atn` was created for.
+    pub fn new(
+        grammar_file_name: &str, vocabulary: Box<dyn Vocabulary>, rule_names: Vec<String>,
+        atn: Arc<ATN>, input: I,
+    ) -> Self {
+        Self::with_strategy(
+            grammar_file_name,
+            vocabulary,
+            rule_names,
+            atn,
+            input,
+            DefaultErrorStrategy::new(),
+        )
+    }
+}
+
+impl<'input, I, H> ParserInterpreter<'input, I, H>
+where
+    I: TokenStream<'input> + TidAble<'input>,
+    H: ErrorStrategy<'input, BaseParserType<'input, I>>,
+{
+    /// Same as `ParserInterpreter::new` but with custom error strategy
+    pub fn with_strategy(
+        grammar_file_name: &str, vocabulary: Box<dyn Vocabulary>, rule_names: Vec<String>,
+        atn: Arc<ATN>, input: I, strategy: H,
+    ) -> Self {
+        let decision_to_dfa = (0..atn.decision_to_state.len())
+            .map(|i| DFA::new(atn.clone(), atn.get_decision_state(i), i as isize).into())
+            .collect();
+        let interpreter = Arc::new(ParserATNSimulator::new(
+            atn.clone(),
+            Arc::new(decision_to_dfa),
+            Arc::new(PredictionContextCache::new()),
+        ));
+        let ext = ParserInterpreterExt {
+            grammar_file_name: grammar_file_name.to_owned(),
+            rule_names: NameList::new(rule_names),
+            vocabulary,
+        };
+        Self {
+            base: BaseParser::new_base_parser(input, interpreter.clone(), ext),
+            interpreter,
+            atn,
+            err_handler: strategy,
+            parent_context_stack: Vec::new(),
+            reusable: None,
+        }
+    }
+
+    /// Makes parser reuse subtrees of the `previous` parse tree instead of parsing them again,
+    /// see `incremental` module.
+    ///
+    /// `previous` must have been created by `ParserInterpreter` for the same ATN from the
+    /// tokens `relexed` has been created from, with `lookahead` tracked by
+    /// `BaseParser::set_track_lookahead`. This parser must parse `relexed.tokens`.
+    ///
+    /// Subtree is reused if it has been parsed without examining relexed tokens and
+    /// without syntax errors, and parser invokes its rule at the same token from the
+    /// same ATN state with the same rule invocation stack.
+    /// Reused subtrees are rebuilt over the new tokens, parse listeners are not notified
+    /// about them. Subtrees are reused by all following `parse` calls.
+    pub fn set_reusable_subtrees<Tok>(
+        &mut self, previous: &InterpreterNode<'input, I::TF>, lookahead: &RuleLookahead,
+        relexed: &Relexed<Tok>,
+    ) {
+        let reusable = incremental::find_reusable_subtrees_by(
+            previous,
+            lookahead,
+            &relexed.old_range,
+            |node| {
+                let interval = node.get_source_interval();
+                !has_errors(node)
+                    && interval.a >= 0
+                    && interval.a <= interval.b
+                    && node.stop().borrow().get_token_type() != TOKEN_EOF
+            },
+        );
+        let mut subtrees = HashMap::<_, Vec<_>>::new();
+        for node in reusable {
+            let start = node.get_source_interval().a;
+            let shift = relexed.new_index(start) - start;
+            let mut invoking_states = Vec::new();
+            let mut parent = node.get_parent_ctx();
+            while let Some(ctx) = parent {
+                invoking_states.push(ctx.get_invoking_state());
+                parent = ctx.get_parent_ctx();
+            }
+            subtrees
+                .entry(start + shift)
+                .or_default()
+                .push(ReusableSubtree {
+                    node,
+                    invoking_states,
+                    shift,
+                });
+        }
+        self.reusable = Some(ReusableSubtrees {
+            subtrees,
+            lookahead: lookahead.clone(),
+        });
+    }
+
+    /// Parses input starting from the rule with `start_rule_index`
+    /// and returns resulting parse tree
+    pub fn parse(
+        &mut self, start_rule_index: usize,
+    ) -> Result<Rc<InterpreterNode<'input, I::TF>>, ANTLRError> {
+        let atn = self.atn.clone();
+        let start_state = atn.rule_to_start_state[start_rule_index];
+        let root_ctx = new_interpreter_ctx(None, -1, start_rule_index);
+        let left_recursive = is_left_recursive(&atn, start_state);
+        if left_recursive {
+            self.enter_recursion_rule(root_ctx.clone(), start_state, start_rule_index, 0);
+        } else {
+            self.base
+                .enter_rule(root_ctx.clone(), start_state as isize, start_rule_index);
+        }
+        self.base.trigger_enter_rule_event();
+
+        loop {
+            let state = atn.states[self.base.get_state() as usize].as_ref();
+            if let ATNStateType::RuleStopState = state.get_state_type() {
+                // pop; return from rule
+                if self.base.get_parser_rule_context().get_invoking_state() == -1 {
+                    return if left_recursive {
+                        let result = self.base.ctx.clone().unwrap();
+                        let (parent, _) = self.parent_context_stack.pop().unwrap();
+                        self.base.unroll_recursion_context(parent);
+                        Ok(result)
+                    } else {
+                        self.base.exit_rule();
+                        Ok(root_ctx)
+                    };
+                }
+                self.visit_rule_stop_state(state);
+                continue;
+            }
+
+            let errors = self.base.get_number_of_syntax_errors();
+            let ctx = self.base.ctx.clone();
+            match self.visit_state(state) {
+                Ok(()) => {}
+                Err(e @ ANTLRError::FallThrough(_)) => return Err(e),
+                Err(ref e) => {
+                    let stop_state = atn.rule_to_stop_state[state.get_rule_index()];
+                    self.base.set_state(stop_state as isize);
+                    self.err_handler.report_error(&mut self.base, e);
+                    self.err_handler.recover(&mut self.base, e)?;
+                }
+            }
+            if self.base.get_number_of_syntax_errors() != errors {
+                let mut ctx = ctx;
+                while let Some(node) = ctx {
+                    set_has_errors(&*node);
+                    ctx = node.get_parent_ctx();
+                }
+            }
+        }
+    }
+
+    fn enter_recursion_rule(
+        &mut self, localctx: Rc<InterpreterNode<'input, I::TF>>, state: ATNStateRef,
+        rule_index: usize, precedence: isize,
+    ) {
+        self.parent_context_stack
+            .push((self.base.ctx.clone(), localctx.get_invoking_state()));
+        self.base
+            .enter_recursion_rule(localctx, state as isize, rule_index, precedence);
+    }
+
+    fn visit_state(&mut self, p: &dyn ATNState) -> Result<(), ANTLRError> {
+        let mut predicted_alt = 1;
+        if let ATNStateType::DecisionState { decision, .. } = p.get_state_type() {
+            if p.get_transitions().len() > 1 {
+                self.err_handler.sync(&mut self.base)?;
+                predicted_alt = self
+                    .interpreter
+                    .adaptive_predict(*decision, &mut self.base)?;
+            }
+        }
+
+        let atn = self.atn.clone();
+        let transition = p.get_transitions()[predicted_alt as usize - 1].as_ref();
+        match transition.get_serialization_type() {
+            TransitionType::TRANSITION_EPSILON => {
+                if let ATNStateType::DecisionState {
+                    state:
+                        ATNDecisionState::StarLoopEntry {
+                            is_precedence: true,
+                            ..
+                        },
+                    ..
+                } = p.get_state_type()
+                {
+                    if let ATNStateType::LoopEndState(_) =
+                        atn.states[transition.get_target()].get_state_type()
+                    {
+                    } else {
+                        // We are at the start of a left recursive rule's (...)* loop
+                        // and we're not taking the exit branch of loop.
+                        let (parent, invoking_state) = self.parent_context_stack.last().unwrap();
+                        let rule_index = self.base.get_parser_rule_context().get_rule_index();
+                        let localctx =
+                            new_interpreter_ctx(parent.clone(), *invoking_state, rule_index);
+                        if has_errors(self.base.ctx.as_deref().unwrap()) {
+                            set_has_errors(&*localctx);
+                        }
+                        self.base.trigger_exit_rule_event();
+                        self.base.push_new_recursion_context(
+                            localctx,
+                            atn.rule_to_start_state[p.get_rule_index()] as isize,
+                            rule_index,
+                        );
+                    }
+                }
+            }
+            TransitionType::TRANSITION_ATOM => {
+                let label = transition.cast::<AtomTransition>().label;
+                self.base.match_token(label, &mut self.err_handler)?;
+            }
+            TransitionType::TRANSITION_RANGE
+            | TransitionType::TRANSITION_SET
+            | TransitionType::TRANSITION_NOTSET => {
+                let la = self.base.input.la(1);
+                if !transition.matches(la, TOKEN_MIN_USER_TOKEN_TYPE, 65535) {
+                    self.err_handler.recover_inline(&mut self.base)?;
+                }
+                self.base.match_wildcard(&mut self.err_handler)?;
+            }
+            TransitionType::TRANSITION_WILDCARD => {
+                self.base.match_wildcard(&mut self.err_handler)?;
+            }
+            TransitionType::TRANSITION_RULE => {
+                let transition = transition.cast::<RuleTransition>();
+                let rule_start = transition.target;
+                let rule_index = atn.states[rule_start].get_rule_index();
+                if self.reuse_subtree(p.get_state_number() as isize, rule_index) {
+                    self.base.set_state(transition.follow_state as isize);
+                    return Ok(());
+                }
+                let newctx = new_interpreter_ctx(
+                    self.base.ctx.clone(),
+                    p.get_state_number() as isize,
+                    rule_index,
+                );
+                if self.err_handler.in_error_recovery_mode(&mut self.base) {
+                    set_has_errors(&*newctx);
+                }
+                if is_left_recursive(&atn, rule_start) {
+                    self.enter_recursion_rule(
+                        newctx,
+                        rule_start,
+                        rule_index,
+                        transition.precedence,
+                    );
+                } else {
+                    self.base
+                        .enter_rule(newctx, rule_start as isize, rule_index);
+                }
+                self.base.trigger_enter_rule_event();
+            }
+            TransitionType::TRANSITION_PREDICATE => {
+                let transition = transition.cast::<PredicateTransition>();
+                let ctx = self.base.ctx.clone();
+                if !self
+                    .base
+                    .sempred(ctx.as_deref(), transition.rule_index, transition.pred_index)
+                {
+                    return Err(FailedPredicateError::new(&mut self.base, None, None));
+                }
+            }
+            TransitionType::TRANSITION_ACTION => {
+                // actions can't be executed without generated code
+            }
+            TransitionType::TRANSITION_PRECEDENCE => {
+                let precedence = transition
+                    .cast::<PrecedencePredicateTransition>()
+                    .precedence;
+                let ctx = self.base.ctx.clone();
+                if !self.base.precpred(ctx.as_deref(), precedence) {
+                    return Err(FailedPredicateError::new(
+                        &mut self.base,
+                        Some(format!("precpred(_ctx, {})", precedence)),
+                        None,
+                    ));
+                }
+            }
+        }
+
+        self.base.set_state(transition.get_target() as isize);
+        Ok(())
+    }
+
+    // adds reusable subtree of the rule invoked from `invoking_state` at the current token
+    // to the parse tree and skips its tokens, returns false if there is no such subtree
+    fn reuse_subtree(&mut self, invoking_state: isize, rule_index: usize) -> bool {
+        let reusable = match self.reusable.take() {
+            Some(reusable) => reusable,
+            None => return false,
+        };
+        let reused = self.try_reuse_subtree(&reusable, invoking_state, rule_index);
+        self.reusable = Some(reusable);
+        reused
+    }
+
+    fn try_reuse_subtree(
+        &mut self, reusable: &ReusableSubtrees<'input, I::TF>, invoking_state: isize,
+        rule_index: usize,
+    ) -> bool {
+        let start = match self.base.input.lt(1) {
+            Some(token) => token.borrow().get_token_index(),
+            None => return false,
+        };
+        let candidates = match reusable.subtrees.get(&start) {
+            Some(candidates) => candidates,
+            None => return false,
+        };
+        // errors inside of the subtree could be suppressed in error recovery mode
+        if self.err_handler.in_error_recovery_mode(&mut self.base) {
+            return false;
+        }
+        let subtree = candidates.iter().find(|it| {
+            it.node.get_rule_index() == rule_index
+                && it.node.get_invoking_state() == invoking_state
+                && self.is_invoked_from(&it.invoking_states)
+        });
+        let subtree = match subtree {
+            Some(subtree) => subtree,
+            None => return false,
+        };
+
+        // continue after the subtree, `seek` can't be used because tokens might not be fetched yet
+        let stop = subtree.node.get_source_interval().b + subtree.shift;
+        while self.base.input.index() <= stop {
+            self.base.input.consume();
+        }
+        // fetch tokens examined by the subtree, so they are counted in the lookahead
+        // of the enclosing rules
+        if let Some(lookahead) = reusable.lookahead.get(&*subtree.node) {
+            let mut k = 1;
+            while self.base.input.size() <= lookahead + subtree.shift
+                && self.base.input.la(k) != TOKEN_EOF
+            {
+                k += 1;
+            }
+        }
+
+        let parent = self.base.ctx.clone();
+        let node = self.copy_subtree(&subtree.node, parent, subtree.shift, &reusable.lookahead);
+        if self.base.build_parse_trees {
+            self.base.ctx.as_ref().unwrap().add_child(node);
+        }
+        true
+    }
+
+    // whether rule invoked now would have ancestors with `invoking_states`
+    fn is_invoked_from(&self, invoking_states: &[isize]) -> bool {
+        let mut ctx = self.base.ctx.clone();
+        for &state in invoking_states {
+            match ctx {
+                Some(node) if node.get_invoking_state() == state => ctx = node.get_parent_ctx(),
+                _ => return false,
+            }
+        }
+        ctx.is_none()
+    }
+
+    // copies `node` of the previous tree with tokens at indexes shifted by `shift`
+    fn copy_subtree(
+        &mut self, node: &Rc<InterpreterNode<'input, I::TF>>,
+        parent: Option<Rc<InterpreterNode<'input, I::TF>>>, shift: isize,
+        lookahead: &RuleLookahead,
+    ) -> Rc<InterpreterNode<'input, I::TF>> {
+        let interval = node.get_source_interval();
+        if node.get_rule_index() == usize::MAX {
+            let token = self.base.input.get(interval.a + shift).clone();
+            return Rc::new(TerminalNode::new(token));
+        }
+        let ctx = new_interpreter_ctx(parent, node.get_invoking_state(), node.get_rule_index());
+        ctx.set_alt_number(node.get_alt_number());
+        ctx.set_start(Some(self.base.input.get(interval.a + shift).clone()));
+        ctx.set_stop(Some(self.base.input.get(interval.b + shift).clone()));
+        for child in node.get_children() {
+            let child = self.copy_subtree(&child, Some(ctx.clone()), shift, lookahead);
+            ctx.add_child(child);
+        }
+        if let (Some(old), Some(new)) = (lookahead.get(&**node), self.base.get_rule_lookahead_mut())
+        {
+            new.record(&*ctx, old + shift);
+        }
+        ctx
+    }
+
+    fn visit_rule_stop_state(&mut self, p: &dyn ATNState) {
+        let rule_start = self.atn.rule_to_start_state[p.get_rule_index()];
+        if is_left_recursive(&self.atn, rule_start) {
+            let (parent, invoking_state) = self.parent_context_stack.pop().unwrap();
+            self.base.unroll_recursion_context(parent);
+            self.base.set_state(invoking_state);
+        } else {
+            self.base.exit_rule();
+        }
+
+        let atn = self.atn.clone();
+        let rule_transition = atn.states[self.base.get_state() as usize].get_transitions()[0]
+            .cast::<RuleTransition>();
+        self.base.set_state(rule_transition.follow_state as isize);
+    }
+}
+
+fn is_left_recursive(atn: &ATN, rule_start: ATNStateRef) -> bool {
+    matches!(
+        atn.states[rule_start].get_state_type(),
+        ATNStateType::RuleStartState {
+            is_left_recursive: true,
+            ..
+        }
+    )
+}
+
+impl<'input, I, H> Deref for ParserInterpreter<'input, I, H>
+where
+    I: TokenStream<'input> + TidAble<'input>,
+    H: ErrorStrategy<'input, BaseParserType<'input, I>>,
+{
+    type Target = BaseParserType<'input, I>;
+
+    fn deref(&self) -> &Self::Target { &self.base }
+}
+
+impl<'input, I, H> DerefMut for ParserInterpreter<'input, I, H>
+where
+    I: TokenStream<'input> + TidAble<'input>,
+    H: ErrorStrategy<'input, BaseParserType<'input, I>>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target { &mut self.base }
+}
@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{Display, Error, Formatter};
 use std::hash::{BuildHasher, Hash, Hasher};
+use std::mem::size_of;
 use std::ops::Deref;
 
 use std::sync::{Arc, RwLock};
@@ -648,4 +649,26 @@ impl PredictionContextCache {
 
     #[doc(hidden)]
     pub fn length(&self) -> usize { self.cache.read().unwrap().len() }
+
+    #[doc(hidden)]
+    pub fn clear(&self) {
+        *self.cache.write().unwrap() = HashMap::with_hasher(MurmurHasherBuilder {});
+    }
+
+    pub(crate) fn approximate_size(&self) -> usize {
+        let cache = self.cache.read().unwrap();
+        let contexts = cache
+            .keys()
+            .map(|ctx| {
+                // reference counters of the Arc
+                let mut size = size_of::<PredictionContext>() + 2 * size_of::<usize>();
+                if let Array(array) = ctx.deref() {
+                    size += array.parents.capacity() * size_of::<Option<Arc<PredictionContext>>>()
+                        + array.return_states.capacity() * size_of::<isize>();
+                }
+                size
+            })
+            .sum::<usize>();
+        contexts + cache.capacity() * (2 * size_of::<Arc<PredictionContext>>() + 1)
+    }
 }
@@ -0,0 +1,360 @@
+//! Per decision statistics of the adaptive prediction
+//!
+//! Equivalent of Java's `ProfilingATNSimulator`. Profiling is enabled on the parser's
+//! `ParserATNSimulator`, and collected statistics can be retrieved after parsing:
+//! ```text
+//! parser.get_interpreter().set_profile(true);
+//! parser.parse()?;
+//! let info = parser.get_interpreter().get_parse_info().unwrap();
+//! for decision in info.get_decision_info().iter().filter(|it| it.invocations > 0) {
+//!     println!("{}: {} invocations, max SLL lookahead {}",
+//!         decision.decision, decision.invocations, decision.sll_max_look);
+//! }
+//! ```
+//! Lookahead depth is the number of tokens examined by a single prediction,
+//! including the current one.
+use std::time::{Duration, Instant};
+
+use bit_set::BitSet;
+
+use crate::atn::INVALID_ALT;
+use crate::atn_config_set::ATNConfigSet;
+use crate::semantic_context::SemanticContext;
+
+/// Common information about the event that happened during prediction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecisionEventInfo {
+    /// Decision number
+    pub decision: usize,
+    /// Index of the token where prediction started
+    pub start_index: isize,
+    /// Index of the token where event was detected
+    pub stop_index: isize,
+    /// Whether event happened during full context (LL) prediction
+    pub full_ctx: bool,
+}
+
+/// Ambiguity detected during prediction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmbiguityInfo {
+    /// Where ambiguity was detected
+    pub event: DecisionEventInfo,
+    /// Alternatives that can match the input
+    pub ambig_alts: BitSet,
+}
+
+/// Evaluation of the semantic predicate during prediction
+#[derive(Clone, Debug)]
+pub struct PredicateEvalInfo {
+    /// Where predicate was evaluated
+    pub event: DecisionEventInfo,
+    /// Evaluated predicate
+    pub semctx: SemanticContext,
+    /// Alternative guarded by the predicate
+    pub predicted_alt: isize,
+    /// Result of the predicate
+    pub eval_result: bool,
+    /// Time spent evaluating the predicate
+    pub eval_time: Duration,
+}
+
+/// Prediction that required the largest lookahead depth for the decision
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookaheadEventInfo {
+    /// Where prediction started and stopped
+    pub event: DecisionEventInfo,
+    /// Alternative chosen by the prediction
+    pub predicted_alt: isize,
+}
+
+/// Statistics of a single decision
+#[derive(Clone, Debug, Default)]
+pub struct DecisionInfo {
+    /// Decision number
+    pub decision: usize,
+    /// Number of times `adaptive_predict` was called for this decision
+    pub invocations: usize,
+    /// Total time spent in `adaptive_predict` for this decision
+    pub time_in_prediction: Duration,
+
+    /// Sum of SLL lookahead depths of all predictions
+    pub sll_total_look: usize,
+    /// Minimal SLL lookahead depth
+    pub sll_min_look: usize,
+    /// Maximal SLL lookahead depth
+    pub sll_max_look: usize,
+    /// Prediction with the maximal SLL lookahead depth
+    pub sll_max_look_event: Option<LookaheadEventInfo>,
+
+    /// Sum of full context lookahead depths of predictions that fell back to full context
+    pub ll_total_look: usize,
+    /// Minimal full context lookahead depth
+    pub ll_min_look: usize,
+    /// Maximal full context lookahead depth
+    pub ll_max_look: usize,
+    /// Prediction with the maximal full context lookahead depth
+    pub ll_max_look_event: Option<LookaheadEventInfo>,
+
+    /// Number of SLL steps that required ATN simulation, i.e. DFA had no edge for the token
+    pub sll_atn_transitions: usize,
+    /// Number of SLL steps that used existing DFA edge
+    pub sll_dfa_transitions: usize,
+    /// Number of times SLL prediction found a conflict and fell back to full context prediction
+    pub ll_fallback: usize,
+    /// Number of full context prediction steps, all of them require ATN simulation
+    pub ll_atn_transitions: usize,
+
+    /// Predictions where full context prediction resolved to a different alternative than SLL
+    pub context_sensitivities: Vec<DecisionEventInfo>,
+    /// Predictions that found no viable alternative
+    pub errors: Vec<DecisionEventInfo>,
+    /// Ambiguities reported during prediction
+    pub ambiguities: Vec<AmbiguityInfo>,
+    /// Semantic predicates evaluated during prediction, precedence predicates are not included
+    pub predicate_evals: Vec<PredicateEvalInfo>,
+}
+
+impl DecisionInfo {
+    fn new(decision: usize) -> DecisionInfo {
+        DecisionInfo {
+            decision,
+            ..Default::default()
+        }
+    }
+}
+
+/// Profiling results of all decisions
+#[derive(Clone, Debug)]
+pub struct ParseInfo {
+    decisions: Vec<DecisionInfo>,
+}
+
+impl ParseInfo {
+    /// Statistics of each decision, index is the decision number
+    pub fn get_decision_info(&self) -> &[DecisionInfo] { &self.decisions }
+
+    /// Decisions that required full context prediction at least once
+    pub fn get_ll_decisions(&self) -> Vec<usize> {
+        self.decisions
+            .iter()
+            .filter(|it| it.ll_fallback > 0)
+            .map(|it| it.decision)
+            .collect()
+    }
+
+    /// Total time spent in prediction by all decisions
+    pub fn get_total_time_in_prediction(&self) -> Duration {
+        self.decisions.iter().map(|it| it.time_in_prediction).sum()
+    }
+
+    /// Total number of tokens examined by SLL prediction
+    pub fn get_total_sll_lookahead_ops(&self) -> usize {
+        self.decisions.iter().map(|it| it.sll_total_look).sum()
+    }
+
+    /// Total number of tokens examined by full context prediction
+    pub fn get_total_ll_lookahead_ops(&self) -> usize {
+        self.decisions.iter().map(|it| it.ll_total_look).sum()
+    }
+
+    /// Total number of SLL steps that required ATN simulation
+    pub fn get_total_sll_atn_lookahead_ops(&self) -> usize {
+        self.decisions.iter().map(|it| it.sll_atn_transitions).sum()
+    }
+
+    /// Total number of full context steps
+    pub fn get_total_ll_atn_lookahead_ops(&self) -> usize {
+        self.decisions.iter().map(|it| it.ll_atn_transitions).sum()
+    }
+
+    /// Total number of steps that required ATN simulation
+    pub fn get_total_atn_lookahead_ops(&self) -> usize {
+        self.get_total_sll_atn_lookahead_ops() + self.get_total_ll_atn_lookahead_ops()
+    }
+}
+
+/// Collects statistics while `ParserATNSimulator` is in profiling mode
+#[derive(Debug)]
+pub(crate) struct Profiler {
+    decisions: Vec<DecisionInfo>,
+    current_decision: usize,
+    prediction_start: Instant,
+    start_index: isize,
+    sll_stop_index: isize,
+    ll_stop_index: isize,
+    conflicting_alt_resolved_by_sll: isize,
+}
+
+impl Profiler {
+    pub(crate) fn new(decisions: usize) -> Profiler {
+        Profiler {
+            decisions: (0..decisions).map(DecisionInfo::new).collect(),
+            current_decision: 0,
+            prediction_start: Instant::now(),
+            start_index: -1,
+            sll_stop_index: -1,
+            ll_stop_index: -1,
+            conflicting_alt_resolved_by_sll: INVALID_ALT,
+        }
+    }
+
+    pub(crate) fn parse_info(&self) -> ParseInfo {
+        ParseInfo {
+            decisions: self.decisions.clone(),
+        }
+    }
+
+    fn current(&mut self) -> &mut DecisionInfo { &mut self.decisions[self.current_decision] }
+
+    fn event(&self, stop_index: isize, full_ctx: bool) -> DecisionEventInfo {
+        DecisionEventInfo {
+            decision: self.current_decision,
+            start_index: self.start_index,
+            stop_index,
+            full_ctx,
+        }
+    }
+
+    pub(crate) fn start_prediction(&mut self, decision: usize, start_index: isize) {
+        self.current_decision = decision;
+        self.start_index = start_index;
+        self.sll_stop_index = -1;
+        self.ll_stop_index = -1;
+        self.prediction_start = Instant::now();
+    }
+
+    pub(crate) fn end_prediction(&mut self, alt: isize) {
+        let elapsed = self.prediction_start.elapsed();
+        let sll_k = (self.sll_stop_index - self.start_index + 1) as usize;
+        let sll_event = self.event(self.sll_stop_index, false);
+        let ll = if self.ll_stop_index >= 0 {
+            let ll_k = (self.ll_stop_index - self.start_index + 1) as usize;
+            Some((ll_k, self.event(self.ll_stop_index, true)))
+        } else {
+            None
+        };
+
+        let info = self.current();
+        info.time_in_prediction += elapsed;
+        info.invocations += 1;
+
+        info.sll_total_look += sll_k;
+        if info.sll_min_look == 0 || sll_k < info.sll_min_look {
+            info.sll_min_look = sll_k;
+        }
+        if sll_k > info.sll_max_look {
+            info.sll_max_look = sll_k;
+            info.sll_max_look_event = Some(LookaheadEventInfo {
+                event: sll_event,
+                predicted_alt: alt,
+            });
+        }
+
+        if let Some((ll_k, ll_event)) = ll {
+            info.ll_total_look += ll_k;
+            if info.ll_min_look == 0 || ll_k < info.ll_min_look {
+                info.ll_min_look = ll_k;
+            }
+            if ll_k > info.ll_max_look {
+                info.ll_max_look = ll_k;
+                info.ll_max_look_event = Some(LookaheadEventInfo {
+                    event: ll_event,
+                    predicted_alt: alt,
+                });
+            }
+        }
+    }
+
+    /// SLL prediction reached the token at `index`, `existing` is whether DFA already had an edge
+    /// for it, `error` is whether that edge leads to the error state
+    pub(crate) fn sll_step(&mut self, index: isize, existing: bool, error: bool) {
+        self.sll_stop_index = index;
+        if existing {
+            self.current().sll_dfa_transitions += 1;
+            if error {
+                let event = self.event(index, false);
+                self.current().errors.push(event);
+            }
+        }
+    }
+
+    /// Full context prediction reached the token at `index`
+    pub(crate) fn ll_step(&mut self, index: isize) { self.ll_stop_index = index; }
+
+    /// ATN simulation step has been done, `reached` is false if no configuration
+    /// could match the current token
+    pub(crate) fn atn_transition(&mut self, full_ctx: bool, reached: bool) {
+        let stop_index = if full_ctx {
+            self.current().ll_atn_transitions += 1;
+            self.ll_stop_index
+        } else {
+            self.current().sll_atn_transitions += 1;
+            self.sll_stop_index
+        };
+        if !reached {
+            let event = self.event(stop_index, full_ctx);
+            self.current().errors.push(event);
+        }
+    }
+
+    pub(crate) fn predicate_evaluated(
+        &mut self, pred: &SemanticContext, alt: isize, full_ctx: bool, result: bool,
+        eval_time: Duration,
+    ) {
+        if let SemanticContext::Precedence(_) = pred {
+            return;
+        }
+        let stop_index = if self.ll_stop_index >= 0 {
+            self.ll_stop_index
+        } else {
+            self.sll_stop_index
+        };
+        let info = PredicateEvalInfo {
+            event: self.event(stop_index, full_ctx),
+            semctx: pred.clone(),
+            predicted_alt: alt,
+            eval_result: result,
+            eval_time,
+        };
+        self.current().predicate_evals.push(info);
+    }
+
+    pub(crate) fn attempting_full_context(
+        &mut self, conflicting_alts: &BitSet, configs: &ATNConfigSet,
+    ) {
+        self.conflicting_alt_resolved_by_sll = if conflicting_alts.is_empty() {
+            configs.get_alts().iter().next()
+        } else {
+            conflicting_alts.iter().next()
+        }
+        .map_or(INVALID_ALT, |it| it as isize);
+        self.current().ll_fallback += 1;
+    }
+
+    pub(crate) fn context_sensitivity(&mut self, prediction: isize, stop_index: isize) {
+        if prediction != self.conflicting_alt_resolved_by_sll {
+            let event = self.event(stop_index, true);
+            self.current().context_sensitivities.push(event);
+        }
+    }
+
+    pub(crate) fn ambiguity(
+        &mut self, ambig_alts: &BitSet, configs: &ATNConfigSet, stop_index: isize,
+    ) {
+        let full_ctx = configs.full_context();
+        let prediction = ambig_alts
+            .iter()
+            .next()
+            .map_or(INVALID_ALT, |it| it as isize);
+        // SLL and LL both found a conflict, but if they resolve it to different
+        // alternatives it is also a context sensitivity
+        if full_ctx {
+            self.context_sensitivity(prediction, stop_index);
+        }
+        let event = self.event(stop_index, full_ctx);
+        self.current().ambiguities.push(AmbiguityInfo {
+            event,
+            ambig_alts: ambig_alts.clone(),
+        });
+    }
+}
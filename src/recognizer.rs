@@ -1,4 +1,5 @@
 use crate::atn::ATN;
+use crate::int_stream::UNKNOWN_SOURCE_NAME;
 use crate::parser::ParserNodeType;
 
 use crate::token_factory::TokenAware;
@@ -52,6 +53,12 @@ pub trait Recognizer<'input>: TokenAware<'input> {
     /// Name of the file this recognizer was generated from
     fn get_grammar_file_name(&self) -> &str { "" }
     fn get_atn(&self) -> &ATN { unimplemented!() }
+
+    /// Name of the source current input is coming from, e.g. file name.
+    /// Used by error listeners to tell which input an error belongs to.
+    ///
+    /// Returns `UNKNOWN_SOURCE_NAME` if input source is not known
+    fn get_input_source_name(&self) -> String { UNKNOWN_SOURCE_NAME.to_owned() }
 }
 
 /// **! Usually generated by ANTLR !**
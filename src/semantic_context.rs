@@ -2,6 +2,7 @@ use std::borrow::Cow::{Borrowed, Owned};
 use std::borrow::{Borrow, Cow};
 use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 
 use crate::parser::{Parser, ParserNodeType};
 
@@ -232,6 +233,30 @@ impl SemanticContext {
     }
 }
 
+impl Display for SemanticContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let join = |f: &mut Formatter<'_>, operands: &[SemanticContext], separator: &str| {
+            for (i, operand) in operands.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(separator)?;
+                }
+                write!(f, "{}", operand)?;
+            }
+            Ok(())
+        };
+        match self {
+            SemanticContext::Predicate {
+                rule_index,
+                pred_index,
+                ..
+            } => write!(f, "{{{}:{}}}?", rule_index, pred_index),
+            SemanticContext::Precedence(precedence) => write!(f, "{{{}>=prec}}?", precedence),
+            SemanticContext::AND(operands) => join(f, operands, "&&"),
+            SemanticContext::OR(operands) => join(f, operands, "||"),
+        }
+    }
+}
+
 fn sort_prec_pred(a: &&SemanticContext, b: &&SemanticContext) -> Ordering {
     match (*a, *b) {
         (SemanticContext::Precedence(a), SemanticContext::Precedence(b)) => a.cmp(b),
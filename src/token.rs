@@ -8,7 +8,7 @@ use std::sync::atomic::{AtomicIsize, Ordering};
 
 use crate::char_stream::InputData;
 use crate::int_stream::EOF;
-use crate::token_factory::{INVALID_COMMON, INVALID_OWNING};
+use crate::token_factory::{INVALID_COMMON, INVALID_OWNING, INVALID_UTF8};
 
 use better_any::type_id;
 
@@ -74,6 +74,7 @@ pub type CommonToken<'a> = GenericToken<Cow<'a, str>>;
 
 type_id!(OwningToken);
 type_id!(CommonToken<'a>);
+type_id!(Utf8Token<'a>);
 
 #[derive(Debug)]
 #[allow(missing_docs)]
@@ -188,6 +189,47 @@ impl<T: Borrow<str> + Debug> Token for GenericToken<T> {
     }
 }
 
+/// `CommonToken` that also has byte offsets in the UTF-8 input, produced by `Utf8TokenFactory`
+#[derive(Clone, Debug)]
+pub struct Utf8Token<'a> {
+    /// Token with code point positions
+    pub base: CommonToken<'a>,
+    /// Byte offset of the first character of the token,
+    /// -1 if token has not been created from the input, e.g. EOF or missing token
+    pub byte_start: isize,
+    /// Byte offset right after the last character of the token,
+    /// -1 if token has not been created from the input
+    pub byte_end: isize,
+}
+
+impl Display for Utf8Token<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result { Display::fmt(&self.base, f) }
+}
+
+impl Token for Utf8Token<'_> {
+    type Data = str;
+
+    fn get_token_type(&self) -> isize { self.base.get_token_type() }
+
+    fn get_channel(&self) -> isize { self.base.get_channel() }
+
+    fn get_start(&self) -> isize { self.base.get_start() }
+
+    fn get_stop(&self) -> isize { self.base.get_stop() }
+
+    fn get_line(&self) -> isize { self.base.get_line() }
+
+    fn get_column(&self) -> isize { self.base.get_column() }
+
+    fn get_text(&self) -> &str { self.base.get_text() }
+
+    fn get_token_index(&self) -> isize { self.base.get_token_index() }
+
+    fn set_token_index(&self, v: isize) { self.base.set_token_index(v) }
+
+    fn to_owned(&self) -> OwningToken { Token::to_owned(&self.base) }
+}
+
 impl Default for &'_ OwningToken {
     fn default() -> Self { &**INVALID_OWNING }
 }
@@ -196,6 +238,10 @@ impl Default for &'_ CommonToken<'_> {
     fn default() -> Self { &**INVALID_COMMON }
 }
 
+impl Default for &'_ Utf8Token<'_> {
+    fn default() -> Self { &**INVALID_UTF8 }
+}
+
 //
 // impl CommonToken {
 //     fn new_common_token(
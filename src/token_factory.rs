@@ -11,7 +11,8 @@ use typed_arena::Arena;
 
 use crate::char_stream::{CharStream, InputData};
 use crate::token::Token;
-use crate::token::{CommonToken, OwningToken, TOKEN_INVALID_TYPE};
+use crate::token::{CommonToken, OwningToken, Utf8Token, TOKEN_INVALID_TYPE};
+use crate::utf8_char_stream::Utf8Slice;
 use better_any::{Tid, TidAble};
 
 #[allow(non_upper_case_globals)]
@@ -40,6 +41,11 @@ lazy_static! {
         text: Borrowed("<invalid>"),
         read_only: true,
     });
+    pub(crate) static ref INVALID_UTF8: Box<Utf8Token<'static>> = Box::new(Utf8Token {
+        base: (**INVALID_COMMON).clone(),
+        byte_start: -1,
+        byte_end: -1,
+    });
 }
 
 /// Trait for creating tokens.
@@ -192,6 +198,76 @@ impl<'a> TokenFactory<'a> for OwningTokenFactory {
     fn get_data(from: Self::From) -> Cow<'a, Self::Data> { from.into() }
 }
 
+/// Token factory that produces `Utf8Token`s, which have both code point and byte positions.
+///
+/// Byte offsets are taken from the `Utf8CharStream` tokens are created from,
+/// and token text is a zero-copy slice of the input.
+#[derive(Default, Tid, Debug)]
+pub struct Utf8TokenFactory;
+
+impl<'a> TokenFactory<'a> for Utf8TokenFactory {
+    type Inner = Utf8Token<'a>;
+    type Tok = Box<Self::Inner>;
+    type Data = str;
+    type From = Utf8Slice<'a>;
+
+    #[inline]
+    fn create<T>(
+        &'a self,
+        source: Option<&mut T>,
+        ttype: isize,
+        text: Option<String>,
+        channel: isize,
+        start: isize,
+        stop: isize,
+        line: isize,
+        column: isize,
+    ) -> Self::Tok
+    where
+        T: CharStream<Self::From> + ?Sized,
+    {
+        let slice = source.map(|x| {
+            if stop >= x.size() || start >= x.size() {
+                None
+            } else {
+                Some(x.get_text(start, stop))
+            }
+        });
+        let text = match (text, slice) {
+            (Some(t), _) => Owned(t),
+            (None, Some(Some(slice))) => Borrowed(slice.text),
+            (None, Some(None)) => Borrowed("<EOF>"),
+            (None, None) => Borrowed(""),
+        };
+        let (byte_start, byte_end) = match slice {
+            Some(Some(slice)) => (
+                slice.byte_start as isize,
+                (slice.byte_start + slice.text.len()) as isize,
+            ),
+            _ => (-1, -1),
+        };
+        Box::new(Utf8Token {
+            base: CommonToken {
+                token_type: ttype,
+                channel,
+                start,
+                stop,
+                token_index: AtomicIsize::new(-1),
+                line,
+                column,
+                text,
+                read_only: false,
+            },
+            byte_start,
+            byte_end,
+        })
+    }
+
+    fn create_invalid() -> Self::Tok { INVALID_UTF8.clone() }
+
+    fn get_data(from: Self::From) -> Cow<'a, Self::Data> { Borrowed(from.text) }
+}
+
 // pub struct DynFactory<'input,TF:TokenFactory<'.into()input>>(TF) where TF::Tok:CoerceUnsized<Box<dyn Token+'input>>;
 // impl <'input,TF:TokenFactory<'input>> TokenFactory<'input> for DynFactory<'input,TF>
 // where TF::Tok:CoerceUnsized<Box<dyn Token+'input>>
@@ -203,6 +279,8 @@ impl<'a> TokenFactory<'a> for OwningTokenFactory {
 pub type ArenaOwningFactory<'a> = ArenaFactory<'a, OwningTokenFactory, OwningToken>;
 ///Arena token factory that contains `CommonToken`s
 pub type ArenaCommonFactory<'a> = ArenaFactory<'a, CommonTokenFactory, CommonToken<'a>>;
+///Arena token factory that contains `Utf8Token`s
+pub type ArenaUtf8Factory<'a> = ArenaFactory<'a, Utf8TokenFactory, Utf8Token<'a>>;
 
 /// This is a wrapper for Token factory that allows to allocate tokens in separate arena.
 /// It can allow to significantly improve performance by passing Tokens by references everywhere.
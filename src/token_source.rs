@@ -1,4 +1,10 @@
-use crate::int_stream::IntStream;
+use std::borrow::Borrow;
+
+use better_any::{Tid, TidAble};
+
+use crate::char_stream::CharStream;
+use crate::int_stream::{IntStream, UNKNOWN_SOURCE_NAME};
+use crate::token::{Token, TOKEN_DEFAULT_CHANNEL, TOKEN_EOF};
 use crate::token_factory::TokenFactory;
 
 /// Produces tokens to be used by parser.
@@ -66,6 +72,83 @@ where
     fn get_token_factory(&self) -> &'input Self::TF { (**self).get_token_factory() }
 }
 
+/// Token source that returns tokens from the list, e.g. tokens that have been lexed before.
+///
+/// Once the list is exhausted, EOF token is returned, so list doesn't have to end with EOF.
+#[derive(Tid, Debug)]
+pub struct ListTokenSource<'input, TF: TokenFactory<'input>> {
+    tokens: Vec<TF::Tok>,
+    index: usize,
+    factory: &'input TF,
+    source_name: String,
+}
+
+impl<'input, TF: TokenFactory<'input>> ListTokenSource<'input, TF> {
+    /// Creates token source that returns `tokens`, `factory` is used to create EOF token
+    /// and missing tokens during error recovery
+    pub fn new(tokens: Vec<TF::Tok>, factory: &'input TF) -> Self {
+        Self {
+            tokens,
+            index: 0,
+            factory,
+            source_name: UNKNOWN_SOURCE_NAME.to_owned(),
+        }
+    }
+
+    /// Sets name returned by `get_source_name`, `UNKNOWN_SOURCE_NAME` by default
+    pub fn with_source_name(mut self, source_name: String) -> Self {
+        self.source_name = source_name;
+        self
+    }
+
+    fn current(&self) -> Option<&TF::Inner> {
+        self.tokens
+            .get(self.index)
+            .or_else(|| self.tokens.last())
+            .map(|it| it.borrow())
+    }
+}
+
+impl<'input, TF: TokenFactory<'input>> TokenSource<'input> for ListTokenSource<'input, TF> {
+    type TF = TF;
+
+    fn next_token(&mut self) -> TF::Tok {
+        if let Some(token) = self.tokens.get(self.index) {
+            self.index += 1;
+            return token.clone();
+        }
+        match self.tokens.last() {
+            Some(last) if last.borrow().get_token_type() == TOKEN_EOF => last.clone(),
+            last => {
+                let start = last.map_or(0, |it| it.borrow().get_stop() + 1);
+                let eof = self.factory.create(
+                    None::<&mut dyn CharStream<TF::From>>,
+                    TOKEN_EOF,
+                    None,
+                    TOKEN_DEFAULT_CHANNEL,
+                    start,
+                    start - 1,
+                    self.get_line(),
+                    self.get_char_position_in_line(),
+                );
+                self.tokens.push(eof.clone());
+                self.index += 1;
+                eof
+            }
+        }
+    }
+
+    fn get_line(&self) -> isize { self.current().map_or(1, |it| it.get_line()) }
+
+    fn get_char_position_in_line(&self) -> isize { self.current().map_or(0, |it| it.get_column()) }
+
+    fn get_input_stream(&mut self) -> Option<&mut dyn IntStream> { None }
+
+    fn get_source_name(&self) -> String { self.source_name.clone() }
+
+    fn get_token_factory(&self) -> &'input TF { self.factory }
+}
+
 // / adaptor to feed parser with existing tokens
 // pub struct IterTokenSource<S, F> where S: Iterator, S::Item: Token, F: TokenFactory<Tok=S::Item> {
 //     iter: S,
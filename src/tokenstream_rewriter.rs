@@ -1,166 +1,501 @@
-const (
-default_program_name = "default"
-program_init_size = 100
-min_token_index = 0
-)
-
-
-pub trait RewriteOperation {
-    fn execute(&self, buffer: * bytes.Buffer) -> isize;
-    fn String(&self) -> String;
-    get_instruction_index()        int
-    get_index()            int
-    get_text()            String
-    get_op_name()            String
-    get_tokens()            TokenStream
-    set_instruction_index(val isize)
-    set_index(isize)
-    set_text(String)
-    set_op_name(String)
-    set_tokens(TokenStream)
+//! Source-to-source transformations over a `TokenStream`
+//!
+//! Useful for rewriting out a buffered input token stream after doing some
+//! augmentation or other manipulations on it.
+//!
+//! You can insert stuff, replace, and delete chunks. Note that the operations
+//! are done lazily--only if you convert the buffer to a `String` with
+//! `get_text()`. This is very efficient because you are not moving data around
+//! all the time. As the buffer of tokens is converted to strings, the
+//! `get_text()` method(s) scan the input token stream and check to see if
+//! there is an operation at the current index. If so, the operation is done and
+//! then normal `String` rendering continues on the buffer. This is like having
+//! multiple Turing machine instruction streams (programs) operating on a single
+//! input tape. :)
+//!
+//! This rewriter makes no modifications to the token stream. It does not ask the
+//! stream to fill itself up nor does it advance the input cursor. The token
+//! stream `index()` will return the same value before and after any `get_text()`
+//! call.
+//!
+//! The rewriter only works on tokens that you have in the buffer and ignores the
+//! current input cursor. If you are buffering tokens on-demand, calling
+//! `get_text()` halfway through the input will only do rewrites for those tokens
+//! in the first half of the file.
+//!
+//! Since the operations are done lazily at `get_text`-time, operations do not
+//! screw up the token index values. That is, an insert operation at token index
+//! `i` does not change the index values for tokens `i+1..n-1`.
+//!
+//! Because operations never actually alter the buffer, you may always get the
+//! original token stream back without undoing anything. Since the instructions
+//! are queued up, you can easily simulate transactions and roll back any changes
+//! if there is an error just by removing instructions. For example,
+//!
+//! ```text
+//! let mut rewriter = TokenStreamRewriter::new(parser.get_input_stream());
+//! rewriter.insert_after_default(t.get_token_index(), "text to put after t");
+//! rewriter.insert_after_default(u.get_token_index(), "text after u");
+//! println!("{}", rewriter.get_text_default());
+//! ```
+//!
+//! You can also have multiple "instruction streams" and get multiple rewrites
+//! from a single pass over the input. Just name the instruction streams and use
+//! that name again when printing the buffer. This could be useful for generating
+//! a C file and also its header file--all from the same buffer:
+//!
+//! ```text
+//! rewriter.insert_after("pass1", t.get_token_index(), "text to put after t");
+//! rewriter.insert_after("pass2", u.get_token_index(), "text after u");
+//! println!("{}", rewriter.get_text("pass1", 0, tokens.size() - 1));
+//! println!("{}", rewriter.get_text("pass2", 0, tokens.size() - 1));
+//! ```
+//!
+//! If you don't use named rewrite streams, a "default" stream is used as the
+//! first example shows.
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+
+use crate::char_stream::InputData;
+use crate::token::{Token, TOKEN_EOF};
+use crate::token_stream::TokenStream;
+
+/// Name of the program used by `*_default` methods
+pub const DEFAULT_PROGRAM_NAME: &str = "default";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum RewriteOperationKind {
+    InsertBefore,
+    InsertAfter,
+    Replace,
 }
 
-pub struct BaseRewriteOperation {
-    instruction_index: isize,
+/// Single rewrite instruction of the `TokenStreamRewriter` program
+#[derive(Clone, Debug)]
+struct RewriteOperation {
+    kind: RewriteOperationKind,
+    /// What index into rewrites list are we?
+    instruction_index: usize,
+    /// Token buffer index.
     index: isize,
-    text: String,
-    op_name: String,
-    tokens: TokenStream,
+    /// Last token index for replace operation.
+    last_index: isize,
+    /// `None` for delete operations
+    text: Option<String>,
 }
 
-impl BaseRewriteOperation {
-    fn (op * BaseRewriteOperation)GetInstructionIndex() int { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)GetIndex() int { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)GetText() String { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)GetOpName() String { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)GetTokens() TokenStream { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)SetInstructionIndex(val isize) { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)SetIndex(val isize)  { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)SetText(val String) { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)SetOpName(val String) { unimplemented ! () }
-
-    fn (op * BaseRewriteOperation)SetTokens(val TokenStream)   { unimplemented ! () }
-
-
-    fn execute(&self, buffer: * bytes.Buffer) -> int { unimplemented!() }
-
-    fn String(&self) -> String { unimplemented!() }
-
-
-    pub struct InsertBeforeOp {
-    base: base_rewrite_operation,
+impl RewriteOperation {
+    fn is_insert(&self) -> bool { self.kind != RewriteOperationKind::Replace }
+
+    fn is_replace(&self) -> bool { self.kind == RewriteOperationKind::Replace }
+
+    /// Execute the rewrite operation by possibly adding to the buffer.
+    /// Return the index of the next token to operate on.
+    fn execute<'input, T>(&self, tokens: &T, buf: &mut String) -> isize
+    where
+        T: TokenStream<'input> + ?Sized,
+    {
+        if let Some(text) = &self.text {
+            buf.push_str(text);
+        }
+        if self.is_replace() {
+            return self.last_index + 1;
+        }
+        let token = tokens.get(self.index).borrow();
+        if token.get_token_type() != TOKEN_EOF {
+            buf.push_str(&token.get_text().to_display());
+        }
+        self.index + 1
     }
+}
 
-    fn new_insert_before_op(index isize, text: String, stream: TokenStream) -> * InsertBeforeOp { unimplemented!() }
-
-    fn execute(&self, buffer: * bytes.Buffer) -> int { unimplemented!() }
-
-    fn String(&self) -> String { unimplemented!() }
-
-
-    pub struct InsertAfterOp {
-    base: base_rewrite_operation,
+impl Display for RewriteOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let text = self.text.as_deref().unwrap_or("");
+        match self.kind {
+            RewriteOperationKind::InsertBefore => {
+                write!(f, "<InsertBeforeOp@{}:\"{}\">", self.index, text)
+            }
+            RewriteOperationKind::InsertAfter => {
+                write!(f, "<InsertAfterOp@{}:\"{}\">", self.index, text)
+            }
+            RewriteOperationKind::Replace if self.text.is_none() => {
+                write!(f, "<DeleteOp@{}..{}>", self.index, self.last_index)
+            }
+            RewriteOperationKind::Replace => write!(
+                f,
+                "<ReplaceOp@{}..{}:\"{}\">",
+                self.index, self.last_index, text
+            ),
+        }
     }
+}
 
-    fn new_insert_after_op(index isize, text: String, stream: TokenStream) -> * InsertAfterOp { unimplemented!() }
-
-    fn execute(&self, buffer: * bytes.Buffer) -> int { unimplemented!() }
-
-    fn String(&self) -> String { unimplemented!() }
+/// Queues up text rewrites for a token stream and renders the result on demand.
+///
+/// See module level documentation for more details.
+pub struct TokenStreamRewriter<'a, 'input, T: TokenStream<'input> + ?Sized> {
+    tokens: &'a T,
+    /// Map of programs, where each program is a list of operations
+    programs: HashMap<String, Vec<Option<RewriteOperation>>>,
+    pd: PhantomData<fn() -> &'input str>,
+}
 
-    pub struct ReplaceOp{
-    base: base_rewrite_operation,
-    last_index: isize,
+impl<'a, 'input, T: TokenStream<'input> + ?Sized> Debug for TokenStreamRewriter<'a, 'input, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenStreamRewriter")
+            .field("programs", &self.programs)
+            .finish()
     }
+}
 
-    fn new_replace_op(from, to: isize, text: String, stream: TokenStream) -> * ReplaceOp { unimplemented!() }
-
-    fn (op * ReplaceOp)Execute(buffer * bytes.Buffer) int { unimplemented ! () }
-
-    fn String(&self) -> String { unimplemented!() }
-
-
-    pub struct TokenStreamRewriter {
-    tokens: TokenStream,
-    programs: map[String]Vec < RewriteOperation >,
-    last_rewrite_token_indexes: map[String]isize,
+impl<'a, 'input, T: TokenStream<'input> + ?Sized> TokenStreamRewriter<'a, 'input, T> {
+    /// Creates rewriter over already buffered tokens of `tokens`
+    pub fn new(tokens: &'a T) -> Self {
+        Self {
+            tokens,
+            programs: HashMap::new(),
+            pd: PhantomData,
+        }
     }
 
-    fn new_token_stream_rewriter(tokens TokenStream) -> * TokenStreamRewriter { unimplemented!() }
-
-    fn get_token_stream(&self) -> TokenStream { unimplemented!() }
+    /// Token stream this rewriter renders
+    pub fn get_token_stream(&self) -> &'a T { self.tokens }
 
-    fn rollback(&self, program_name: String, instruction_index: isize) { unimplemented!() }
-
-    fn rollback_default(&self, instruction_index: isize) { unimplemented!() }
-    fn delete_program(&self, program_name: String) { unimplemented!() }
-
-    fn delete_program_default(&self) { unimplemented!() }
-
-    fn insert_after(&self, program_name: String, index: isize, text: String) { unimplemented!() }
-
-    fn insert_after_default(&self, index: isize, text: String) { unimplemented!() }
-
-    fn insert_after_token(&self, program_name: String, token: Token, text: String) { unimplemented!() }
-
-    fn insert_before(&self, program_name: String, index: isize, text: String) { unimplemented!() }
-
-    fn insert_before_default(&self, index: isize, text: String) { unimplemented!() }
-
-    fn insert_before_token(&self, program_name: String, token Token, text: String) { unimplemented!() }
-
-    fn replace(&self, program_name: String, from: isize, to: isize, text: String) { unimplemented!() }
-
-    fn (tsr * TokenStreamRewriter)ReplaceDefault(from, to: isize, text: String)   { unimplemented ! () }
+    /// Rollback the instruction stream for a program so that
+    /// the indicated instruction (via `instruction_index`) is no
+    /// longer in the stream.
+    pub fn rollback(&mut self, program_name: &str, instruction_index: usize) {
+        if let Some(program) = self.programs.get_mut(program_name) {
+            program.truncate(instruction_index);
+        }
+    }
 
-    fn (tsr * TokenStreamRewriter)ReplaceDefaultPos(index isize, text: String) { unimplemented ! () }
+    /// Same as `rollback` for default program
+    pub fn rollback_default(&mut self, instruction_index: usize) {
+        self.rollback(DEFAULT_PROGRAM_NAME, instruction_index)
+    }
 
-    fn (tsr * TokenStreamRewriter)ReplaceToken(program_name String, from: Token, to: Token, text: String) { unimplemented ! () }
+    /// Reset the program so that no instructions exist
+    pub fn delete_program(&mut self, program_name: &str) {
+        self.rollback(program_name, 0)
+    }
 
-    fn (tsr * TokenStreamRewriter)ReplaceTokenDefault(from, to: Token, text: String) { unimplemented ! () }
+    /// Same as `delete_program` for default program
+    pub fn delete_program_default(&mut self) { self.delete_program(DEFAULT_PROGRAM_NAME) }
+
+    /// Inserts `text` after token at `index`
+    pub fn insert_after(&mut self, program_name: &str, index: isize, text: impl Into<String>) {
+        // to insert after, just insert before next index (even if past end)
+        self.add_to_program(
+            program_name,
+            RewriteOperationKind::InsertAfter,
+            index + 1,
+            index + 1,
+            Some(text.into()),
+        );
+    }
 
-    fn (tsr * TokenStreamRewriter)ReplaceTokenDefaultPos(index Token, text: String) { unimplemented ! () }
+    /// Same as `insert_after` for default program
+    pub fn insert_after_default(&mut self, index: isize, text: impl Into<String>) {
+        self.insert_after(DEFAULT_PROGRAM_NAME, index, text)
+    }
 
-    fn (tsr * TokenStreamRewriter)Delete(program_name String, from: isize, to: isize) { unimplemented ! () }
+    /// Inserts `text` before token at `index`
+    pub fn insert_before(&mut self, program_name: &str, index: isize, text: impl Into<String>) {
+        self.add_to_program(
+            program_name,
+            RewriteOperationKind::InsertBefore,
+            index,
+            index,
+            Some(text.into()),
+        );
+    }
 
-    fn (tsr * TokenStreamRewriter)DeleteDefault(from, to: isize) { unimplemented ! () }
+    /// Same as `insert_before` for default program
+    pub fn insert_before_default(&mut self, index: isize, text: impl Into<String>) {
+        self.insert_before(DEFAULT_PROGRAM_NAME, index, text)
+    }
 
-    fn (tsr * TokenStreamRewriter)DeleteDefaultPos(index isize) { unimplemented ! () }
+    /// Replaces tokens in `from..=to` range with `text`
+    ///
+    /// Panics if range is invalid for current token buffer
+    pub fn replace(&mut self, program_name: &str, from: isize, to: isize, text: impl Into<String>) {
+        self.replace_inner(program_name, from, to, Some(text.into()))
+    }
 
-    fn (tsr * TokenStreamRewriter)DeleteToken(program_name String, from: Token, to: Token)   { unimplemented ! () }
+    /// Same as `replace` for default program
+    pub fn replace_default(&mut self, from: isize, to: isize, text: impl Into<String>) {
+        self.replace(DEFAULT_PROGRAM_NAME, from, to, text)
+    }
 
-    fn (tsr * TokenStreamRewriter)DeleteTokenDefault(from, to Token) { unimplemented ! () }
+    /// Deletes tokens in `from..=to` range
+    ///
+    /// Panics if range is invalid for current token buffer
+    pub fn delete(&mut self, program_name: &str, from: isize, to: isize) {
+        self.replace_inner(program_name, from, to, None)
+    }
 
-    fn (tsr * TokenStreamRewriter)GetLastRewriteTokenIndex(program_name String)int   { unimplemented ! () }
+    /// Same as `delete` for default program
+    pub fn delete_default(&mut self, from: isize, to: isize) {
+        self.delete(DEFAULT_PROGRAM_NAME, from, to)
+    }
 
-    fn (tsr * TokenStreamRewriter)GetLastRewriteTokenIndexDefault()int { unimplemented ! () }
+    fn replace_inner(&mut self, program_name: &str, from: isize, to: isize, text: Option<String>) {
+        let size = self.tokens.size();
+        if from > to || from < 0 || to < 0 || to >= size {
+            panic!("replace: range invalid: {}..{}(size={})", from, to, size);
+        }
+        self.add_to_program(program_name, RewriteOperationKind::Replace, from, to, text);
+    }
 
-    fn (tsr * TokenStreamRewriter)SetLastRewriteTokenIndex(program_name String, i: isize) { unimplemented ! () }
+    fn add_to_program(
+        &mut self, program_name: &str, kind: RewriteOperationKind, index: isize, last_index: isize,
+        text: Option<String>,
+    ) {
+        let program = self
+            .programs
+            .entry(program_name.to_owned())
+            .or_insert_with(Vec::new);
+        program.push(Some(RewriteOperation {
+            kind,
+            instruction_index: program.len(),
+            index,
+            last_index,
+            text,
+        }));
+    }
 
-    fn (tsr * TokenStreamRewriter)InitializeProgram(name String)Vec< RewriteOperation >  { unimplemented ! () }
+    /// Returns the text from the original tokens altered per the
+    /// instructions given to this rewriter.
+    pub fn get_text_default(&self) -> String {
+        self.get_text(DEFAULT_PROGRAM_NAME, 0, self.tokens.size() - 1)
+    }
 
-    fn (tsr * TokenStreamRewriter)AddToProgram(name String, op: RewriteOperation) { unimplemented ! () }
+    /// Returns the text associated with the tokens in the `start..=stop` interval from the
+    /// original token stream but with the alterations given to this rewriter by `program_name`.
+    /// Text includes tokens from all channels, so whitespace and comments
+    /// on hidden channels are preserved.
+    pub fn get_text(&self, program_name: &str, start: isize, stop: isize) -> String {
+        let size = self.tokens.size();
+        let start = start.max(0);
+        let stop = stop.min(size - 1);
+
+        let mut rewrites = match self.programs.get(program_name) {
+            Some(program) if !program.is_empty() => program.clone(),
+            _ => return self.get_original_text(start, stop),
+        };
+
+        let mut buf = String::new();
+        let mut index_to_op = reduce_to_single_operation_per_index(&mut rewrites);
+
+        let mut i = start;
+        while i <= stop && i < size {
+            match index_to_op.remove(&i) {
+                None => {
+                    let token = self.tokens.get(i).borrow();
+                    if token.get_token_type() != TOKEN_EOF {
+                        buf.push_str(&token.get_text().to_display());
+                    }
+                    i += 1;
+                }
+                Some(op) => i = op.execute(self.tokens, &mut buf),
+            }
+        }
+
+        // include stuff after end if it's last index in buffer
+        // So, if they did an insertAfter(lastValidIndex, "foo"), include
+        // foo if end==lastValidIndex.
+        if stop == size - 1 {
+            let mut rest = index_to_op
+                .into_iter()
+                .map(|(_, op)| op)
+                .filter(|op| op.index >= size - 1)
+                .collect::<Vec<_>>();
+            rest.sort_by_key(|op| op.index);
+            for op in rest {
+                buf.push_str(op.text.as_deref().unwrap_or(""));
+            }
+        }
+
+        buf
+    }
 
-    fn (tsr * TokenStreamRewriter)GetProgram(name String) Vec< RewriteOperation >    { unimplemented ! () }
-    fn (tsr * TokenStreamRewriter)GetTextDefault() String { unimplemented ! () }
-    fn (tsr * TokenStreamRewriter)GetText(program_name String, interval: * Interval) String   { unimplemented ! () }
+    fn get_original_text(&self, start: isize, stop: isize) -> String {
+        let mut buf = String::new();
+        for i in start..=stop {
+            let token = self.tokens.get(i).borrow();
+            if token.get_token_type() == TOKEN_EOF {
+                break;
+            }
+            buf.push_str(&token.get_text().to_display());
+        }
+        buf
+    }
+}
 
-    fn reduce_to_single_operation_per_index(rewrites Vec<RewriteOperation>) -> map[int]RewriteOperation { unimplemented ! () }
+/// Collapses program into map of operations with at most one operation per token index.
+///
+/// We need to combine operations and report invalid operations (like
+/// overlapping replaces that are not completed nested). Inserts to
+/// same index need to be combined etc...  Here are the cases:
+///
+/// ```text
+/// I.i.u I.j.v                            leave alone, nonoverlapping
+/// I.i.u I.i.v                            combine: Iivu
+///
+/// R.i-j.u R.x-y.v | i-j in x-y           delete first R
+/// R.i-j.u R.i-j.v                        delete first R
+/// R.i-j.u R.x-y.v | x-y in i-j           ERROR
+/// R.i-j.u R.x-y.v | boundaries overlap   ERROR
+///
+/// Delete special case of replace (text==null):
+/// D.i-j.u D.x-y.v | boundaries overlap   combine to max(min)..max(right)
+///
+/// I.i.u R.x-y.v | i in (x+1)-y           delete I (since insert before
+///                                        we're not deleting i)
+/// I.i.u R.x-y.v | i not in (x+1)-y       leave alone, nonoverlapping
+/// R.x-y.v I.i.u | i in x-y               ERROR
+/// R.x-y.v I.x.u                          R.x-y.uv (combine, delete I)
+/// R.x-y.v I.i.u | i not in x-y           leave alone, nonoverlapping
+///
+/// I.i.u = insert u before op @ i
+/// R.x-y.u = replace x-y indexed tokens with u
+/// ```
+///
+/// First we need to examine replaces. For any replace op:
+///
+///   1. wipe out any insertions before op within that range.
+///   2. Drop any replace op before that is contained completely within
+///      that range.
+///   3. Throw exception upon boundary overlap with any previous replace.
+///
+/// Then we can deal with inserts:
+///
+///   1. for any inserts to same index, combine even if not adjacent.
+///   2. for any prior replace with same left boundary, combine this
+///      insert with replace and delete this replace.
+///   3. throw exception if index in same range as previous replace
+///
+/// Don't actually delete; make op null in list. Easier to walk list.
+/// Later we can throw as we add to index &rarr; op map.
+///
+/// Note that `I.2 R.2-2` will wipe out `I.2` even though, technically, the
+/// inserted stuff would be before the replace range. But, if you
+/// add tokens in front of a method body '{' and then delete the method
+/// body, I think the stuff before the '{' you added should disappear too.
+///
+/// Panics on overlapping operations that cannot be combined.
+fn reduce_to_single_operation_per_index(
+    rewrites: &mut Vec<Option<RewriteOperation>>,
+) -> HashMap<isize, RewriteOperation> {
+    // WALK REPLACES
+    for i in 0..rewrites.len() {
+        let mut rop = match &rewrites[i] {
+            Some(op) if op.is_replace() => op.clone(),
+            _ => continue,
+        };
+        // Wipe prior inserts within range
+        for j in 0..i {
+            let iop = match &rewrites[j] {
+                Some(op) if op.is_insert() => op.clone(),
+                _ => continue,
+            };
+            if iop.index == rop.index {
+                // E.g., insert before 2, delete 2..2; update replace
+                // text to include insert before, kill insert
+                rewrites[iop.instruction_index] = None;
+                rop.text = Some(cat_op_text(&iop.text, &rop.text));
+            } else if iop.index > rop.index && iop.index <= rop.last_index {
+                // delete insert as it's a no-op.
+                rewrites[iop.instruction_index] = None;
+            }
+        }
+        // Drop any prior replaces contained within
+        for j in 0..i {
+            let prev_rop = match &rewrites[j] {
+                Some(op) if op.is_replace() => op.clone(),
+                _ => continue,
+            };
+            if prev_rop.index >= rop.index && prev_rop.last_index <= rop.last_index {
+                // delete replace as it's a no-op.
+                rewrites[prev_rop.instruction_index] = None;
+                continue;
+            }
+            // throw exception unless disjoint or identical
+            let disjoint = prev_rop.last_index < rop.index || prev_rop.index > rop.last_index;
+            // Delete special case of replace (text==null):
+            // D.i-j.u D.x-y.v | boundaries overlap combine to max(min)..max(right)
+            if prev_rop.text.is_none() && rop.text.is_none() && !disjoint {
+                rewrites[prev_rop.instruction_index] = None; // kill first delete
+                rop.index = prev_rop.index.min(rop.index);
+                rop.last_index = prev_rop.last_index.max(rop.last_index);
+            } else if !disjoint {
+                panic!(
+                    "replace op boundaries of {} overlap with previous {}",
+                    rop, prev_rop
+                );
+            }
+        }
+        rewrites[i] = Some(rop);
+    }
 
+    // WALK INSERTS
+    for i in 0..rewrites.len() {
+        let mut iop = match &rewrites[i] {
+            Some(op) if op.is_insert() => op.clone(),
+            _ => continue,
+        };
+        // combine current insert with prior if any at same index
+        for j in 0..i {
+            let prev_iop = match &rewrites[j] {
+                Some(op) if op.is_insert() => op.clone(),
+                _ => continue,
+            };
+            if prev_iop.index == iop.index {
+                if prev_iop.kind == RewriteOperationKind::InsertAfter {
+                    iop.text = Some(cat_op_text(&prev_iop.text, &iop.text));
+                } else {
+                    // combine current insert with prior if any at same index
+                    iop.text = Some(cat_op_text(&iop.text, &prev_iop.text));
+                }
+                // delete redundant prior insert
+                rewrites[prev_iop.instruction_index] = None;
+            }
+        }
+        // look for replaces where iop.index is in range; error
+        let mut deleted = false;
+        for j in 0..i {
+            let rop = match &mut rewrites[j] {
+                Some(op) if op.is_replace() => op,
+                _ => continue,
+            };
+            if iop.index == rop.index {
+                rop.text = Some(cat_op_text(&iop.text, &rop.text));
+                deleted = true; // delete current insert
+                continue;
+            }
+            if iop.index >= rop.index && iop.index <= rop.last_index {
+                panic!("insert op {} within boundaries of previous {}", iop, rop);
+            }
+        }
+        rewrites[i] = if deleted { None } else { Some(iop) };
+    }
 
-    /*
-        quick fixing Go lack of: overloads,
-     */
+    let mut m = HashMap::new();
+    for op in rewrites.drain(..).flatten() {
+        if m.contains_key(&op.index) {
+            panic!("should only be one op per index");
+        }
+        m.insert(op.index, op);
+    }
+    m
+}
 
-    fn max(a, b isize) -> int { unimplemented!() }
-    fn min(a, b isize) -> int { unimplemented!() }
+fn cat_op_text(a: &Option<String>, b: &Option<String>) -> String {
+    let mut result = a.clone().unwrap_or_default();
+    result.push_str(b.as_deref().unwrap_or(""));
+    result
 }
- 
\ No newline at end of file
@@ -1,19 +1,109 @@
-use std::rc::Weak;
-use parser::BaseParser;
+//! Parse listener that traces parser progress
+use std::borrow::Borrow;
+use std::fmt::{Debug, Formatter};
+use std::io::{stdout, Stdout, Write};
 
-pub struct TraceListener {
-    parser: Box<BaseParser>,
+use crate::char_stream::InputData;
+use crate::parser::ParserNodeType;
+use crate::parser_rule_context::ParserRuleContext;
+use crate::recognizer::Recognizer;
+use crate::rule_context::CustomRuleContext;
+use crate::token::Token;
+use crate::tree::{ErrorNode, ParseTreeListener, TerminalNode};
+use crate::utils::escape_whitespaces;
+
+/// Listener that logs rule entry/exit and token consumption,
+/// equivalent of `Parser.setTrace(true)` in Java runtime.
+///
+/// Output looks like:
+/// ```text
+/// enter   s, LT(1)=x
+/// consume [@0,0:0='x',<1>,1:0] rule a
+/// exit    a, LT(-1)=x
+/// ```
+/// Since listener does not have access to the token stream, exit event reports last token
+/// of the rule (`LT(-1)`) instead of the lookahead token.
+///
+/// To use it with generated parser, generated listener trait has to be implemented for it:
+/// ```text
+/// impl<'input> MyGrammarListener<'input> for TraceListener {}
+///
+/// parser.add_parse_listener(Box::new(TraceListener::new(&parser)));
+/// ```
+pub struct TraceListener<W: Write = Stdout> {
+    rule_names: Vec<String>,
+    rule_stack: Vec<usize>,
+    out: W,
+}
+
+impl<W: Write> Debug for TraceListener<W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceListener")
+            .field("rule_names", &self.rule_names)
+            .field("rule_stack", &self.rule_stack)
+            .finish()
+    }
 }
 
 impl TraceListener {
-    fn new_trace_listener(parser: Box<BaseParser>) -> * TraceListener { unimplemented!() }
+    /// Creates trace listener that prints to stdout
+    pub fn new<'input>(recog: &(impl Recognizer<'input> + ?Sized)) -> Self {
+        Self::with_writer(recog, stdout())
+    }
+}
+
+impl<W: Write> TraceListener<W> {
+    /// Creates trace listener that writes events to `out`
+    pub fn with_writer<'input>(recog: &(impl Recognizer<'input> + ?Sized), out: W) -> Self {
+        Self {
+            rule_names: recog
+                .get_rule_names()
+                .iter()
+                .map(|&it| it.to_owned())
+                .collect(),
+            rule_stack: Vec::new(),
+            out,
+        }
+    }
+
+    /// Returns underlying writer
+    pub fn into_inner(self) -> W { self.out }
+
+    fn consume<T: Token + ?Sized>(&mut self, token: &T) {
+        let rule_name = rule_name(&self.rule_names, self.rule_stack.last().copied());
+        let _ = writeln!(self.out, "consume {} rule {}", token, rule_name);
+    }
+}
+
+fn rule_name(rule_names: &[String], rule_index: Option<usize>) -> &str {
+    rule_index
+        .and_then(|it| rule_names.get(it))
+        .map(|it| it.as_str())
+        .unwrap_or("n/a")
+}
 
-    fn visit_error_node(&self, _: ErrorNode) { unimplemented!() }
+impl<'input, Node: ParserNodeType<'input>, W: Write> ParseTreeListener<'input, Node>
+    for TraceListener<W>
+{
+    fn visit_terminal(&mut self, node: &TerminalNode<'input, Node>) {
+        self.consume(node.symbol.borrow())
+    }
 
-    fn enter_every_rule(&self, ctx: ParserRuleContext) { unimplemented!() }
+    fn visit_error_node(&mut self, node: &ErrorNode<'input, Node>) {
+        self.consume(node.symbol.borrow())
+    }
 
-    fn visit_terminal(&self, node: TerminalNode) { unimplemented!() }
+    fn enter_every_rule(&mut self, ctx: &Node::Type) {
+        self.rule_stack.push(ctx.get_rule_index());
+        let text = escape_whitespaces(ctx.start().get_text().to_display(), false);
+        let rule_name = rule_name(&self.rule_names, Some(ctx.get_rule_index()));
+        let _ = writeln!(self.out, "enter   {}, LT(1)={}", rule_name, text);
+    }
 
-    fn exit_every_rule(&self, ctx: ParserRuleContext) { unimplemented!() }
+    fn exit_every_rule(&mut self, ctx: &Node::Type) {
+        let text = escape_whitespaces(ctx.stop().get_text().to_display(), false);
+        let rule_name = rule_name(&self.rule_names, Some(ctx.get_rule_index()));
+        let _ = writeln!(self.out, "exit    {}, LT(-1)={}", rule_name, text);
+        self.rule_stack.pop();
+    }
 }
- 
\ No newline at end of file
@@ -0,0 +1,603 @@
+//! Matching parse trees against tree patterns
+//!
+//! Tree pattern is a piece of input in the language of the grammar in which some parts are
+//! replaced with tags:
+//!  - `<ruleName>` matches any subtree created by rule `ruleName`
+//!  - `<TOKEN>` matches any token of type `TOKEN`
+//!  - `<label:ruleName>` or `<label:TOKEN>` does the same, but also makes matched node
+//!    available under `label`
+//!
+//! For example `<ID> = <expr>;` compiled for `statement` rule matches all assignments.
+//! Pattern is tokenized with the grammar's lexer and parsed with the grammar's ATN,
+//! so resulting pattern tree has the same structure as the parse trees created by the parser.
+//!
+//! ```text
+//! let matcher = ParseTreePatternMatcher::new(&*parser, MyParser::get_serialized_atn(), lex)?;
+//! let pattern = matcher.compile("<id:ID> = <expr>;", RULE_statement)?;
+//! for m in pattern.find_all(&*parser, tree, "//statement")? {
+//!     println!("{} is assigned to {}", m.get("id").unwrap().get_text(), m.get("expr").unwrap().get_text());
+//! }
+//! ```
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::rc::Rc;
+use std::sync::atomic::AtomicIsize;
+use std::sync::Arc;
+
+use better_any::TidExt;
+
+use crate::atn::ATN;
+use crate::atn_deserialization_options::ATNDeserializationOptions;
+use crate::atn_deserializer::{ATNDeserializationError, ATNDeserializer};
+use crate::common_token_stream::CommonTokenStream;
+use crate::error_strategy::BailErrorStrategy;
+use crate::errors::ANTLRError;
+use crate::int_stream::IntStream;
+use crate::parser::{Parser, ParserNodeType};
+use crate::parser_interpreter::{InterpreterContext, InterpreterContextType, ParserInterpreter};
+use crate::recognizer::Recognizer;
+use crate::rule_context::CustomRuleContext;
+use crate::token::{OwningToken, Token, TOKEN_DEFAULT_CHANNEL, TOKEN_EOF};
+use crate::token_factory::OwningTokenFactory;
+use crate::token_source::ListTokenSource;
+use crate::tree::{ParseTree, TerminalNode, Tree};
+use crate::trees;
+use crate::vocabulary::VocabularyImpl;
+use crate::xpath::{XPath, XPathError};
+
+type PatternNode = dyn InterpreterContext<'static, OwningTokenFactory>;
+
+type PatternLexer = dyn Fn(&str) -> Vec<OwningToken>;
+
+/// Compiles tree patterns for a particular grammar.
+///
+/// Requires serialized ATN of the grammar's parser, because patterns are parsed with
+/// an ATN that has additional transitions for rule tags,
+/// and a function that splits text into tokens with the grammar's lexer.
+/// Lexer function has to return all tokens of the text except EOF, for example:
+/// ```text
+/// |text: &str| {
+///     let mut lexer = MyLexer::new(InputStream::new(text));
+///     let mut tokens = Vec::new();
+///     loop {
+///         let token = lexer.next_token();
+///         if token.get_token_type() == TOKEN_EOF { break tokens }
+///         tokens.push(token.to_owned())
+///     }
+/// }
+/// ```
+pub struct ParseTreePatternMatcher {
+    grammar_file_name: String,
+    rule_names: Vec<String>,
+    literal_names: Vec<Option<String>>,
+    symbolic_names: Vec<Option<String>>,
+    atn: Arc<ATN>,
+    lexer: Box<PatternLexer>,
+    start: String,
+    stop: String,
+    escape: String,
+}
+
+impl Debug for ParseTreePatternMatcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseTreePatternMatcher")
+            .field("grammar_file_name", &self.grammar_file_name)
+            .field("start", &self.start)
+            .field("stop", &self.stop)
+            .field("escape", &self.escape)
+            .finish()
+    }
+}
+
+impl ParseTreePatternMatcher {
+    /// Creates matcher for the grammar of `parser`.
+    ///
+    /// `serialized_atn` is the serialized ATN of the same parser,
+    /// usually returned by generated parser's `get_serialized_atn()`.
+    /// Returns error if `serialized_atn` can't be deserialized.
+    pub fn new<'input>(
+        parser: &(impl Recognizer<'input> + ?Sized), serialized_atn: &str,
+        lexer: impl Fn(&str) -> Vec<OwningToken> + 'static,
+    ) -> Result<Self, ATNDeserializationError> {
+        let mut options = ATNDeserializationOptions::default();
+        options.set_generate_rule_bypass_transitions(true);
+        let atn = ATNDeserializer::new(Some(options)).try_deserialize(serialized_atn.chars())?;
+
+        let vocabulary = parser.get_vocabulary();
+        let max_token_type = vocabulary.get_max_token_type();
+        Ok(Self {
+            grammar_file_name: parser.get_grammar_file_name().to_owned(),
+            rule_names: parser
+                .get_rule_names()
+                .iter()
+                .map(|&it| it.to_owned())
+                .collect(),
+            literal_names: (0..=max_token_type)
+                .map(|it| vocabulary.get_literal_name(it).map(str::to_owned))
+                .collect(),
+            symbolic_names: (0..=max_token_type)
+                .map(|it| vocabulary.get_symbolic_name(it).map(str::to_owned))
+                .collect(),
+            atn: Arc::new(atn),
+            lexer: Box::new(lexer),
+            start: "<".to_owned(),
+            stop: ">".to_owned(),
+            escape: "\\".to_owned(),
+        })
+    }
+
+    /// Sets delimiters used for tags, `<`, `>` and `\` by default.
+    ///
+    /// Escaped delimiters are treated as a part of the text, escape sequences themselves
+    /// are removed before text is passed to the lexer.
+    ///
+    /// ### Panics
+    /// If `start` or `stop` is empty
+    pub fn set_delimiters(&mut self, start: &str, stop: &str, escape: &str) {
+        assert!(!start.is_empty(), "start cannot be null or empty");
+        assert!(!stop.is_empty(), "stop cannot be null or empty");
+        self.start = start.to_owned();
+        self.stop = stop.to_owned();
+        self.escape = escape.to_owned();
+    }
+
+    /// Compiles `pattern` as an input for the rule with `pattern_rule_index`.
+    ///
+    /// Returns error if pattern can't be parsed with that rule,
+    /// if rule does not consume the full pattern, or if tags in pattern are malformed
+    /// or refer to unknown rules or tokens (see `InvalidPatternError`).
+    pub fn compile(
+        &self, pattern: &str, pattern_rule_index: usize,
+    ) -> Result<ParseTreePattern, ANTLRError> {
+        let (tokens, tags) = self
+            .tokenize(pattern)
+            .map_err(|e| ANTLRError::OtherError(Rc::new(e)))?;
+        let token_source = ListTokenSource::new(
+            tokens.into_iter().map(Box::new).collect(),
+            &OwningTokenFactory,
+        );
+        let vocabulary =
+            VocabularyImpl::new(self.literal_names.iter(), self.symbolic_names.iter(), None);
+        let mut parser = ParserInterpreter::with_strategy(
+            &self.grammar_file_name,
+            Box::new(vocabulary),
+            self.rule_names.clone(),
+            self.atn.clone(),
+            CommonTokenStream::new(token_source),
+            BailErrorStrategy::new(),
+        );
+        parser.remove_error_listeners();
+
+        let tree = parser
+            .parse(pattern_rule_index)
+            .map_err(unwrap_cancellation)?;
+        // Make sure tree pattern compilation checks for a complete parse
+        if parser.input.la(1) != TOKEN_EOF {
+            return Err(ANTLRError::OtherError(Rc::new(
+                StartRuleDoesNotConsumeFullPattern,
+            )));
+        }
+
+        Ok(ParseTreePattern {
+            pattern: pattern.to_owned(),
+            pattern_rule_index,
+            pattern_tree: tree,
+            tags,
+        })
+    }
+
+    /// Splits `pattern` into tokens, returns them along with the tags at the
+    /// corresponding token indexes
+    fn tokenize(
+        &self, pattern: &str,
+    ) -> Result<(Vec<OwningToken>, Vec<Option<Tag>>), InvalidPatternError> {
+        let mut tokens = Vec::new();
+        let mut tags = Vec::new();
+        for chunk in self.split(pattern)? {
+            match chunk {
+                Chunk::Tag { label, name } => {
+                    let first = name.chars().next().unwrap_or_default();
+                    let (ttype, kind) = if first.is_uppercase() {
+                        let ttype = (0..self.symbolic_names.len())
+                            .find(|&it| self.symbolic_names[it].as_deref() == Some(name))
+                            .ok_or_else(|| InvalidPatternError::UnknownToken(name.to_owned()))?;
+                        (ttype as isize, TagKind::Token)
+                    } else if first.is_lowercase() {
+                        let rule_index = self
+                            .rule_names
+                            .iter()
+                            .position(|it| it == name)
+                            .ok_or_else(|| InvalidPatternError::UnknownRule(name.to_owned()))?;
+                        let ttype = self.atn.rule_to_token_type[rule_index];
+                        (ttype, TagKind::Rule)
+                    } else {
+                        return Err(InvalidPatternError::InvalidTag(name.to_owned()));
+                    };
+                    let tag = Tag {
+                        name: name.to_owned(),
+                        label: label.map(str::to_owned),
+                        kind,
+                    };
+                    tokens.push(tag_token(ttype, tag.to_string()));
+                    tags.push(Some(tag));
+                }
+                Chunk::Text(text) => {
+                    let chunk_tokens = (self.lexer)(&text);
+                    tags.extend(chunk_tokens.iter().map(|_| None));
+                    tokens.extend(chunk_tokens);
+                }
+            }
+        }
+        Ok((tokens, tags))
+    }
+
+    /// Splits `pattern` into text and tag chunks
+    fn split<'a>(&self, pattern: &'a str) -> Result<Vec<Chunk<'a>>, InvalidPatternError> {
+        let escaped_start = format!("{}{}", self.escape, self.start);
+        let escaped_stop = format!("{}{}", self.escape, self.stop);
+        // find all start and stop indexes first, then collect
+        let mut starts = Vec::new();
+        let mut stops = Vec::new();
+        let mut p = 0;
+        while p < pattern.len() {
+            let rest = &pattern[p..];
+            if !self.escape.is_empty() && rest.starts_with(&escaped_start) {
+                p += escaped_start.len();
+            } else if !self.escape.is_empty() && rest.starts_with(&escaped_stop) {
+                p += escaped_stop.len();
+            } else if rest.starts_with(&self.start) {
+                starts.push(p);
+                p += self.start.len();
+            } else if rest.starts_with(&self.stop) {
+                stops.push(p);
+                p += self.stop.len();
+            } else {
+                p += rest.chars().next().unwrap().len_utf8();
+            }
+        }
+
+        if starts.len() > stops.len() {
+            return Err(InvalidPatternError::UnterminatedTag);
+        }
+        if starts.len() < stops.len() {
+            return Err(InvalidPatternError::MissingStartTag);
+        }
+        if starts.iter().zip(&stops).any(|(start, stop)| start >= stop) {
+            return Err(InvalidPatternError::TagsOutOfOrder);
+        }
+
+        let mut chunks = Vec::new();
+        let mut text_start = 0;
+        for (&start, &stop) in starts.iter().zip(&stops) {
+            if start > text_start {
+                chunks.push(Chunk::Text(pattern[text_start..start].into()));
+            }
+            let tag = &pattern[start + self.start.len()..stop];
+            chunks.push(match tag.find(':') {
+                Some(colon) => Chunk::Tag {
+                    label: Some(&tag[..colon]),
+                    name: &tag[colon + 1..],
+                },
+                None => Chunk::Tag {
+                    label: None,
+                    name: tag,
+                },
+            });
+            text_start = stop + self.stop.len();
+        }
+        if text_start < pattern.len() {
+            chunks.push(Chunk::Text(pattern[text_start..].into()));
+        }
+
+        // strip out the escape sequences from text chunks but not tags
+        Ok(chunks
+            .into_iter()
+            .map(|chunk| match chunk {
+                Chunk::Text(text) if !self.escape.is_empty() => {
+                    Chunk::Text(text.replace(&self.escape, "").into())
+                }
+                chunk => chunk,
+            })
+            .collect())
+    }
+}
+
+enum Chunk<'a> {
+    Tag {
+        label: Option<&'a str>,
+        name: &'a str,
+    },
+    Text(Cow<'a, str>),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum TagKind {
+    Rule,
+    Token,
+}
+
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    label: Option<String>,
+    kind: TagKind,
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "<{}:{}>", label, self.name),
+            None => write!(f, "<{}>", self.name),
+        }
+    }
+}
+
+fn tag_token(ttype: isize, text: String) -> OwningToken {
+    OwningToken {
+        token_type: ttype,
+        channel: TOKEN_DEFAULT_CHANNEL,
+        start: -1,
+        stop: -1,
+        token_index: AtomicIsize::new(-1),
+        line: 0,
+        column: -1,
+        text,
+        read_only: false,
+    }
+}
+
+fn unwrap_cancellation(e: ANTLRError) -> ANTLRError {
+    if let ANTLRError::FallThrough(cause) = &e {
+        if let Some(cause) = cause
+            .source()
+            .and_then(|it| it.downcast_ref::<ANTLRError>())
+        {
+            return cause.clone();
+        }
+    }
+    e
+}
+
+/// Error returned by `ParseTreePatternMatcher::compile` if pattern has extra tokens
+/// after the part that has been parsed by the pattern rule
+#[derive(Debug)]
+pub struct StartRuleDoesNotConsumeFullPattern;
+
+impl Display for StartRuleDoesNotConsumeFullPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("start rule does not consume full pattern")
+    }
+}
+
+impl Error for StartRuleDoesNotConsumeFullPattern {}
+
+/// Error returned by `ParseTreePatternMatcher::compile` if pattern has malformed tags
+/// or tags that refer to unknown rules or tokens
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum InvalidPatternError {
+    /// `<TOKEN>` tag refers to a token that grammar does not define
+    UnknownToken(String),
+    /// `<rule>` tag refers to a rule that grammar does not define
+    UnknownRule(String),
+    /// Tag name is neither token nor rule name
+    InvalidTag(String),
+    /// Tag start delimiter is not followed by the stop delimiter
+    UnterminatedTag,
+    /// Tag stop delimiter without preceding start delimiter
+    MissingStartTag,
+    /// Stop delimiter comes before the start delimiter of the same tag
+    TagsOutOfOrder,
+}
+
+impl Display for InvalidPatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidPatternError::UnknownToken(name) => write!(f, "unknown token {}", name),
+            InvalidPatternError::UnknownRule(name) => write!(f, "unknown rule {}", name),
+            InvalidPatternError::InvalidTag(name) => write!(f, "invalid tag: {}", name),
+            InvalidPatternError::UnterminatedTag => f.write_str("unterminated tag in pattern"),
+            InvalidPatternError::MissingStartTag => f.write_str("missing start tag in pattern"),
+            InvalidPatternError::TagsOutOfOrder => {
+                f.write_str("tag delimiters out of order in pattern")
+            }
+        }
+    }
+}
+
+impl Error for InvalidPatternError {}
+
+/// Compiled tree pattern, created by `ParseTreePatternMatcher::compile`
+pub struct ParseTreePattern {
+    pattern: String,
+    pattern_rule_index: usize,
+    pattern_tree: Rc<PatternNode>,
+    // tags of the pattern tokens by token index
+    tags: Vec<Option<Tag>>,
+}
+
+impl Debug for ParseTreePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseTreePattern")
+            .field("pattern", &self.pattern)
+            .field("pattern_rule_index", &self.pattern_rule_index)
+            .finish()
+    }
+}
+
+impl ParseTreePattern {
+    /// Pattern string this pattern has been compiled from
+    pub fn get_pattern(&self) -> &str { &self.pattern }
+
+    /// Index of the rule pattern has been compiled for
+    pub fn get_pattern_rule_index(&self) -> usize { self.pattern_rule_index }
+
+    /// Parse tree of the pattern itself.
+    ///
+    /// Tags are represented as tokens, rule tags are additionally wrapped
+    /// in the node of the corresponding rule.
+    pub fn get_pattern_tree(&self) -> &Rc<dyn InterpreterContext<'static, OwningTokenFactory>> {
+        &self.pattern_tree
+    }
+
+    /// Matches `tree` against this pattern.
+    ///
+    /// Returned `ParseTreeMatch` contains nodes bound to tags
+    /// or first mismatched node if match has failed.
+    pub fn match_tree<'input, Node: ParserNodeType<'input>>(
+        &self, tree: &Rc<Node::Type>,
+    ) -> ParseTreeMatch<'input, Node> {
+        let mut labels = HashMap::new();
+        let mismatched_node = self.match_impl::<Node>(tree, &self.pattern_tree, &mut labels);
+        ParseTreeMatch {
+            tree: tree.clone(),
+            labels,
+            mismatched_node,
+        }
+    }
+
+    /// Returns whether `tree` matches this pattern
+    pub fn matches<'input, Node: ParserNodeType<'input>>(&self, tree: &Rc<Node::Type>) -> bool {
+        self.match_tree::<Node>(tree).succeeded()
+    }
+
+    /// Finds all nodes in `tree` selected by `xpath` that match this pattern.
+    ///
+    /// Returns error if `xpath` is not a valid path expression for `recog`, see `XPath::new`
+    pub fn find_all<'input, Node: ParserNodeType<'input>>(
+        &self, recog: &(impl Recognizer<'input, Node = Node> + ?Sized), tree: Rc<Node::Type>,
+        xpath: &str,
+    ) -> Result<Vec<ParseTreeMatch<'input, Node>>, XPathError> {
+        Ok(XPath::new(recog, xpath)?
+            .evaluate(tree)
+            .iter()
+            .map(|it| self.match_tree::<Node>(it))
+            .filter(|it| it.succeeded())
+            .collect())
+    }
+
+    fn tag_of(&self, token: &OwningToken, kind: TagKind) -> Option<&Tag> {
+        self.tags
+            .get(token.get_token_index() as usize)
+            .and_then(Option::as_ref)
+            .filter(|it| it.kind == kind)
+    }
+
+    /// Returns first node of `tree` that does not match `pattern_tree`,
+    /// or `None` if they match
+    fn match_impl<'input, Node: ParserNodeType<'input>>(
+        &self, tree: &Rc<Node::Type>, pattern_tree: &Rc<PatternNode>,
+        labels: &mut HashMap<String, Vec<Rc<Node::Type>>>,
+    ) -> Option<Rc<Node::Type>> {
+        let mut bind = |tag: &Tag| {
+            labels
+                .entry(tag.name.clone())
+                .or_default()
+                .push(tree.clone());
+            if let Some(label) = &tag.label {
+                labels.entry(label.clone()).or_default().push(tree.clone());
+            }
+        };
+
+        match (
+            trees::get_token_type::<Node>(tree.as_ref()),
+            pattern_symbol(pattern_tree.as_ref()),
+        ) {
+            // x and <ID>, x and y, or x and x
+            (Some(ttype), Some(symbol)) => {
+                if ttype != symbol.get_token_type() {
+                    return Some(tree.clone());
+                }
+                if let Some(tag) = self.tag_of(symbol, TagKind::Token) {
+                    bind(tag);
+                    None
+                } else if tree.get_text() == symbol.text {
+                    None
+                } else {
+                    Some(tree.clone())
+                }
+            }
+            // (expr ...) and <expr> or (expr ...) and (expr ...)
+            (None, None) => {
+                if let Some(tag) = self.rule_tag(pattern_tree.as_ref()) {
+                    if tree.get_rule_index() == pattern_tree.get_rule_index() {
+                        bind(tag);
+                        return None;
+                    }
+                    return Some(tree.clone());
+                }
+                if tree.get_child_count() != pattern_tree.get_child_count() {
+                    return Some(tree.clone());
+                }
+                tree.get_children()
+                    .zip(pattern_tree.get_children())
+                    .find_map(|(child, pattern_child)| {
+                        self.match_impl::<Node>(&child, &pattern_child, labels)
+                    })
+            }
+            // if nodes aren't both tokens or both rule nodes, can't match
+            _ => Some(tree.clone()),
+        }
+    }
+
+    /// Returns rule tag if `pattern_tree` is `(expr <expr>)` subtree
+    fn rule_tag(&self, pattern_tree: &PatternNode) -> Option<&Tag> {
+        if pattern_tree.get_child_count() != 1 {
+            return None;
+        }
+        let child = pattern_tree.get_child(0)?;
+        self.tag_of(pattern_symbol(child.as_ref())?, TagKind::Rule)
+    }
+}
+
+fn pattern_symbol(node: &PatternNode) -> Option<&OwningToken> {
+    node.downcast_ref::<TerminalNode<'static, InterpreterContextType<'static, OwningTokenFactory>>>(
+    )
+    .map(|it| it.symbol.as_ref())
+}
+
+/// Result of matching parse tree against `ParseTreePattern`
+pub struct ParseTreeMatch<'input, Node: ParserNodeType<'input>> {
+    tree: Rc<Node::Type>,
+    labels: HashMap<String, Vec<Rc<Node::Type>>>,
+    mismatched_node: Option<Rc<Node::Type>>,
+}
+
+impl<'input, Node: ParserNodeType<'input>> Debug for ParseTreeMatch<'input, Node> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseTreeMatch")
+            .field("tree", &self.tree)
+            .field("labels", &self.labels)
+            .field("mismatched_node", &self.mismatched_node)
+            .finish()
+    }
+}
+
+impl<'input, Node: ParserNodeType<'input>> ParseTreeMatch<'input, Node> {
+    /// Tree that has been matched against the pattern
+    pub fn get_tree(&self) -> &Rc<Node::Type> { &self.tree }
+
+    /// Returns last node bound to `label`.
+    ///
+    /// Unlabeled tags are bound to the rule or token name, so for `<expr>` or `<ID>` tags
+    /// nodes can be retrieved with `get("expr")` or `get("ID")`.
+    /// If there are several tags with the same label, use `get_all` to get all of them.
+    pub fn get(&self, label: &str) -> Option<&Rc<Node::Type>> {
+        self.labels.get(label).and_then(|it| it.last())
+    }
+
+    /// Returns all nodes bound to `label` in the order they appear in the pattern
+    pub fn get_all(&self, label: &str) -> &[Rc<Node::Type>] {
+        self.labels.get(label).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Map from label to all the nodes bound to it
+    pub fn get_labels(&self) -> &HashMap<String, Vec<Rc<Node::Type>>> { &self.labels }
+
+    /// First node that did not match the pattern, or `None` if match succeeded
+    pub fn get_mismatched_node(&self) -> Option<&Rc<Node::Type>> { self.mismatched_node.as_ref() }
+
+    /// Returns whether tree matched the pattern
+    pub fn succeeded(&self) -> bool { self.mismatched_node.is_none() }
+}
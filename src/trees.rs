@@ -2,9 +2,17 @@
 A set of utility routines useful for all kinds of ANTLR trees.
 */
 
+use std::borrow::Borrow;
+use std::iter::{from_fn, successors};
 use std::ops::Deref;
+use std::rc::Rc;
 
-use crate::tree::Tree;
+use better_any::TidExt;
+
+use crate::parser::ParserNodeType;
+use crate::rule_context::CustomRuleContext;
+use crate::token::Token;
+use crate::tree::{ErrorNode, TerminalNode, Tree};
 use crate::utils;
 
 /// Print out a whole tree, not just a node, in LISP format
@@ -35,16 +43,61 @@ pub fn get_node_text<'a>(t: &(impl Tree<'a> + ?Sized), rule_names: &[&str]) -> S
     t.get_node_text(rule_names)
 }
 
-//pub fn get_children(t: impl Tree) -> Vec<Rc<dyn Tree>> { unimplemented!() }
-//
-//pub fn get_ancestors(t: impl Tree) -> Vec<Rc<dyn Tree>> { unimplemented!() }
-//
-//pub fn find_all_token_nodes(t: impl ParseTree, ttype: isize) -> Vec<Rc<dyn ParseTree>> { unimplemented!() }
-//
-//pub fn find_all_rule_nodes(t: impl ParseTree, rule_index: isize) -> Vec<Rc<dyn ParseTree>> { unimplemented!() }
-//
-//pub fn find_all_nodes(t: impl ParseTree, index: isize, find_tokens: bool) -> Vec<Rc<dyn ParseTree>> { unimplemented!() }
-//
-////fn trees_find_all_nodes(t: ParseTree, index: isize, findTokens: bool, nodes: * Vec<ParseTree>) { unimplemented!() }
-//
-//pub fn descendants(t: impl ParseTree) -> Vec<dyn ParseTree> { unimplemented!() }
+/// Returns ancestors of `t`, starting from the root of the tree and ending with the parent of `t`.
+///
+/// Leaf nodes don't keep reference to their parent, so for them result is always empty.
+pub fn get_ancestors<'input, T: Tree<'input> + ?Sized>(
+    t: &T,
+) -> Vec<Rc<<T::Ctx as ParserNodeType<'input>>::Type>> {
+    let mut ancestors = successors(t.get_parent(), |it| it.get_parent()).collect::<Vec<_>>();
+    ancestors.reverse();
+    ancestors
+}
+
+/// Returns all leaf nodes (including error nodes) in the subtree of `t`
+/// that contain token of type `ttype`.
+///
+/// Nodes are returned in depth-first order, `t` itself is not checked.
+pub fn find_all_token_nodes<'input, T: Tree<'input> + ?Sized>(
+    t: &T, ttype: isize,
+) -> Vec<Rc<<T::Ctx as ParserNodeType<'input>>::Type>> {
+    descendants(t)
+        .filter(|it| get_token_type::<T::Ctx>(it.deref()) == Some(ttype))
+        .collect()
+}
+
+/// Returns all rule nodes in the subtree of `t` that have `rule_index`.
+///
+/// Nodes are returned in depth-first order, `t` itself is not checked.
+pub fn find_all_rule_nodes<'input, T: Tree<'input> + ?Sized>(
+    t: &T, rule_index: usize,
+) -> Vec<Rc<<T::Ctx as ParserNodeType<'input>>::Type>> {
+    descendants(t)
+        .filter(|it| it.get_rule_index() == rule_index)
+        .collect()
+}
+
+/// Returns iterator over all nodes in the subtree of `t` in depth-first order.
+///
+/// `t` itself is not included because it is not necessarily behind `Rc`.
+pub fn descendants<'input, T: Tree<'input> + ?Sized>(
+    t: &T,
+) -> impl Iterator<Item = Rc<<T::Ctx as ParserNodeType<'input>>::Type>> {
+    let mut stack = t.get_children().collect::<Vec<_>>();
+    stack.reverse();
+    from_fn(move || {
+        let node = stack.pop()?;
+        let len = stack.len();
+        stack.extend(node.get_children());
+        stack[len..].reverse();
+        Some(node)
+    })
+}
+
+crate fn get_token_type<'input, Node: ParserNodeType<'input>>(node: &Node::Type) -> Option<isize> {
+    if let Some(leaf) = node.downcast_ref::<TerminalNode<'input, Node>>() {
+        return Some(leaf.symbol.borrow().get_token_type());
+    }
+    node.downcast_ref::<ErrorNode<'input, Node>>()
+        .map(|leaf| leaf.symbol.borrow().get_token_type())
+}
@@ -0,0 +1,103 @@
+//! Two-stage parsing
+//!
+//! Most of the input can be parsed with the faster `PredictionMode::SLL`, which can fail
+//! on some valid input though. So the fastest way to parse is to try SLL first, and
+//! only if it fails to reparse input with full `PredictionMode::LL`:
+//! ```text
+//! let mut parser = CSVParser::with_dyn_strategy(tokens);
+//! let (tree, stage) = parse_two_stage(&mut parser, CSVParser::set_error_strategy, |parser| {
+//!     parser.csvFile()
+//! })?;
+//! ```
+//! Error strategy is switched by `parse_two_stage` itself, so parser has to be able to store
+//! any strategy, e.g. generated parser created with `with_dyn_strategy`.
+use std::ops::{CoerceUnsized, DerefMut};
+use std::rc::Rc;
+
+use better_any::TidAble;
+
+use crate::error_strategy::{BailErrorStrategy, DefaultErrorStrategy, ErrorStrategy};
+use crate::errors::ANTLRError;
+use crate::parser::{BaseParser, Parser, ParserNodeType, ParserRecog};
+use crate::token_stream::TokenStream;
+use crate::tree::{ErrorNode, Listenable, ParseTreeListener, TerminalNode};
+use crate::PredictionMode;
+
+/// Stage of `parse_two_stage` that produced the result
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ParseStage {
+    /// Input has been parsed with `PredictionMode::SLL` without syntax errors
+    SLL,
+    /// SLL stage failed, so input has been reparsed with `PredictionMode::LL`
+    LL,
+}
+
+/// Boxed error strategy of the parser with `BaseParser` type `P`
+pub type DynErrorStrategy<'input, P> = Box<dyn ErrorStrategy<'input, P> + 'input>;
+
+/// Parses input with `start_rule` in two stages, first with `PredictionMode::SLL`
+/// and `BailErrorStrategy`, and if it fails, once again with `PredictionMode::LL`
+/// and `DefaultErrorStrategy`.
+///
+/// `set_strategy` is used to install the error strategy for each stage, for generated parsers
+/// it is `set_error_strategy` of the parser created with `with_dyn_strategy`.
+/// Parser is left with `DefaultErrorStrategy` afterwards, regardless of the stage.
+///
+/// Error listeners are removed during the first stage, so only errors of the second stage
+/// are reported. Before each stage parser is reset and input is rewound to the token
+/// parsing started from, so token stream has to support seeking back,
+/// e.g. `CommonTokenStream`.
+/// Parse listeners are notified in both stages.
+/// Prediction mode of the parser is restored afterwards.
+///
+/// Returns result of the successful stage, or error of the second stage.
+pub fn parse_two_stage<'input, P, Ext, I, Ctx, T, R>(
+    parser: &mut P,
+    mut set_strategy: impl FnMut(&mut P, DynErrorStrategy<'input, BaseParser<'input, Ext, I, Ctx, T>>),
+    mut start_rule: impl FnMut(&mut P) -> Result<R, ANTLRError>,
+) -> Result<(R, ParseStage), ANTLRError>
+where
+    P: DerefMut<Target = BaseParser<'input, Ext, I, Ctx, T>>,
+    Ext: ParserRecog<'input, BaseParser<'input, Ext, I, Ctx, T>> + 'static,
+    I: TokenStream<'input> + TidAble<'input>,
+    Ctx: ParserNodeType<'input, TF = I::TF>,
+    T: ParseTreeListener<'input, Ctx> + ?Sized,
+    Ctx::Type: Listenable<T>,
+    Rc<TerminalNode<'input, Ctx>>: CoerceUnsized<Rc<Ctx::Type>>,
+    Rc<ErrorNode<'input, Ctx>>: CoerceUnsized<Rc<Ctx::Type>>,
+{
+    let start_index = parser.input.index();
+    let prediction_mode = parser.get_interpreter().get_prediction_mode();
+    let rewind = |parser: &mut P| {
+        parser.reset();
+        parser.input.seek(start_index);
+    };
+
+    rewind(parser);
+    set_strategy(parser, Box::new(BailErrorStrategy::new()));
+    parser
+        .get_interpreter()
+        .set_prediction_mode(PredictionMode::SLL);
+    let listeners = parser.replace_error_listeners(Vec::new());
+    let result = start_rule(parser);
+    parser.replace_error_listeners(listeners);
+    set_strategy(parser, Box::new(DefaultErrorStrategy::new()));
+    if parser.get_number_of_syntax_errors() == 0 {
+        if let Ok(result) = result {
+            parser
+                .get_interpreter()
+                .set_prediction_mode(prediction_mode);
+            return Ok((result, ParseStage::SLL));
+        }
+    }
+
+    rewind(parser);
+    parser
+        .get_interpreter()
+        .set_prediction_mode(PredictionMode::LL);
+    let result = start_rule(parser);
+    parser
+        .get_interpreter()
+        .set_prediction_mode(prediction_mode);
+    result.map(|result| (result, ParseStage::LL))
+}
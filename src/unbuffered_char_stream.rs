@@ -0,0 +1,320 @@
+//! `CharStream` that reads its data from `io::Read` on demand
+use std::borrow::Cow;
+use std::char::REPLACEMENT_CHARACTER;
+use std::io::{self, Read};
+
+use better_any::{impl_tid, TidAble};
+
+use crate::char_stream::CharStream;
+use crate::int_stream::{IntStream, EOF, UNKNOWN_SOURCE_NAME};
+
+const READ_SIZE: usize = 8192;
+
+/// Lexer input that is read and decoded from UTF-8 `io::Read` on demand.
+///
+/// Equivalent of Java's `UnbufferedCharStream`. Only characters after the first
+/// active `IntStream::mark` are kept in memory, so input of arbitrary size can be lexed,
+/// lexer marks the start of the current token, so it is enough for it to work.
+/// Indexes are code point indexes same as in Java runtime.
+///
+/// Because characters are released once lexer has moved past them, `seek` and `get_text`
+/// work only inside the current window, and tokens have to own their text,
+/// e.g. tokens created by `CommonTokenFactory` or `OwningTokenFactory`.
+///
+/// Invalid UTF-8 sequences are replaced with `U+FFFD`. If reading fails,
+/// input is considered to end at that point, and error is available via `get_error`.
+#[derive(Debug)]
+pub struct UnbufferedCharStream<R> {
+    reader: R,
+    bytes: Vec<u8>,
+    bytes_pos: usize,
+    eof: bool,
+    error: Option<io::Error>,
+
+    // characters of the current window, `data[p]` is the current character
+    data: Vec<char>,
+    p: usize,
+    num_markers: isize,
+    last_char: isize,
+    last_char_buffer_start: isize,
+    current_char_index: isize,
+    name: String,
+}
+
+#[impl_tid]
+impl<'a, R: 'static> TidAble<'a> for UnbufferedCharStream<R> {}
+
+impl<R: Read> UnbufferedCharStream<R> {
+    /// Creates stream that reads UTF-8 text from `reader`.
+    ///
+    /// `reader` is read in chunks, so it doesn't need to be buffered.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            bytes: Vec::new(),
+            bytes_pos: 0,
+            eof: false,
+            error: None,
+            data: Vec::new(),
+            p: 0,
+            num_markers: 0,
+            last_char: EOF,
+            last_char_buffer_start: EOF,
+            current_char_index: 0,
+            name: UNKNOWN_SOURCE_NAME.to_string(),
+        }
+    }
+
+    /// Sets name returned by `get_source_name`
+    pub fn with_source_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Error that stopped reading of the input, if any
+    pub fn get_error(&self) -> Option<&io::Error> { self.error.as_ref() }
+
+    fn buffer_start_index(&self) -> isize { self.current_char_index - self.p as isize }
+
+    /// Makes sure that `want` characters starting from the current one are in the window
+    fn sync(&mut self, want: isize) {
+        let need = self.p as isize + want - self.data.len() as isize;
+        for _ in 0..need {
+            match self.next_char() {
+                Some(ch) => self.data.push(ch),
+                None => break,
+            }
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        loop {
+            let available = &self.bytes[self.bytes_pos..];
+            if let Some(&first) = available.first() {
+                let width = utf8_width(first);
+                if available.len() >= width || self.eof {
+                    let len = width.min(available.len());
+                    let (ch, len) = match std::str::from_utf8(&available[..len]) {
+                        Ok(text) => (text.chars().next().unwrap(), len),
+                        Err(e) => (REPLACEMENT_CHARACTER, e.error_len().unwrap_or(len)),
+                    };
+                    self.bytes_pos += len;
+                    return Some(ch);
+                }
+            }
+            if self.eof {
+                return None;
+            }
+            self.read_bytes();
+        }
+    }
+
+    fn read_bytes(&mut self) {
+        self.bytes.drain(..self.bytes_pos);
+        self.bytes_pos = 0;
+        let len = self.bytes.len();
+        self.bytes.resize(len + READ_SIZE, 0);
+        let read = loop {
+            match self.reader.read(&mut self.bytes[len..]) {
+                Ok(read) => break read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.error = Some(e);
+                    break 0;
+                }
+            }
+        };
+        self.eof = read == 0;
+        self.bytes.truncate(len + read);
+    }
+
+    fn get_text_inner(&self, a: isize, b: isize) -> String {
+        let start = self.buffer_start_index();
+        let end = start + self.data.len() as isize;
+        if a < start || a > end {
+            panic!(
+                "interval {}..={} is outside of the buffered window {}..{}",
+                a, b, start, end
+            );
+        }
+        let b = b.min(end - 1);
+        if b < a {
+            return String::new();
+        }
+        self.data[(a - start) as usize..=(b - start) as usize]
+            .iter()
+            .collect()
+    }
+}
+
+/// Length of the UTF-8 sequence starting with `first` byte, 1 for invalid ones
+fn utf8_width(first: u8) -> usize {
+    match first {
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => 1,
+    }
+}
+
+impl<R: Read> CharStream<String> for UnbufferedCharStream<R> {
+    fn get_text(&self, a: isize, b: isize) -> String { self.get_text_inner(a, b) }
+}
+
+impl<'a, R: Read> CharStream<Cow<'a, str>> for UnbufferedCharStream<R> {
+    fn get_text(&self, a: isize, b: isize) -> Cow<'a, str> { self.get_text_inner(a, b).into() }
+}
+
+impl<R: Read> IntStream for UnbufferedCharStream<R> {
+    fn consume(&mut self) {
+        if self.la(1) == EOF {
+            panic!("cannot consume EOF");
+        }
+        self.last_char = self.data[self.p] as isize;
+        // nobody needs this window anymore, so it can be discarded
+        if self.p == self.data.len() - 1 && self.num_markers == 0 {
+            self.data.clear();
+            self.p = 0;
+            self.last_char_buffer_start = self.last_char;
+        } else {
+            self.p += 1;
+        }
+        self.current_char_index += 1;
+    }
+
+    fn la(&mut self, i: isize) -> isize {
+        if i == -1 {
+            return self.last_char;
+        }
+        if i == 0 {
+            panic!("should not be called with offset 0");
+        }
+        self.sync(i);
+        let index = self.p as isize + i - 1;
+        if index < 0 {
+            panic!("cannot look behind the buffered window");
+        }
+        self.data.get(index as usize).map_or(EOF, |&ch| ch as isize)
+    }
+
+    /// Keeps characters from the current one in memory until the marker is released.
+    /// Markers must be released in the reverse order.
+    fn mark(&mut self) -> isize {
+        if self.num_markers == 0 {
+            self.last_char_buffer_start = self.last_char;
+        }
+        self.num_markers += 1;
+        -self.num_markers
+    }
+
+    fn release(&mut self, marker: isize) {
+        if marker != -self.num_markers {
+            panic!("release() called with an invalid marker");
+        }
+        self.num_markers -= 1;
+        if self.num_markers == 0 && self.p > 0 {
+            self.data.drain(..self.p);
+            self.p = 0;
+            self.last_char_buffer_start = self.last_char;
+        }
+    }
+
+    fn index(&self) -> isize { self.current_char_index }
+
+    /// Seeks inside of the current window, or forward
+    fn seek(&mut self, mut index: isize) {
+        if index == self.current_char_index {
+            return;
+        }
+        if index > self.current_char_index {
+            self.sync(index - self.current_char_index);
+            index = index.min(self.buffer_start_index() + self.data.len() as isize);
+        }
+        let i = index - self.buffer_start_index();
+        if i < 0 {
+            panic!("cannot seek to index {} before the buffered window", index);
+        }
+        self.p = i as usize;
+        self.current_char_index = index;
+        self.last_char = if self.p == 0 {
+            self.last_char_buffer_start
+        } else {
+            self.data[self.p - 1] as isize
+        };
+    }
+
+    /// Number of characters read so far, whole size is unknown until the end of the input
+    fn size(&self) -> isize { self.buffer_start_index() + self.data.len() as isize }
+
+    fn get_source_name(&self) -> String { self.name.clone() }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{self, Read};
+
+    use crate::char_stream::CharStream;
+    use crate::int_stream::{IntStream, EOF};
+
+    use super::UnbufferedCharStream;
+
+    /// Returns one byte per read to split UTF-8 sequences
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&first, rest)) if !buf.is_empty() => {
+                    buf[0] = first;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unbuffered_char_stream() {
+        let mut input = UnbufferedCharStream::new(ByteReader("V1は3\u{1F600}\n".as_bytes()));
+        assert_eq!(input.la(1), 'V' as isize);
+        assert_eq!(input.la(3), 0x306F);
+        input.consume();
+        // without markers consumed characters are discarded
+        input.consume();
+        assert_eq!(input.index(), 2);
+        assert_eq!(input.la(-1), '1' as isize);
+
+        let marker = input.mark();
+        input.consume();
+        input.consume();
+        assert_eq!(input.la(1), 0x1F600);
+        assert_eq!(CharStream::<String>::get_text(&input, 2, 4), "は3\u{1F600}");
+        input.seek(2);
+        assert_eq!(input.la(1), 0x306F);
+        assert_eq!(input.la(-1), '1' as isize);
+        input.seek(5);
+        assert_eq!(input.la(1), '\n' as isize);
+        input.release(marker);
+        assert_eq!(input.data.len(), 1);
+        assert_eq!(input.la(-1), 0x1F600);
+
+        input.consume();
+        assert_eq!(input.la(1), EOF);
+        assert_eq!(input.index(), 6);
+        assert_eq!(input.size(), 6);
+        assert!(input.get_error().is_none());
+
+        let mut input = UnbufferedCharStream::new(&b"a\xFFb\xE3\x81"[..]);
+        let text = std::iter::from_fn(|| {
+            let ch = input.la(1);
+            if ch == EOF {
+                return None;
+            }
+            input.consume();
+            Some(std::char::from_u32(ch as u32).unwrap())
+        })
+        .collect::<String>();
+        assert_eq!(text, "a\u{FFFD}b\u{FFFD}");
+    }
+}
@@ -0,0 +1,193 @@
+//! `CharStream` over UTF-8 text that is indexed by code points
+use better_any::{impl_tid, TidAble};
+
+use crate::char_stream::CharStream;
+use crate::int_stream::{IntStream, EOF, UNKNOWN_SOURCE_NAME};
+
+/// Lexer input over UTF-8 text with the same index behavior as Java runtime.
+///
+/// Unlike `InputStream<&str>`, which uses byte offsets as indexes, this stream indexes input
+/// by code points, so token start/stop are the same as in other ANTLR targets, while
+/// `get_text` still returns zero-copy slices of the original text.
+/// Moving to an index is proportional to the distance from the current position, which is fine
+/// for lexer, since it seeks only inside of the current token.
+///
+/// Use it with `Utf8TokenFactory` to have byte offsets of the tokens as well.
+/// ```text
+/// let lexer = CSVLexer::new_with_token_factory(Utf8CharStream::new(text), &Utf8TokenFactory);
+/// ```
+#[derive(Debug)]
+pub struct Utf8CharStream<'a> {
+    name: String,
+    data: &'a str,
+    // code point index and byte offset of the current character
+    index: isize,
+    byte_index: usize,
+    size: isize,
+}
+
+#[impl_tid]
+impl<'a> TidAble<'a> for Utf8CharStream<'a> {}
+
+/// Part of the UTF-8 input along with its position
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Utf8Slice<'a> {
+    /// Text of the slice
+    pub text: &'a str,
+    /// Byte offset of the slice in the whole input
+    pub byte_start: usize,
+}
+
+impl<'a> Utf8CharStream<'a> {
+    /// Creates stream over `data`
+    pub fn new(data: &'a str) -> Self {
+        Self {
+            name: UNKNOWN_SOURCE_NAME.to_string(),
+            data,
+            index: 0,
+            byte_index: 0,
+            size: data.chars().count() as isize,
+        }
+    }
+
+    /// Sets name returned by `get_source_name`
+    pub fn with_source_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Resets input stream to start from the beginning
+    pub fn reset(&mut self) {
+        self.index = 0;
+        self.byte_index = 0;
+    }
+
+    /// Byte offset of the character at code point `index`, or the length of the input
+    /// if `index` is past the end
+    pub fn get_byte_offset(&self, index: isize) -> usize {
+        self.move_by(self.byte_index, index - self.index).0
+    }
+
+    /// Moves `offset` code points from the byte position `from`,
+    /// returns new byte position and the number of code points actually moved
+    fn move_by(&self, from: usize, offset: isize) -> (usize, isize) {
+        let mut position = from;
+        let mut moved = 0;
+        if offset > 0 {
+            for ch in self.data[from..].chars().take(offset as usize) {
+                position += ch.len_utf8();
+                moved += 1;
+            }
+        } else {
+            for ch in self.data[..from].chars().rev().take(-offset as usize) {
+                position -= ch.len_utf8();
+                moved -= 1;
+            }
+        }
+        (position, moved)
+    }
+
+    fn get_slice(&self, start: isize, stop: isize) -> Utf8Slice<'a> {
+        let byte_start = self.get_byte_offset(start);
+        let byte_stop = self.move_by(byte_start, stop - start + 1).0;
+        Utf8Slice {
+            text: &self.data[byte_start..byte_stop.max(byte_start)],
+            byte_start,
+        }
+    }
+}
+
+impl<'a> CharStream<Utf8Slice<'a>> for Utf8CharStream<'a> {
+    fn get_text(&self, a: isize, b: isize) -> Utf8Slice<'a> { self.get_slice(a, b) }
+}
+
+impl<'a, T: From<&'a str>> CharStream<T> for Utf8CharStream<'a> {
+    fn get_text(&self, a: isize, b: isize) -> T { self.get_slice(a, b).text.into() }
+}
+
+impl IntStream for Utf8CharStream<'_> {
+    #[inline]
+    fn consume(&mut self) {
+        match self.data[self.byte_index..].chars().next() {
+            Some(ch) => {
+                self.byte_index += ch.len_utf8();
+                self.index += 1;
+            }
+            None => panic!("cannot consume EOF"),
+        }
+    }
+
+    #[inline]
+    fn la(&mut self, offset: isize) -> isize {
+        let ch = match offset {
+            1 => self.data[self.byte_index..].chars().next(),
+            0 => panic!("should not be called with offset 0"),
+            _ if offset > 0 => self.data[self.byte_index..]
+                .chars()
+                .nth(offset as usize - 1),
+            _ => self.data[..self.byte_index]
+                .chars()
+                .nth_back(-offset as usize - 1),
+        };
+        ch.map_or(EOF, |ch| ch as isize)
+    }
+
+    #[inline]
+    fn mark(&mut self) -> isize { -1 }
+
+    #[inline]
+    fn release(&mut self, _marker: isize) {}
+
+    #[inline]
+    fn index(&self) -> isize { self.index }
+
+    #[inline]
+    fn seek(&mut self, index: isize) {
+        let (byte_index, moved) = self.move_by(self.byte_index, index - self.index);
+        self.byte_index = byte_index;
+        self.index += moved;
+    }
+
+    #[inline]
+    fn size(&self) -> isize { self.size }
+
+    fn get_source_name(&self) -> String { self.name.clone() }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::char_stream::CharStream;
+    use crate::int_stream::{IntStream, EOF};
+
+    use super::{Utf8CharStream, Utf8Slice};
+
+    #[test]
+    fn test_utf8_char_stream() {
+        let mut input = Utf8CharStream::new("V1は3🎉");
+        assert_eq!(input.size(), 5);
+        assert_eq!(input.la(3), 0x306F);
+        input.seek(2);
+        assert_eq!(input.la(1), 0x306F);
+        assert_eq!(input.la(-1), '1' as isize);
+        input.consume();
+        assert_eq!(input.index(), 3);
+        assert_eq!(input.get_byte_offset(3), 5);
+        assert_eq!(input.la(2), 0x1F389);
+        assert_eq!(input.la(3), EOF);
+        assert_eq!(input.la(-1), 0x306F);
+        assert_eq!(input.la(-2), '1' as isize);
+        assert_eq!(CharStream::<&str>::get_text(&input, 2, 4), "は3🎉");
+        assert_eq!(
+            CharStream::<Utf8Slice<'_>>::get_text(&input, 4, 4),
+            Utf8Slice {
+                text: "🎉",
+                byte_start: 6
+            }
+        );
+        input.seek(0);
+        assert_eq!(input.la(1), 'V' as isize);
+        input.seek(10);
+        assert_eq!(input.index(), 5);
+        assert_eq!(input.la(1), EOF);
+    }
+}
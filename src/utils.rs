@@ -14,6 +14,23 @@ pub fn escape_whitespaces(data: impl Borrow<str>, escape_spaces: bool) -> String
     res
 }
 
+/// Escapes `label` to be used inside quoted Graphviz DOT string,
+/// whitespace is shown as escape sequences instead of breaking the label
+pub fn escape_dot(label: &str) -> String {
+    let mut result = String::with_capacity(label.len());
+    for ch in label.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\\\n"),
+            '\r' => result.push_str("\\\\r"),
+            '\t' => result.push_str("\\\\t"),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
 pub trait Sealed {}
 
 // pub enum Cow2<'a,Ref,T:Borrow<Ref> = Ref>{
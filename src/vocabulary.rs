@@ -16,7 +16,7 @@ pub trait Vocabulary: Sync + Debug {
     fn get_display_name(&self, token_type: isize) -> Cow<'_, str>;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct VocabularyImpl {
     literal_names: Vec<Option<String>>,
     symbolic_names: Vec<Option<String>>,
@@ -0,0 +1,321 @@
+//! XPath-like queries over parse trees
+//!
+//! Path is a sequence of elements, each of them is prefixed with either
+//! `/` (matches children of the previous node) or `//` (matches any node in the subtree
+//! of the previous node, including the node itself).
+//! Element can be one of:
+//!  - rule name, e.g. `expr`, matches rule nodes with that rule
+//!  - token name, e.g. `ID`, or token literal, e.g. `'+'`, matches leaf nodes with that token type
+//!  - `*`, matches any node
+//!
+//! Element can be negated with `!`, so `/!expr` matches all rule nodes that are not `expr`.
+//! For example:
+//!  - `//ID` finds all `ID` tokens anywhere in the tree
+//!  - `/prog/func` finds all `func` nodes which are children of root `prog` node
+//!  - `/prog/*/expr` finds all `expr` nodes which are grandchildren of root `prog` node
+//!  - `//func/'return'` finds all `return` keyword tokens which are children of `func` nodes
+//!
+//! ```text
+//! let xpath = XPath::new(&*parser, "//func/ID")?;
+//! for node in xpath.evaluate(tree) { ... }
+//! ```
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::iter::once;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::parser::ParserNodeType;
+use crate::recognizer::Recognizer;
+use crate::rule_context::CustomRuleContext;
+use crate::token::{TOKEN_EOF, TOKEN_INVALID_TYPE};
+use crate::tree::Tree;
+use crate::trees;
+
+/// Compiled path expression
+///
+/// Rule and token names are resolved when expression is compiled,
+/// so `XPath` can be reused for any number of trees created by the same parser.
+pub struct XPath<'input, Node: ParserNodeType<'input>> {
+    path: String,
+    elements: Vec<XPathElement>,
+    phantom: PhantomData<fn() -> &'input Node>,
+}
+
+impl<'input, Node: ParserNodeType<'input>> Debug for XPath<'input, Node> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XPath")
+            .field("path", &self.path)
+            .field("elements", &self.elements)
+            .finish()
+    }
+}
+
+/// Error returned by `XPath::new` if path can't be compiled
+///
+/// Indexes are byte offsets in the path string.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum XPathError {
+    /// Path contains invalid character or unterminated token literal at `index`
+    InvalidSyntax {
+        /// Offset of the invalid part
+        index: usize,
+    },
+    /// Path element is expected at `index` after `/`, `//` or `!`
+    MissingElement {
+        /// Offset where element is expected, equals path length if path ends with operator
+        index: usize,
+    },
+    /// Element at `index` can't be used without `/` or `//` prefix
+    UnexpectedElement {
+        /// Offset of the element
+        index: usize,
+    },
+    /// Grammar has no token with this name or literal
+    UnknownToken {
+        /// Token name as written in path
+        name: String,
+        /// Offset of the name
+        index: usize,
+    },
+    /// Grammar has no rule with this name
+    UnknownRule {
+        /// Rule name as written in path
+        name: String,
+        /// Offset of the name
+        index: usize,
+    },
+}
+
+impl Display for XPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XPathError::InvalidSyntax { index } => {
+                write!(f, "invalid tokens or characters at index {}", index)
+            }
+            XPathError::MissingElement { index } => {
+                write!(f, "missing path element at index {}", index)
+            }
+            XPathError::UnexpectedElement { index } => {
+                write!(f, "unexpected path element at index {}", index)
+            }
+            XPathError::UnknownToken { name, index } => {
+                write!(f, "{} at index {} isn't a valid token name", name, index)
+            }
+            XPathError::UnknownRule { name, index } => {
+                write!(f, "{} at index {} isn't a valid rule name", name, index)
+            }
+        }
+    }
+}
+
+impl Error for XPathError {}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum ElementKind {
+    Rule(usize),
+    Token(isize),
+    Wildcard,
+}
+
+#[derive(Debug)]
+struct XPathElement {
+    kind: ElementKind,
+    anywhere: bool,
+    invert: bool,
+}
+
+impl XPathElement {
+    fn matches<'input, Node: ParserNodeType<'input>>(&self, node: &Node::Type) -> bool {
+        let token_type = trees::get_token_type::<Node>(node);
+        match self.kind {
+            ElementKind::Rule(rule_index) => {
+                token_type.is_none() && (node.get_rule_index() == rule_index) != self.invert
+            }
+            ElementKind::Token(ttype) => {
+                token_type.is_some() && (token_type == Some(ttype)) != self.invert
+            }
+            ElementKind::Wildcard => !self.invert,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum PathToken<'a> {
+    Root,
+    Anywhere,
+    Wildcard,
+    Bang,
+    TokenRef(&'a str),
+    RuleRef(&'a str),
+    Literal(&'a str),
+}
+
+fn tokenize(path: &str) -> Result<Vec<(usize, PathToken<'_>)>, XPathError> {
+    let mut result = Vec::new();
+    let mut chars = path.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            '/' if chars.peek().map(|&(_, c)| c) == Some('/') => {
+                chars.next();
+                PathToken::Anywhere
+            }
+            '/' => PathToken::Root,
+            '*' => PathToken::Wildcard,
+            '!' => PathToken::Bang,
+            '\'' => {
+                let end = chars
+                    .find(|&(_, c)| c == '\'')
+                    .map(|(i, _)| i + 1)
+                    .ok_or(XPathError::InvalidSyntax { index: start })?;
+                PathToken::Literal(&path[start..end])
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = path.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                let name = &path[start..end];
+                if c.is_uppercase() {
+                    PathToken::TokenRef(name)
+                } else {
+                    PathToken::RuleRef(name)
+                }
+            }
+            _ => return Err(XPathError::InvalidSyntax { index: start }),
+        };
+        result.push((start, token));
+    }
+    Ok(result)
+}
+
+impl<'input, Node: ParserNodeType<'input>> XPath<'input, Node> {
+    /// Compiles `path` using rule names and vocabulary of `recog`
+    ///
+    /// Returns error if `path` is not a valid path expression or refers to unknown rule or token
+    pub fn new(
+        recog: &(impl Recognizer<'input, Node = Node> + ?Sized), path: &str,
+    ) -> Result<Self, XPathError> {
+        let mut tokens = tokenize(path)?.into_iter();
+        let mut elements = Vec::new();
+        while let Some((index, token)) = tokens.next() {
+            let element = match token {
+                PathToken::Root | PathToken::Anywhere => {
+                    let mut next = tokens.next();
+                    let invert = matches!(next, Some((_, PathToken::Bang)));
+                    if invert {
+                        next = tokens.next();
+                    }
+                    let (index, next) =
+                        next.ok_or(XPathError::MissingElement { index: path.len() })?;
+                    let mut element =
+                        Self::path_element(recog, index, next, token == PathToken::Anywhere)?;
+                    element.invert = invert;
+                    element
+                }
+                PathToken::TokenRef(_) | PathToken::RuleRef(_) | PathToken::Wildcard => {
+                    // same as `/` prefix
+                    Self::path_element(recog, index, token, false)?
+                }
+                PathToken::Bang | PathToken::Literal(_) => {
+                    return Err(XPathError::UnexpectedElement { index })
+                }
+            };
+            elements.push(element);
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            elements,
+            phantom: PhantomData,
+        })
+    }
+
+    fn path_element(
+        recog: &(impl Recognizer<'input, Node = Node> + ?Sized), index: usize,
+        token: PathToken<'_>, anywhere: bool,
+    ) -> Result<XPathElement, XPathError> {
+        let kind = match token {
+            PathToken::Wildcard => ElementKind::Wildcard,
+            PathToken::TokenRef(name) | PathToken::Literal(name) => {
+                let vocabulary = recog.get_vocabulary();
+                let ttype = (TOKEN_EOF..=vocabulary.get_max_token_type())
+                    .filter(|&it| it != TOKEN_INVALID_TYPE)
+                    .find(|&it| {
+                        vocabulary.get_symbolic_name(it) == Some(name)
+                            || vocabulary.get_literal_name(it) == Some(name)
+                    })
+                    .ok_or_else(|| XPathError::UnknownToken {
+                        name: name.to_owned(),
+                        index,
+                    })?;
+                ElementKind::Token(ttype)
+            }
+            PathToken::RuleRef(name) => {
+                let rule_index = recog
+                    .get_rule_names()
+                    .iter()
+                    .position(|&it| it == name)
+                    .ok_or_else(|| XPathError::UnknownRule {
+                        name: name.to_owned(),
+                        index,
+                    })?;
+                ElementKind::Rule(rule_index)
+            }
+            PathToken::Root | PathToken::Anywhere | PathToken::Bang => {
+                return Err(XPathError::MissingElement { index })
+            }
+        };
+        Ok(XPathElement {
+            kind,
+            anywhere,
+            invert: false,
+        })
+    }
+
+    /// Path this expression has been compiled from
+    pub fn get_path(&self) -> &str { &self.path }
+
+    /// Returns all nodes of the tree with root `t` that match this path.
+    ///
+    /// Nodes are returned in depth-first order without duplicates.
+    pub fn evaluate(&self, t: Rc<Node::Type>) -> Vec<Rc<Node::Type>> {
+        // initially `t` is treated as the only child of an imaginary root
+        let mut work = vec![t];
+        for (i, element) in self.elements.iter().enumerate() {
+            let mut visited = HashSet::new();
+            let mut next = Vec::new();
+            for node in &work {
+                let candidates: Vec<_> = if element.anywhere {
+                    once(node.clone())
+                        .chain(trees::descendants(node.as_ref()))
+                        .collect()
+                } else if i == 0 {
+                    vec![node.clone()]
+                } else {
+                    node.get_children().collect()
+                };
+                next.extend(candidates.into_iter().filter(|it| {
+                    element.matches::<Node>(it.as_ref())
+                        && visited.insert(Rc::as_ptr(it) as *const u8)
+                }));
+            }
+            work = next;
+        }
+        work
+    }
+}
+
+/// Returns all nodes of the tree with root `t` that match `path`.
+///
+/// Shortcut for `XPath::new(recog, path)?.evaluate(t)`
+pub fn find_all<'input, Node: ParserNodeType<'input>>(
+    recog: &(impl Recognizer<'input, Node = Node> + ?Sized), t: Rc<Node::Type>, path: &str,
+) -> Result<Vec<Rc<Node::Type>>, XPathError> {
+    Ok(XPath::new(recog, path)?.evaluate(t))
+}
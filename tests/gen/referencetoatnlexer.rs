@@ -79,6 +79,8 @@ impl<'input, Input: CharStream<From<'input>>> ReferenceToATNLexer<'input, Input>
 
     fn get_grammar_file_name(&self) -> &'static str { "ReferenceToATNLexer.g4" }
 
+    pub fn get_serialized_atn() -> &'static str { _serializedATN }
+
     pub fn new_with_token_factory(input: Input, tf: &'input LocalTokenFactory<'input>) -> Self {
         antlr_rust::recognizer::check_version("0", "2");
         Self {
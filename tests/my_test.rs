@@ -9,21 +9,53 @@
 
 mod gen {
     use std::fmt::Write;
-    use std::io::Read;
+    use std::cell::RefCell;
+    use std::io::{self, Read};
     use std::iter::FromIterator;
+    use std::sync::Arc;
 
+    use antlr_rust::atn_deserializer::{ATNDeserializationError, ATNDeserializer};
+    use antlr_rust::atn_serializer::ATNSerializer;
+    use antlr_rust::atn_simulator::{CacheSize, IATNSimulator};
+    use antlr_rust::atn_state::{ATNDecisionState, ATNStateType};
     use antlr_rust::common_token_stream::CommonTokenStream;
-    use antlr_rust::int_stream::IntStream;
-    use antlr_rust::lexer::Lexer;
+    use antlr_rust::error_listener::{DiagnosticCollector, ErrorListener, Severity};
+    use antlr_rust::dot_generator::DOTGenerator;
+    use antlr_rust::error_strategy::{BailErrorStrategy, DefaultErrorStrategy};
+    use antlr_rust::errors::ANTLRError;
+    use antlr_rust::file_stream::FileStream;
+    use antlr_rust::incremental::{self, LexedToken, TextEdit};
+    use antlr_rust::int_stream::{IntStream, UNKNOWN_SOURCE_NAME};
+    use antlr_rust::interpreter_data::InterpreterData;
+    use antlr_rust::interval_set::Interval;
+    use antlr_rust::lexer::{Lexer, PositionConfig, PositionEncoding};
+    use antlr_rust::lexer_interpreter::LexerInterpreter;
+    use antlr_rust::lexer_atn_simulator::ERROR_DFA_STATE_REF;
+    use antlr_rust::parser::Parser;
+    use antlr_rust::parser_interpreter::{InterpreterContext, ParserInterpreter};
+    use antlr_rust::profiling::ParseInfo;
+    use antlr_rust::recognizer::Recognizer;
 
-    use antlr_rust::token::{Token, TOKEN_EOF};
-    use antlr_rust::token_factory::{ArenaCommonFactory, OwningTokenFactory};
+    use antlr_rust::token::{CommonToken, Token, Utf8Token, TOKEN_EOF, TOKEN_INVALID_TYPE};
+    use antlr_rust::token_factory::{
+        ArenaCommonFactory, ArenaUtf8Factory, OwningTokenFactory, TokenFactory,
+    };
+    use antlr_rust::TokenSource;
     use antlr_rust::token_stream::{TokenStream, UnbufferedTokenStream};
+    use antlr_rust::trace_listener::TraceListener;
+    use antlr_rust::tokenstream_rewriter::TokenStreamRewriter;
+    use antlr_rust::tree_pattern::{InvalidPatternError, ParseTreePatternMatcher};
+    use antlr_rust::trees;
+    use antlr_rust::unbuffered_char_stream::UnbufferedCharStream;
+    use antlr_rust::utf8_char_stream::Utf8CharStream;
+    use antlr_rust::two_stage::{parse_two_stage, DynErrorStrategy, ParseStage};
+    use antlr_rust::xpath::{self, XPath, XPathError};
     use antlr_rust::tree::{
         ParseTree, ParseTreeListener, ParseTreeVisitor, ParseTreeWalker, TerminalNode, Tree,
         VisitChildren, Visitable,
     };
-    use antlr_rust::InputStream;
+    use antlr_rust::vocabulary::{Vocabulary, VocabularyImpl};
+    use antlr_rust::{InputStream, ListTokenSource, PredictionMode};
     use csvlexer::*;
     use csvlistener::*;
     use csvparser::CSVParser;
@@ -160,6 +192,47 @@ if (x < x && a > 0) then duh
         assert!(token_source_iter.next().is_none());
     }
 
+    #[test]
+    fn test_token_stream_rewriter() {
+        let tf = ArenaCommonFactory::default();
+        let lexer = CSVLexer::new_with_token_factory(InputStream::new("a, b\nc, d\n".into()), &tf);
+        let mut tokens = CommonTokenStream::new(lexer);
+        while tokens.la(1) != TOKEN_EOF {
+            tokens.consume();
+        }
+
+        let mut rewriter = TokenStreamRewriter::new(&tokens);
+        rewriter.replace_default(0, 0, "x");
+        rewriter.insert_before_default(3, "<");
+        rewriter.insert_after_default(3, ">");
+        rewriter.delete_default(6, 7);
+        rewriter.insert_after("other", 9, "EOF");
+        // hidden whitespace tokens are kept
+        assert_eq!(rewriter.get_text_default(), "x, <b>\ncd\n");
+        assert_eq!(rewriter.get_text("other", 0, tokens.size() - 1), "a, b\nc, d\nEOF");
+
+        rewriter.rollback_default(1);
+        assert_eq!(rewriter.get_text_default(), "x, b\nc, d\n");
+        rewriter.delete_program_default();
+        assert_eq!(rewriter.get_text_default(), "a, b\nc, d\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap with previous")]
+    fn test_token_stream_rewriter_overlap() {
+        let tf = ArenaCommonFactory::default();
+        let lexer = CSVLexer::new_with_token_factory(InputStream::new("a,b,c\n".into()), &tf);
+        let mut tokens = CommonTokenStream::new(lexer);
+        while tokens.la(1) != TOKEN_EOF {
+            tokens.consume();
+        }
+
+        let mut rewriter = TokenStreamRewriter::new(&tokens);
+        rewriter.replace_default(0, 2, "x");
+        rewriter.replace_default(1, 3, "y");
+        rewriter.get_text_default();
+    }
+
     struct Listener {}
 
     impl<'input> ParseTreeListener<'input, CSVParserContextType> for Listener {
@@ -324,6 +397,115 @@ if (x < x && a > 0) then duh
         );
     }
 
+    impl<'input> CSVListener<'input> for TraceListener<Vec<u8>> {}
+
+    #[test]
+    fn test_trace_listener() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer =
+            CSVLexer::new_with_token_factory(InputStream::new("h1\nd1,d2\n".into()), &tf);
+        let token_source = CommonTokenStream::new(_lexer);
+        let mut parser = CSVParser::new(token_source);
+        let trace = TraceListener::with_writer(&*parser, Vec::new());
+        let id = parser.add_parse_listener(Box::new(trace));
+        parser.csvFile().expect("expected to parse successfully");
+
+        let trace = parser.remove_parse_listener(id).into_inner();
+        assert_eq!(
+            String::from_utf8(trace).unwrap(),
+            "enter   csvFile, LT(1)=h1\n\
+             enter   hdr, LT(1)=h1\n\
+             enter   row, LT(1)=h1\n\
+             enter   field, LT(1)=h1\n\
+             consume [@0,0:1='h1',<5>,1:0] rule field\n\
+             exit    field, LT(-1)=h1\n\
+             consume [@1,2:2='\\n',<3>,1:2] rule row\n\
+             exit    row, LT(-1)=\\n\n\
+             exit    hdr, LT(-1)=\\n\n\
+             enter   row, LT(1)=d1\n\
+             enter   field, LT(1)=d1\n\
+             consume [@2,3:4='d1',<5>,2:0] rule field\n\
+             exit    field, LT(-1)=d1\n\
+             consume [@3,5:5=',',<1>,2:2] rule row\n\
+             enter   field, LT(1)=d2\n\
+             consume [@4,6:7='d2',<5>,2:3] rule field\n\
+             exit    field, LT(-1)=d2\n\
+             consume [@5,8:8='\\n',<3>,2:5] rule row\n\
+             exit    row, LT(-1)=\\n\n\
+             exit    csvFile, LT(-1)=\\n\n"
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct SourceNameCollector(Rc<RefCell<Vec<String>>>);
+
+    impl<'a, T: Recognizer<'a>> ErrorListener<'a, T> for SourceNameCollector {
+        fn syntax_error(
+            &self,
+            recognizer: &T,
+            _offending_symbol: Option<&<T::TF as TokenFactory<'a>>::Inner>,
+            line: isize,
+            column: isize,
+            msg: &str,
+            _error: Option<&ANTLRError>,
+        ) {
+            self.0.borrow_mut().push(format!(
+                "{}: line {}:{} {}",
+                recognizer.get_input_source_name(),
+                line,
+                column,
+                msg
+            ))
+        }
+    }
+
+    #[test]
+    fn test_file_stream_source_name() {
+        let path = std::env::temp_dir().join(format!(
+            "antlr_rust_test_file_stream_source_name_{}.csv",
+            std::process::id()
+        ));
+        let file_name = path.to_string_lossy().into_owned();
+        std::fs::write(&path, "h1\n\"d1\n").unwrap();
+        let input = FileStream::new(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let tf = ArenaCommonFactory::default();
+        let mut lexer = CSVLexer::new_with_token_factory(input, &tf);
+        assert_eq!(lexer.get_source_name(), file_name);
+        lexer.remove_error_listeners();
+        lexer.add_error_listener(Box::new(SourceNameCollector(errors.clone())));
+        let token_source = CommonTokenStream::new(lexer);
+        assert_eq!(token_source.get_source_name(), file_name);
+        let mut parser = CSVParser::new(token_source);
+        parser.remove_error_listeners();
+        parser.add_error_listener(Box::new(SourceNameCollector(errors.clone())));
+        let _ = parser.csvFile();
+
+        assert_eq!(
+            *errors.borrow(),
+            vec![
+                format!("{}: line 2:0 token recognition error at: '\"d1\n'", file_name),
+                format!(
+                    "{}: line 3:0 mismatched input '<EOF>' expecting {{',', '\r', '\n', TEXT, STRING}}",
+                    file_name
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_source_name() {
+        let tf = ArenaCommonFactory::default();
+        let lexer = CSVLexer::new_with_token_factory(InputStream::new("h1\n"), &tf);
+        assert_eq!(lexer.get_source_name(), UNKNOWN_SOURCE_NAME);
+        let token_source = ListTokenSource::new(Vec::new(), &tf);
+        assert_eq!(token_source.get_source_name(), UNKNOWN_SOURCE_NAME);
+        let parser = CSVParser::new(CommonTokenStream::new(token_source));
+        assert_eq!(parser.get_input_source_name(), UNKNOWN_SOURCE_NAME);
+    }
+
     #[test]
     fn test_byte_parser() {}
 
@@ -396,4 +578,1556 @@ if (x < x && a > 0) then duh
 
         let _result = parse(&tf);
     }
+
+    #[test]
+    fn test_trees() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer =
+            CSVLexer::new_with_token_factory(InputStream::new("h1,h2\nd1,d2\nd3\n".into()), &tf);
+        let token_source = CommonTokenStream::new(_lexer);
+        let mut parser = CSVParser::new(token_source);
+        let result = parser.csvFile().expect("parsed unsuccessfully");
+
+        let texts = trees::find_all_token_nodes(&*result, csvparser::TEXT)
+            .iter()
+            .map(|it| it.get_text())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["h1", "h2", "d1", "d2", "d3"]);
+
+        let rows = trees::find_all_rule_nodes(&*result, csvparser::RULE_row);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].get_text(), "d1,d2\n");
+        assert!(trees::find_all_rule_nodes(&*result, csvparser::RULE_csvFile).is_empty());
+
+        let rule_indexes = trees::descendants(&*result)
+            .filter(|it| it.get_child_count() > 0)
+            .map(|it| it.get_rule_index())
+            .collect::<Vec<_>>();
+        assert_eq!(rule_indexes, vec![1, 2, 3, 3, 2, 3, 3, 2, 3]);
+        assert_eq!(trees::descendants(&*result).count(), 19);
+
+        let field = trees::find_all_rule_nodes(&*rows[0], csvparser::RULE_field);
+        let ancestors = trees::get_ancestors(&*field[1])
+            .iter()
+            .map(|it| it.get_rule_index())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ancestors,
+            vec![csvparser::RULE_csvFile, csvparser::RULE_hdr, csvparser::RULE_row]
+        );
+        assert!(trees::get_ancestors(&*result).is_empty());
+    }
+
+    #[test]
+    fn test_xpath() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer =
+            CSVLexer::new_with_token_factory(InputStream::new("h1,h2\nd1,d2\nd3\n".into()), &tf);
+        let token_source = CommonTokenStream::new(_lexer);
+        let mut parser = CSVParser::new(token_source);
+        let result = parser.csvFile().expect("parsed unsuccessfully");
+
+        let query = |path: &str| {
+            xpath::find_all(&*parser, result.clone(), path)
+                .unwrap()
+                .iter()
+                .map(|it| it.get_text())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(query("/csvFile"), vec!["h1,h2\nd1,d2\nd3\n"]);
+        assert_eq!(query("//TEXT"), vec!["h1", "h2", "d1", "d2", "d3"]);
+        assert_eq!(query("/csvFile/row/field"), vec!["d1", "d2", "d3"]);
+        assert_eq!(query("/csvFile/*/*/TEXT"), vec!["d1", "d2", "d3"]);
+        assert_eq!(query("/csvFile/hdr/*/*/TEXT"), vec!["h1", "h2"]);
+        assert_eq!(query("//row/','"), vec![",", ","]);
+        assert_eq!(query("/csvFile/!hdr"), vec!["d1,d2\n", "d3\n"]);
+        assert_eq!(query("//row//field"), vec!["h1", "h2", "d1", "d2", "d3"]);
+        assert_eq!(query("//field/!TEXT"), Vec::<String>::new());
+        assert!(query("/hdr").is_empty());
+
+        let xpath = XPath::new(&*parser, "//hdr//TEXT").unwrap();
+        assert_eq!(xpath.evaluate(result.clone()).len(), 2);
+    }
+
+    #[test]
+    fn test_xpath_invalid() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer = CSVLexer::new_with_token_factory(InputStream::new("".into()), &tf);
+        let token_source = CommonTokenStream::new(_lexer);
+        let parser = CSVParser::new(token_source);
+        let error = XPath::new(&*parser, "//row/ID").unwrap_err();
+        assert_eq!(
+            error,
+            XPathError::UnknownToken {
+                name: "ID".to_owned(),
+                index: 6
+            }
+        );
+        assert_eq!(error.to_string(), "ID at index 6 isn't a valid token name");
+        assert_eq!(
+            XPath::new(&*parser, "//expr").unwrap_err(),
+            XPathError::UnknownRule {
+                name: "expr".to_owned(),
+                index: 2
+            }
+        );
+        assert_eq!(
+            XPath::new(&*parser, "//row/").unwrap_err(),
+            XPathError::MissingElement { index: 6 }
+        );
+        assert_eq!(
+            XPath::new(&*parser, "//'x").unwrap_err(),
+            XPathError::InvalidSyntax { index: 2 }
+        );
+        assert_eq!(
+            XPath::new(&*parser, "/row?").unwrap_err(),
+            XPathError::InvalidSyntax { index: 4 }
+        );
+        assert_eq!(
+            XPath::new(&*parser, "'x'").unwrap_err(),
+            XPathError::UnexpectedElement { index: 0 }
+        );
+    }
+
+    type CSVStrParser<'a> = CSVParser<
+        'a,
+        CommonTokenStream<'a, CSVLexer<'a, InputStream<&'a str>>>,
+        DefaultErrorStrategy<'a, CSVParserContextType>,
+    >;
+
+    fn csv_pattern_matcher(parser: &CSVStrParser<'_>) -> ParseTreePatternMatcher {
+        let atn = CSVStrParser::get_serialized_atn();
+        ParseTreePatternMatcher::new(&**parser, atn, |text| {
+            let tf = ArenaCommonFactory::default();
+            let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(text.into()), &tf);
+            let mut tokens = Vec::new();
+            loop {
+                let token = lexer.next_token();
+                if token.get_token_type() == TOKEN_EOF {
+                    break tokens;
+                }
+                tokens.push(Token::to_owned(&*token));
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tree_pattern() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer = CSVLexer::new_with_token_factory(
+            InputStream::new("a,b\n1,\"s\"\n3,x\n".into()),
+            &tf,
+        );
+        let token_source = CommonTokenStream::new(_lexer);
+        let mut parser = CSVParser::new(token_source);
+        let result = parser.csvFile().expect("parsed unsuccessfully");
+        let rows = xpath::find_all(&*parser, result.clone(), "/csvFile/row").unwrap();
+
+        let matcher = csv_pattern_matcher(&parser);
+        let pattern = matcher
+            .compile("<x:field>,<TEXT>\n", csvparser::RULE_row)
+            .unwrap();
+        assert_eq!(pattern.get_pattern_rule_index(), csvparser::RULE_row);
+
+        let m = pattern.match_tree::<CSVParserContextType>(&rows[1]);
+        assert!(m.succeeded());
+        assert_eq!(m.get("x").unwrap().get_text(), "3");
+        assert_eq!(m.get("field").unwrap().get_text(), "3");
+        assert_eq!(m.get("TEXT").unwrap().get_text(), "x");
+        assert_eq!(m.get_all("x").len(), 1);
+        assert!(m.get("y").is_none());
+
+        let m = pattern.match_tree::<CSVParserContextType>(&rows[0]);
+        assert!(!m.succeeded());
+        assert_eq!(m.get_mismatched_node().unwrap().get_text(), "\"s\"");
+
+        let matches = pattern.find_all(&*parser, result.clone(), "//row").unwrap();
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].get("x").unwrap().get_text(), "a");
+
+        let pattern = matcher.compile("a,<TEXT>\n", csvparser::RULE_row).unwrap();
+        let matches = pattern.find_all(&*parser, result.clone(), "//row").unwrap();
+        assert_eq!(matches.len(), 1);
+        assert!(!pattern.matches::<CSVParserContextType>(&rows[1]));
+    }
+
+    #[test]
+    fn test_tree_pattern_errors() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer = CSVLexer::new_with_token_factory(InputStream::new("".into()), &tf);
+        let token_source = CommonTokenStream::new(_lexer);
+        let parser = CSVParser::new(token_source);
+
+        let matcher = csv_pattern_matcher(&parser);
+        assert!(matches!(
+            matcher.compile("<field>\n\n", csvparser::RULE_row),
+            Err(ANTLRError::OtherError(_))
+        ));
+        assert!(matches!(
+            matcher.compile("<TEXT>,,", csvparser::RULE_row),
+            Err(ANTLRError::NoAltError(_))
+        ));
+        assert!(matches!(
+            matcher.compile("<TEXT>\r", csvparser::RULE_row),
+            Err(ANTLRError::InputMismatchError(_))
+        ));
+    }
+
+    #[test]
+    fn test_tree_pattern_invalid_tags() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer = CSVLexer::new_with_token_factory(InputStream::new("".into()), &tf);
+        let token_source = CommonTokenStream::new(_lexer);
+        let parser = CSVParser::new(token_source);
+
+        let matcher = csv_pattern_matcher(&parser);
+        let compile_error = |pattern: &str| match matcher.compile(pattern, csvparser::RULE_row) {
+            Err(ANTLRError::OtherError(e)) => e
+                .downcast_ref::<InvalidPatternError>()
+                .expect("expected InvalidPatternError")
+                .clone(),
+            _ => panic!("pattern {} compiled unexpectedly", pattern),
+        };
+        assert_eq!(
+            compile_error("<value>"),
+            InvalidPatternError::UnknownRule("value".to_owned())
+        );
+        assert_eq!(
+            compile_error("<ID>\n"),
+            InvalidPatternError::UnknownToken("ID".to_owned())
+        );
+        assert_eq!(
+            compile_error("<x:1>"),
+            InvalidPatternError::InvalidTag("1".to_owned())
+        );
+        assert_eq!(compile_error("<TEXT"), InvalidPatternError::UnterminatedTag);
+        assert_eq!(compile_error("TEXT>"), InvalidPatternError::MissingStartTag);
+        assert_eq!(compile_error("><TEXT"), InvalidPatternError::TagsOutOfOrder);
+    }
+
+    #[test]
+    fn test_tree_pattern_invalid_atn() {
+        let tf = ArenaCommonFactory::default();
+        let mut _lexer = CSVLexer::new_with_token_factory(InputStream::new("".into()), &tf);
+        let token_source = CommonTokenStream::new(_lexer);
+        let parser = CSVParser::new(token_source);
+
+        let atn = CSVStrParser::get_serialized_atn();
+        let truncated = atn.chars().take(atn.chars().count() / 2).collect::<String>();
+        assert!(ParseTreePatternMatcher::new(&*parser, &truncated, |_| Vec::new()).is_err());
+    }
+
+    fn csv_lexer_interpreter(input: &str) -> LexerInterpreter<'_, InputStream<&str>> {
+        let atn = CSVLexer::<InputStream<&str>>::get_serialized_atn();
+        LexerInterpreter::new(
+            "CSVLexer.g4",
+            Box::new(VocabularyImpl::new(
+                _LITERAL_NAMES.iter(),
+                _SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            modeNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(ATNDeserializer::new(None).deserialize(atn.chars())),
+            InputStream::new(input),
+        )
+    }
+
+    #[test]
+    fn test_lexer_interpreter() {
+        let input = "a, \"b\"\"c\"\r\n1\n";
+        let mut expected = Vec::new();
+        let tf = ArenaCommonFactory::default();
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(input), &tf);
+        loop {
+            let token = lexer.next_token();
+            expected.push((token.get_token_type(), token.get_channel(), token.get_text().to_owned()));
+            if token.get_token_type() == TOKEN_EOF {
+                break;
+            }
+        }
+
+        let mut actual = Vec::new();
+        let mut lexer = csv_lexer_interpreter(input);
+        loop {
+            let token = lexer.next_token();
+            actual.push((token.get_token_type(), token.get_channel(), token.get_text().to_owned()));
+            if token.get_token_type() == TOKEN_EOF {
+                break;
+            }
+        }
+        assert_eq!(actual, expected);
+        assert_eq!(lexer.get_rule_names(), &ruleNames);
+        assert_eq!(lexer.get_mode_names(), &["DEFAULT_MODE".to_owned()]);
+    }
+
+    #[test]
+    fn test_parser_interpreter() {
+        let lexer = csv_lexer_interpreter("V123,V2\nd1,d2\n");
+        let mut parser = ParserInterpreter::new(
+            "CSV.g4",
+            Box::new(VocabularyImpl::new(
+                csvparser::_LITERAL_NAMES.iter(),
+                csvparser::_SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            csvparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(ATNDeserializer::new(None).deserialize(
+                CSVStrParser::get_serialized_atn().chars(),
+            )),
+            CommonTokenStream::new(lexer),
+        );
+        let result = parser.parse(csvparser::RULE_csvFile).unwrap();
+        assert_eq!(
+            result.to_string_tree(&*parser),
+            "(csvFile (hdr (row (field V123) , (field V2) \\n)) (row (field d1) , (field d2) \\n))"
+        );
+    }
+
+    #[test]
+    fn test_parser_interpreter_lr() {
+        let lexer = SimpleLRLexer::new(InputStream::new("x y z"));
+        let atn = SimpleLRParser::<
+            CommonTokenStream<SimpleLRLexer<InputStream<&str>>>,
+            DefaultErrorStrategy<SimpleLRParserContextType>,
+        >::get_serialized_atn();
+        let mut parser = ParserInterpreter::new(
+            "SimpleLR.g4",
+            Box::new(VocabularyImpl::new(
+                simplelrparser::_LITERAL_NAMES.iter(),
+                simplelrparser::_SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            simplelrparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(ATNDeserializer::new(None).deserialize(atn.chars())),
+            CommonTokenStream::new(lexer),
+        );
+        let result = parser.parse(simplelrparser::RULE_s).unwrap();
+        assert_eq!(result.to_string_tree(&*parser), "(s (a (a (a x) y) z))");
+    }
+
+    // same format as `.interp` files generated by ANTLR tool
+    fn interp_file(
+        literal_names: &[Option<&str>], symbolic_names: &[Option<&str>], rule_names: &[&str],
+        lexer_names: Option<(&[&str], &[&str])>, serialized_atn: &str,
+    ) -> String {
+        let mut result = String::new();
+        let escape = |name: &str| name.replace('\r', "\\r").replace('\n', "\\n");
+        result += "token literal names:\n";
+        for name in literal_names {
+            writeln!(result, "{}", name.map(escape).unwrap_or("null".to_owned())).unwrap();
+        }
+        result += "\ntoken symbolic names:\n";
+        for name in symbolic_names {
+            writeln!(result, "{}", name.unwrap_or("null")).unwrap();
+        }
+        result += "\nrule names:\n";
+        for name in rule_names {
+            writeln!(result, "{}", name).unwrap();
+        }
+        if let Some((channel_names, mode_names)) = lexer_names {
+            writeln!(result, "\nchannel names:\n{}", channel_names.join("\n")).unwrap();
+            writeln!(result, "\nmode names:\n{}", mode_names.join("\n")).unwrap();
+        }
+        let atn = serialized_atn
+            .chars()
+            .map(|it| it as u32)
+            .map(|it| if it > 0xFFFF { it - 0x3000 } else { it })
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>();
+        writeln!(result, "\natn:\n[{}]", atn.join(", ")).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_interpreter_data() {
+        let lexer_data: InterpreterData = interp_file(
+            &_LITERAL_NAMES,
+            &_SYMBOLIC_NAMES,
+            &ruleNames,
+            Some((&channelNames, &modeNames)),
+            CSVLexer::<InputStream<&str>>::get_serialized_atn(),
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(lexer_data.mode_names, Some(vec!["DEFAULT_MODE".to_owned()]));
+        assert_eq!(
+            lexer_data.channel_names,
+            Some(vec!["DEFAULT_TOKEN_CHANNEL".to_owned(), "HIDDEN".to_owned()])
+        );
+        assert_eq!(lexer_data.vocabulary.get_literal_name(T__1), Some("'\\r'"));
+        assert_eq!(lexer_data.vocabulary.get_symbolic_name(STRING), Some("STRING"));
+
+        let parser_data: InterpreterData = interp_file(
+            &csvparser::_LITERAL_NAMES,
+            &csvparser::_SYMBOLIC_NAMES,
+            &csvparser::ruleNames,
+            None,
+            CSVStrParser::get_serialized_atn(),
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(parser_data.rule_names, csvparser::ruleNames);
+        assert!(parser_data.mode_names.is_none());
+
+        let lexer: LexerInterpreter<_> =
+            lexer_data.create_lexer("CSVLexer.g4", InputStream::new("V123,V2\nd1,d2\n"));
+        let mut parser = parser_data.create_parser("CSV.g4", CommonTokenStream::new(lexer));
+        let result = parser.parse(csvparser::RULE_csvFile).unwrap();
+        assert_eq!(
+            result.to_string_tree(&*parser),
+            "(csvFile (hdr (row (field V123) , (field V2) \\n)) (row (field d1) , (field d2) \\n))"
+        );
+
+        let error = "token literal names:\nnull\n\nrule names:\nrow\nfield\n"
+            .parse::<InterpreterData>()
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "Missing token symbolic names section");
+    }
+
+    #[test]
+    fn test_dfa_export() {
+        let lexer = csv_lexer_interpreter("a,b\n1,2\n");
+        let mut parser = ParserInterpreter::new(
+            "CSV.g4",
+            Box::new(VocabularyImpl::new(
+                csvparser::_LITERAL_NAMES.iter(),
+                csvparser::_SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            csvparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(ATNDeserializer::new(None).deserialize(
+                CSVStrParser::get_serialized_atn().chars(),
+            )),
+            CommonTokenStream::new(lexer),
+        );
+        assert_eq!(parser.get_dfa_strings(), vec![""; 4]);
+        parser.parse(csvparser::RULE_csvFile).unwrap();
+        let dfa = parser.get_dfa_strings();
+        assert_eq!(dfa.len(), 4);
+        assert_eq!(dfa[0], "s0-EOF->:s1=>2\n");
+        assert_eq!(dfa[1], "s0-','->:s1=>1\ns0-'\n'->:s2=>2\n");
+        assert_eq!(dfa[3], "s0-TEXT->:s1=>1\n");
+        assert_eq!(
+            parser.get_dfa_dot_graphs()[3],
+            "digraph DFA3 {\n\
+             rankdir=LR;\n\
+             s0[fontsize=11, label=\"s0\", shape=circle, fixedsize=true, width=.55, peripheries=1];\n\
+             s1[fontsize=11, label=\"s1=>1\", shape=doublecircle, fixedsize=false, peripheries=1];\n\
+             s0 -> s1 [fontsize=11, fontname=\"Courier\", arrowsize=.7, label = \"TEXT\"];\n\
+             }\n"
+        );
+
+        let mut lexer = csv_lexer_interpreter("ab,\"\"\n");
+        while lexer.next_token().get_token_type() != TOKEN_EOF {}
+        let dfa = lexer.get_dfa_strings();
+        assert_eq!(dfa.len(), 1);
+        assert!(dfa[0].starts_with("s0-'\n'->:s5=>3\n"), "{}", dfa[0]);
+        assert!(dfa[0].contains("s0-'a'->:s1=>5\n:s1=>5-'b'->:s1=>5\n"), "{}", dfa[0]);
+        let dot = &lexer.get_dfa_dot_graphs()[0];
+        assert!(dot.starts_with("digraph DFA0 {\n"), "{}", dot);
+        assert!(dot.contains("label = \"'\\\"'\""), "{}", dot);
+    }
+
+    #[test]
+    fn test_atn_dot() {
+        let atn = ATNDeserializer::new(None).deserialize(
+            SimpleLRParser::<
+                CommonTokenStream<SimpleLRLexer<InputStream<&str>>>,
+                DefaultErrorStrategy<SimpleLRParserContextType>,
+            >::get_serialized_atn()
+            .chars(),
+        );
+        let vocabulary = VocabularyImpl::new(
+            simplelrparser::_LITERAL_NAMES.iter(),
+            simplelrparser::_SYMBOLIC_NAMES.iter(),
+            None,
+        );
+        let generator = DOTGenerator::new(&atn, &simplelrparser::ruleNames, &vocabulary);
+        let graphs = generator.get_dot_graphs();
+        assert_eq!(graphs.len(), 2);
+        assert!(graphs[0].contains("s4 -> s5 [label=\"<a>[0]\", style=bold];\n"));
+        assert_eq!(
+            graphs[1],
+            "digraph \"a\" {\n\
+             rankdir=LR;\n\
+             ranksep=.25;\n\
+             label=\"a (left recursive)\";\n\
+             node [shape=circle, fontsize=11, fixedsize=true, width=.55];\n\
+             edge [fontsize=11, fontname=\"Courier\", arrowsize=.7];\n\
+             s2[label=\"s2\"];\n\
+             s6[label=\"s6\"];\n\
+             s7[label=\"s7\"];\n\
+             s8[label=\"s8\"];\n\
+             s13[label=\"s13\\nd=0\\nprecedence\", fixedsize=false];\n\
+             s11[label=\"s11\"];\n\
+             s14[label=\"s14\"];\n\
+             s9[label=\"s9\"];\n\
+             s3[shape=doublecircle, width=.45];\n\
+             s10[label=\"s10\"];\n\
+             s12[label=\"s12\"];\n\
+             s15[label=\"s15\"];\n\
+             s2 -> s6 [label=\"ε\", style=dashed];\n\
+             s6 -> s7 [label=\"action_1:-1\", style=dashed];\n\
+             s7 -> s8 [label=\"ID\"];\n\
+             s8 -> s13 [label=\"ε\", style=dashed];\n\
+             s13 -> s11 [label=\"ε\", style=dashed];\n\
+             s13 -> s14 [label=\"ε\", style=dashed];\n\
+             s11 -> s9 [label=\"ε\", style=dashed];\n\
+             s14 -> s3 [label=\"ε\", style=dashed];\n\
+             s9 -> s10 [label=\"{2>=prec}?\", style=dashed];\n\
+             s10 -> s12 [label=\"ID\"];\n\
+             s12 -> s15 [label=\"ε\", style=dashed];\n\
+             s15 -> s13 [label=\"ε\", style=dashed];\n\
+             }\n"
+        );
+
+        let atn = ATNDeserializer::new(None)
+            .deserialize(CSVLexer::<InputStream<&str>>::get_serialized_atn().chars());
+        let vocabulary = VocabularyImpl::new(
+            csvlexer::_LITERAL_NAMES.iter(),
+            csvlexer::_SYMBOLIC_NAMES.iter(),
+            None,
+        );
+        let generator = DOTGenerator::new(&atn, &csvlexer::ruleNames, &vocabulary);
+        let text = generator.get_rule_dot(csvlexer::TEXT as usize - 1);
+        assert!(text.starts_with("digraph \"TEXT\" {\n"), "{}", text);
+        assert!(text.contains("s26 -> s28 [label=\"~{'\\\\n', '\\\\r', ' ', '\\\"', ','}\"];\n"));
+        assert!(text.contains("s29[label=\"s29\\nd=2\", fixedsize=false];\n"));
+    }
+
+    #[test]
+    fn test_verify_atn() {
+        let deserializer = ATNDeserializer::new(None);
+        let mut atn = deserializer.deserialize(CSVStrParser::get_serialized_atn().chars());
+        assert_eq!(deserializer.verify_atn(&atn), Ok(()));
+
+        let entry = atn
+            .states
+            .iter_mut()
+            .find_map(|state| match state.get_state_type_mut() {
+                ATNStateType::DecisionState {
+                    nongreedy,
+                    state: ATNDecisionState::StarLoopEntry { .. },
+                    ..
+                } => {
+                    *nongreedy = true;
+                    Some(state.get_state_number())
+                }
+                _ => None,
+            })
+            .unwrap();
+        let error = deserializer.verify_atn(&atn).unwrap_err();
+        assert_eq!(error.state, entry);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "invalid ATN state {}: star loop entry state that enters loop first must be greedy",
+                entry
+            )
+        );
+    }
+
+    #[test]
+    fn test_try_deserialize() {
+        let deserializer = ATNDeserializer::new(None);
+        let serialized = CSVStrParser::get_serialized_atn();
+        assert!(deserializer.try_deserialize(serialized.chars()).is_ok());
+
+        let with_value = |index: usize, value: u32| -> String {
+            let mut chars = serialized.chars().collect::<Vec<_>>();
+            chars[index] = std::char::from_u32(value).unwrap();
+            chars.into_iter().collect()
+        };
+        let error = deserializer
+            .try_deserialize(with_value(0, 7).chars())
+            .unwrap_err();
+        assert_eq!(error, ATNDeserializationError::UnsupportedVersion(7));
+        assert_eq!(error.to_string(), "unsupported ATN version 7 (expected 3 or 4)");
+        assert!(matches!(
+            deserializer.try_deserialize(with_value(1, 0x1234).chars()),
+            Err(ATNDeserializationError::UnsupportedUuid(_))
+        ));
+        // first state type goes after version, uuid, grammar type, max token type and state count
+        let error = deserializer
+            .try_deserialize(with_value(12, 99 + 2).chars())
+            .unwrap_err();
+        assert_eq!(error, ATNDeserializationError::InvalidStateType(99));
+        assert_eq!(error.to_string(), "invalid ATN state type 99");
+
+        let truncated = serialized.chars().take(serialized.chars().count() / 2);
+        assert_eq!(
+            deserializer
+                .try_deserialize(truncated.collect::<String>().chars())
+                .unwrap_err(),
+            ATNDeserializationError::UnexpectedEnd
+        );
+
+        // no states, no non-greedy and precedence states, negative rule count
+        let error = deserializer
+            .try_deserialize_ints(&[4, 1, 1, 0, 0, 0, -1])
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ATNDeserializationError::InvalidCount {
+                kind: "rules",
+                count: -1
+            }
+        );
+        assert_eq!(error.to_string(), "invalid number of rules -1");
+        // state count larger than the rest of the data
+        assert_eq!(
+            deserializer
+                .try_deserialize_ints(&[4, 1, 1, 0x7FFF_FFFF, 0])
+                .unwrap_err(),
+            ATNDeserializationError::InvalidCount {
+                kind: "states",
+                count: 0x7FFF_FFFF
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_version_4() {
+        // s : A ;
+        #[rustfmt::skip]
+        let serialized = [
+            4, 1, 1, // version, grammar type, max token type
+            4, 2, 0, 7, 0, 1, 0, 1, 0, // states
+            0, 0, // non-greedy and precedence states
+            1, 0, // rules
+            0, // modes
+            0, // sets
+            3, 0, 2, 1, 0, 0, 0, 2, 3, 5, 1, 0, 0, 3, 1, 1, 0, 0, 0, // edges
+            0, // decisions
+        ];
+        let deserializer = ATNDeserializer::new(None);
+        let atn = deserializer.deserialize_ints(&serialized);
+        assert_eq!(atn.max_token_type, 1);
+        assert_eq!(atn.rule_to_start_state, vec![0]);
+        assert_eq!(atn.rule_to_stop_state, vec![1]);
+        assert_eq!(atn.states.len(), 4);
+        let vocabulary =
+            VocabularyImpl::new([None, Some("'a'")].iter(), [None, Some("A")].iter(), None);
+        assert_eq!(
+            DOTGenerator::new(&atn, &["s"], &vocabulary)
+                .get_rule_dot(0)
+                .lines()
+                .filter(|it| it.contains("->"))
+                .collect::<Vec<_>>(),
+            vec![
+                "s0 -> s2 [label=\"ε\", style=dashed];",
+                "s2 -> s3 [label=\"'a'\"];",
+                "s3 -> s1 [label=\"ε\", style=dashed];",
+            ]
+        );
+
+        // values that don't fit into 15 bits take two 16-bit words in Java-style string encoding
+        let mut words = serialized.iter().map(|&it| it as u32).collect::<Vec<_>>();
+        words.splice(2..3, vec![0x8001, 0x1170]);
+        let encoded = words
+            .into_iter()
+            .map(|it| std::char::from_u32(it).unwrap())
+            .collect::<String>();
+        let atn = deserializer.deserialize(encoded.chars());
+        assert_eq!(atn.max_token_type, 0x11170);
+        assert_eq!(atn.states.len(), 4);
+
+        let data = serialized
+            .iter()
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let data = format!(
+            "token literal names:\nnull\n'a'\n\ntoken symbolic names:\nnull\nA\n\n\
+             rule names:\ns\n\natn:\n[{}]\n",
+            data
+        )
+        .parse::<InterpreterData>()
+        .unwrap();
+        assert_eq!(data.atn.states.len(), 4);
+        assert_eq!(data.rule_names, vec!["s"]);
+    }
+
+    #[test]
+    fn test_atn_serializer() {
+        let serialized_atns = [
+            CSVStrParser::get_serialized_atn(),
+            CSVLexer::<InputStream<&str>>::get_serialized_atn(),
+            XMLLexer::<InputStream<&str>>::get_serialized_atn(),
+            SimpleLRParser::<
+                CommonTokenStream<SimpleLRLexer<InputStream<&str>>>,
+                DefaultErrorStrategy<SimpleLRParserContextType>,
+            >::get_serialized_atn(),
+        ];
+        let deserializer = ATNDeserializer::new(None);
+        let vocabulary = VocabularyImpl::new(None::<&Option<&str>>, None, None);
+        for serialized in serialized_atns.iter() {
+            let atn = deserializer.deserialize(serialized.chars());
+            let data = ATNSerializer::new(&atn).serialize();
+            assert_eq!(data[0], 4);
+
+            let from_ints = deserializer.deserialize_ints(&data);
+            let from_string =
+                deserializer.deserialize(ATNSerializer::new(&atn).serialize_to_string().chars());
+            assert_eq!(ATNSerializer::new(&from_ints).serialize(), data);
+            assert_eq!(ATNSerializer::new(&from_string).serialize(), data);
+
+            let graphs = DOTGenerator::new(&atn, &[], &vocabulary).get_dot_graphs();
+            assert_eq!(
+                DOTGenerator::new(&from_ints, &[], &vocabulary).get_dot_graphs(),
+                graphs
+            );
+            assert_eq!(from_ints.mode_to_start_state, atn.mode_to_start_state);
+            assert_eq!(from_ints.rule_to_token_type, atn.rule_to_token_type);
+            assert_eq!(from_ints.decision_to_state, atn.decision_to_state);
+        }
+
+        // lexer actions and modes survive round trip,
+        // input avoids tags because `CLOSE` pops mode in a custom action
+        let atn =
+            deserializer.deserialize(XMLLexer::<InputStream<&str>>::get_serialized_atn().chars());
+        let atn = deserializer.deserialize_ints(&ATNSerializer::new(&atn).serialize());
+        let input = "<?xml version=\"1.0\"?>\n<!--c-->text &amp; &#42;";
+        let mut lexer = LexerInterpreter::<_>::new(
+            "XMLLexer.g4",
+            Box::new(VocabularyImpl::new(
+                xmllexer::_LITERAL_NAMES.iter(),
+                xmllexer::_SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            xmllexer::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            xmllexer::modeNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(atn),
+            InputStream::new(input),
+        );
+        let mut expected = XMLLexer::new(InputStream::new(input));
+        loop {
+            let token = lexer.next_token();
+            let expected = expected.next_token();
+            assert_eq!(token.to_string(), expected.to_string());
+            if token.get_token_type() == TOKEN_EOF {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_profiling() {
+        let lexer = ReferenceToATNLexer::new_with_token_factory(
+            InputStream::new_owned("a 34 b 12".to_owned().into_boxed_str()),
+            &FACTORY,
+        );
+        let mut parser = ReferenceToATNParser::new(CommonTokenStream::new(lexer));
+        assert!(parser.get_interpreter().get_parse_info().is_none());
+        parser.get_interpreter().set_profile(true);
+        parser.a().unwrap();
+        let info = parser.get_interpreter().get_parse_info().unwrap();
+
+        // loop decision is predicted before each token and at EOF
+        let decision = &info.get_decision_info()[0];
+        assert_eq!(decision.invocations, 5);
+        assert_eq!(decision.sll_max_look, 2);
+        let max_look = decision.sll_max_look_event.as_ref().unwrap();
+        assert_eq!(
+            (max_look.event.start_index, max_look.event.stop_index),
+            (1, 2)
+        );
+        assert_eq!(
+            decision.sll_atn_transitions + decision.sll_dfa_transitions,
+            decision.sll_total_look
+        );
+        // trailing `12` can be matched either by the loop or by `ATN?`
+        assert_eq!(decision.ll_fallback, 1);
+        assert_eq!(decision.ll_atn_transitions, 1);
+        assert_eq!(info.get_ll_decisions(), vec![0]);
+        assert_eq!(decision.ambiguities.len(), 1);
+        let ambiguity = &decision.ambiguities[0];
+        assert!(ambiguity.event.full_ctx);
+        assert_eq!(ambiguity.event.start_index, 3);
+        assert_eq!(ambiguity.ambig_alts.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert!(decision.errors.is_empty());
+        assert!(decision.context_sensitivities.is_empty());
+        assert_eq!(info.get_decision_info()[1].invocations, 0);
+
+        parser.get_interpreter().set_profile(false);
+        assert!(parser.get_interpreter().get_parse_info().is_none());
+    }
+
+    #[test]
+    fn test_profiling_error() {
+        // interpreter predicts every decision and bail strategy doesn't sync before them,
+        // so row at EOF fails in prediction
+        let lexer = csv_lexer_interpreter("a,,");
+        let mut parser = ParserInterpreter::with_strategy(
+            "CSV.g4",
+            Box::new(VocabularyImpl::new(
+                csvparser::_LITERAL_NAMES.iter(),
+                csvparser::_SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            csvparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(ATNDeserializer::new(None).deserialize(
+                CSVStrParser::get_serialized_atn().chars(),
+            )),
+            CommonTokenStream::new(lexer),
+            BailErrorStrategy::new(),
+        );
+        parser.remove_error_listeners();
+        parser.get_interpreter().set_profile(true);
+        let result = parser.parse(csvparser::RULE_row);
+        assert!(matches!(result, Err(ANTLRError::FallThrough(_))));
+        let info = parser.get_interpreter().get_parse_info().unwrap();
+
+        // `(',' field)*` loop is predicted after each of three fields, last prediction fails at EOF
+        let decision = &info.get_decision_info()[1];
+        assert_eq!(decision.errors.len(), 1);
+        assert_eq!(decision.invocations, 3);
+        assert_eq!(decision.sll_total_look, 3);
+    }
+
+    fn two_stage_csv(input: &str) -> (ParseStage, String, Vec<String>) {
+        let tf = ArenaCommonFactory::default();
+        let lexer = CSVLexer::new_with_token_factory(InputStream::new(input), &tf);
+        let mut parser = CSVParser::with_dyn_strategy(CommonTokenStream::new(lexer));
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        parser.remove_error_listeners();
+        parser.add_error_listener(Box::new(SourceNameCollector(errors.clone())));
+        let (tree, stage) =
+            parse_two_stage(&mut parser, CSVParser::set_error_strategy, |parser| {
+                parser.csvFile()
+            })
+            .unwrap();
+        assert_eq!(
+            parser.get_interpreter().get_prediction_mode(),
+            PredictionMode::LL
+        );
+        let tree = tree.to_string_tree(&*parser);
+        let errors = errors.borrow().clone();
+        (stage, tree, errors)
+    }
+
+    #[test]
+    fn test_two_stage_parse() {
+        let (stage, tree, errors) = two_stage_csv("h1,h2\nd1,d2\n");
+        assert_eq!(stage, ParseStage::SLL);
+        assert_eq!(
+            tree,
+            "(csvFile (hdr (row (field h1) , (field h2) \\n)) (row (field d1) , (field d2) \\n))"
+        );
+        assert!(errors.is_empty());
+
+        // SLL stage bails out silently, so error is reported only once by LL stage
+        let (stage, tree, errors) = two_stage_csv("h1,h2\nd1,d2");
+        assert_eq!(stage, ParseStage::LL);
+        assert_eq!(
+            tree,
+            "(csvFile (hdr (row (field h1) , (field h2) \\n)) (row (field d1) , (field d2) <missing '\\n'>))"
+        );
+        assert_eq!(
+            errors,
+            vec!["<unknown>: line 2:5 extraneous input '<EOF>' expecting {',', '\r', '\n'}"]
+        );
+    }
+
+    #[test]
+    fn test_two_stage_parse_error_recovery() {
+        let (stage, tree, errors) = two_stage_csv("");
+        assert_eq!(stage, ParseStage::LL);
+        // SLL stage fails on the first token and leaves its strategy in error recovery mode,
+        // which must not suppress the same error and change recovery in LL stage
+        assert_eq!(
+            errors,
+            vec!["<unknown>: line 1:0 no viable alternative at input '<EOF>'"]
+        );
+        assert_eq!(
+            tree,
+            "(csvFile (hdr (row field)) (row (field <EOF>) <missing '\\n'>))"
+        );
+    }
+
+    #[test]
+    fn test_two_stage_parse_interpreter() {
+        let tf = ArenaCommonFactory::default();
+        let lexer = CSVLexer::new_with_token_factory(InputStream::new("h1,h2\nd1,d2"), &tf);
+        let mut parser = ParserInterpreter::with_strategy(
+            "CSV.g4",
+            Box::new(VocabularyImpl::new(
+                csvparser::_LITERAL_NAMES.iter(),
+                csvparser::_SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            csvparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(ATNDeserializer::new(None).deserialize(
+                CSVStrParser::get_serialized_atn().chars(),
+            )),
+            CommonTokenStream::new(lexer),
+            Box::new(DefaultErrorStrategy::new()) as DynErrorStrategy<_>,
+        );
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        parser.remove_error_listeners();
+        parser.add_error_listener(Box::new(SourceNameCollector(errors.clone())));
+        let (tree, stage) = parse_two_stage(
+            &mut parser,
+            |parser, strategy| parser.err_handler = strategy,
+            |parser| parser.parse(csvparser::RULE_csvFile),
+        )
+        .unwrap();
+        assert_eq!(stage, ParseStage::LL);
+        assert_eq!(
+            *errors.borrow(),
+            vec!["<unknown>: line 2:5 mismatched input '<EOF>' expecting {',', '\r', '\n'}"]
+        );
+        assert_eq!(
+            tree.to_string_tree(&*parser),
+            "(csvFile (hdr (row (field h1) , (field h2) \\n)) (row (field d1) , (field d2)))"
+        );
+    }
+
+    #[test]
+    fn test_dfa_cache() {
+        let csv_parser = |input: &'static str| {
+            ParserInterpreter::new(
+                "CSV.g4",
+                Box::new(VocabularyImpl::new(
+                    csvparser::_LITERAL_NAMES.iter(),
+                    csvparser::_SYMBOLIC_NAMES.iter(),
+                    None,
+                )),
+                csvparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+                Arc::new(ATNDeserializer::new(None).deserialize(
+                    CSVStrParser::get_serialized_atn().chars(),
+                )),
+                CommonTokenStream::new(csv_lexer_interpreter(input)),
+            )
+        };
+        let lr_parser = |input: &'static str| {
+            let atn = SimpleLRParser::<
+                CommonTokenStream<SimpleLRLexer<InputStream<&str>>>,
+                DefaultErrorStrategy<SimpleLRParserContextType>,
+            >::get_serialized_atn();
+            ParserInterpreter::new(
+                "SimpleLR.g4",
+                Box::new(VocabularyImpl::new(
+                    simplelrparser::_LITERAL_NAMES.iter(),
+                    simplelrparser::_SYMBOLIC_NAMES.iter(),
+                    None,
+                )),
+                simplelrparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+                Arc::new(ATNDeserializer::new(None).deserialize(atn.chars())),
+                CommonTokenStream::new(SimpleLRLexer::new(InputStream::new(input))),
+            )
+        };
+        let input = "h1,\"h 2\"\r\nd1,d2\n,\n";
+
+        let mut lexer = csv_lexer_interpreter(input);
+        while lexer.next_token().get_token_type() != TOKEN_EOF {}
+        let mut lexer_dfa = Vec::new();
+        lexer.get_interpreter().unwrap().save_dfa(&mut lexer_dfa).unwrap();
+        let loaded = csv_lexer_interpreter("");
+        assert!(loaded.get_interpreter().unwrap().load_dfa(&mut lexer_dfa.as_slice()).unwrap());
+        assert_eq!(loaded.get_dfa_strings(), lexer.get_dfa_strings());
+
+        let mut parser = csv_parser(input);
+        parser.parse(csvparser::RULE_csvFile).unwrap();
+        let mut parser_dfa = Vec::new();
+        parser.get_interpreter().save_dfa(&mut parser_dfa).unwrap();
+        let mut loaded = csv_parser(input);
+        assert!(loaded.get_interpreter().load_dfa(&mut parser_dfa.as_slice()).unwrap());
+        assert_eq!(loaded.get_dfa_strings(), parser.get_dfa_strings());
+        // all predictions for the same input are made by the loaded DFA
+        loaded.get_interpreter().set_profile(true);
+        let tree = loaded.parse(csvparser::RULE_csvFile).unwrap();
+        assert_eq!(
+            tree.to_string_tree(&*loaded),
+            "(csvFile (hdr (row (field h1) , (field \"h 2\") \\r \\n)) (row (field d1) , (field d2) \\n) (row field , field \\n))"
+        );
+        let parse_info = loaded.get_interpreter().get_parse_info().unwrap();
+        assert!(parse_info.get_total_sll_lookahead_ops() > 0);
+        assert_eq!(parse_info.get_total_atn_lookahead_ops(), 0);
+
+        // precedence DFA
+        let mut parser = lr_parser("x y z");
+        parser.parse(simplelrparser::RULE_s).unwrap();
+        let mut lr_dfa = Vec::new();
+        parser.get_interpreter().save_dfa(&mut lr_dfa).unwrap();
+        let mut loaded = lr_parser("x y z");
+        assert!(loaded.get_interpreter().load_dfa(&mut lr_dfa.as_slice()).unwrap());
+        assert_eq!(loaded.get_dfa_strings(), parser.get_dfa_strings());
+        let tree = loaded.parse(simplelrparser::RULE_s).unwrap();
+        assert_eq!(tree.to_string_tree(&*loaded), "(s (a (a (a x) y) z))");
+
+        // corrupted precedence DFA start state is rejected when loading, not at prediction
+        let decision_to_dfa = parser.get_interpreter().decision_to_dfa();
+        let precedence_dfa = decision_to_dfa
+            .iter()
+            .find(|it| it.read().is_precedence_dfa())
+            .unwrap();
+        let s0 = precedence_dfa.read().s0.unwrap();
+        let load_corrupted = || {
+            let mut corrupted = Vec::new();
+            parser.get_interpreter().save_dfa(&mut corrupted).unwrap();
+            lr_parser("x y z")
+                .get_interpreter()
+                .load_dfa(&mut corrupted.as_slice())
+                .unwrap_err()
+                .to_string()
+        };
+        precedence_dfa.write().s0 = None;
+        assert_eq!(load_corrupted(), "Precedence DFA without start state");
+        precedence_dfa.write().s0 = Some(s0);
+        precedence_dfa.write().states[s0].edges[0] = ERROR_DFA_STATE_REF;
+        assert_eq!(load_corrupted(), "Error edge in precedence DFA start state");
+
+        // DFA saved in the older format is ignored
+        let mut old_format = lexer_dfa.clone();
+        old_format[8..12].copy_from_slice(&1i32.to_le_bytes());
+        assert!(!csv_lexer_interpreter("")
+            .get_interpreter()
+            .unwrap()
+            .load_dfa(&mut old_format.as_slice())
+            .unwrap());
+
+        // DFA saved for a different ATN is ignored
+        let parser = csv_parser(input);
+        assert!(!parser.get_interpreter().load_dfa(&mut lr_dfa.as_slice()).unwrap());
+        assert!(!parser.get_interpreter().load_dfa(&mut lexer_dfa.as_slice()).unwrap());
+        assert_eq!(parser.get_dfa_strings(), csv_parser(input).get_dfa_strings());
+        assert!(parser
+            .get_interpreter()
+            .load_dfa(&mut &parser_dfa[..parser_dfa.len() - 1])
+            .is_err());
+
+        // corrupted number of lexer DFA edges is rejected before allocating them
+        let mut corrupted = lexer_dfa.clone();
+        for chunk in corrupted[8..].chunks_exact_mut(4) {
+            if chunk == 128i32.to_le_bytes() {
+                chunk.copy_from_slice(&i32::MAX.to_le_bytes());
+            }
+        }
+        let err = csv_lexer_interpreter("")
+            .get_interpreter()
+            .unwrap()
+            .load_dfa(&mut corrupted.as_slice())
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Invalid number of DFA edges 2147483647");
+    }
+
+    #[test]
+    fn test_dfa_clear_and_limit() {
+        let input = "h1,\"h 2\"\r\nd1,d2\n,\nd3,d4,d5\n";
+        let csv_parser = |input: &'static str| {
+            ParserInterpreter::new(
+                "CSV.g4",
+                Box::new(VocabularyImpl::new(
+                    csvparser::_LITERAL_NAMES.iter(),
+                    csvparser::_SYMBOLIC_NAMES.iter(),
+                    None,
+                )),
+                csvparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+                Arc::new(ATNDeserializer::new(None).deserialize(
+                    CSVStrParser::get_serialized_atn().chars(),
+                )),
+                CommonTokenStream::new(csv_lexer_interpreter(input)),
+            )
+        };
+
+        let mut parser = csv_parser(input);
+        assert_eq!(parser.get_interpreter().get_cache_size(), CacheSize::default());
+        let tree = parser.parse(csvparser::RULE_csvFile).unwrap().to_string_tree(&*parser);
+        let size = parser.get_interpreter().get_cache_size();
+        assert!(size.dfa_states > 0);
+        assert!(size.approximate_bytes > 0);
+        let dfa = parser.get_dfa_strings();
+
+        parser.get_interpreter().clear_dfa();
+        assert_eq!(parser.get_interpreter().get_cache_size(), CacheSize::default());
+        parser.reset();
+        parser.input.seek(0);
+        let reparsed = parser.parse(csvparser::RULE_csvFile).unwrap().to_string_tree(&*parser);
+        assert_eq!(reparsed, tree);
+        assert_eq!(parser.get_interpreter().get_cache_size(), size);
+        assert_eq!(parser.get_dfa_strings(), dfa);
+
+        let tokenize = |limit| {
+            let mut lexer = csv_lexer_interpreter(input);
+            lexer.get_interpreter().unwrap().set_dfa_state_limit(limit);
+            let mut tokens = Vec::new();
+            let mut max_states = 0;
+            loop {
+                let token_type = lexer.next_token().get_token_type();
+                tokens.push(token_type);
+                let states = lexer.get_interpreter().unwrap().get_cache_size().dfa_states;
+                max_states = max_states.max(states);
+                if token_type == TOKEN_EOF {
+                    break (tokens, max_states);
+                }
+            }
+        };
+        let (tokens, max_states) = tokenize(None);
+        let (limited_tokens, limited_max_states) = tokenize(Some(2));
+        assert_eq!(limited_tokens, tokens);
+        assert!(limited_max_states < max_states);
+
+        let mut limited = csv_parser(input);
+        limited.get_interpreter().set_dfa_state_limit(Some(1));
+        let result = limited.parse(csvparser::RULE_csvFile).unwrap();
+        assert_eq!(result.to_string_tree(&*limited), tree);
+        assert!(limited.get_interpreter().get_cache_size().dfa_states < size.dfa_states);
+    }
+
+    #[test]
+    fn test_lexer_unicode_dfa_edges() {
+        let input = "привет,мир\n日本,🎉\n";
+        let mut lexer = csv_lexer_interpreter(input);
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token();
+            if token.get_token_type() == TOKEN_EOF {
+                break;
+            }
+            tokens.push(token.get_text().to_owned());
+        }
+        assert_eq!(tokens, ["привет", ",", "мир", "\n", "日本", ",", "🎉", "\n"]);
+
+        let dfa = lexer.get_dfa_strings().concat();
+        for edge in &["-'п'->", "-'р'->", "-'日'->", "-'🎉'->"] {
+            assert!(dfa.contains(edge), "{} is missing in\n{}", edge, dfa);
+        }
+
+        let mut saved = Vec::new();
+        lexer.get_interpreter().unwrap().save_dfa(&mut saved).unwrap();
+        let loaded = csv_lexer_interpreter("");
+        assert!(loaded.get_interpreter().unwrap().load_dfa(&mut saved.as_slice()).unwrap());
+        assert_eq!(loaded.get_dfa_strings().concat(), dfa);
+    }
+
+    #[test]
+    fn test_incremental_relex() {
+        let tf = ArenaCommonFactory::default();
+        let old_text = "h1,h2\nd1,d2\nd3,d4\nd5,d6\n";
+        let new_text = "h1,h2\nd1,d2\nd3,xyz\nd5,d6\n";
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(old_text), &tf);
+        let old = incremental::lex_all(&mut lexer);
+
+        let source = ListTokenSource::new(incremental::get_tokens(&old), &tf);
+        let mut parser = CSVParser::new(CommonTokenStream::new(source));
+        parser.set_track_lookahead(true);
+        let tree = parser.csvFile().expect("parsed unsuccessfully");
+
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(new_text), &tf);
+        let relexed = incremental::relex(&mut lexer, &old, &TextEdit::new(15, 2, 3));
+        // only `,xyz` has been lexed again
+        assert_eq!(relexed.old_range, 9..11);
+        assert_eq!(relexed.new_range, 9..11);
+
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(new_text), &tf);
+        let expected = incremental::lex_all(&mut lexer);
+        assert_eq!(relexed.tokens.len(), expected.len());
+        // token indexes of the reused tokens have been set by the parser
+        let fields = |token: &CommonToken| {
+            (
+                token.get_token_type(),
+                token.get_text().to_owned(),
+                token.get_start(),
+                token.get_stop(),
+                token.get_line(),
+                token.get_column(),
+            )
+        };
+        for (actual, expected) in relexed.tokens.iter().zip(&expected) {
+            assert_eq!(fields(actual.token), fields(expected.token));
+            assert_eq!(actual.state, expected.state);
+            assert_eq!(actual.lookahead, expected.lookahead);
+        }
+        // shifted tokens still borrow their text from the input
+        let shifted = &relexed.tokens[relexed.new_range.end..relexed.tokens.len() - 1];
+        assert!(shifted
+            .iter()
+            .all(|it| matches!(it.token.text, Cow::Borrowed(_))));
+
+        let reusable = incremental::find_reusable_subtrees(
+            &*tree,
+            parser.get_rule_lookahead().unwrap(),
+            &relexed.old_range,
+        );
+        let reusable = reusable.iter().map(|it| it.get_text()).collect::<Vec<_>>();
+        assert_eq!(reusable, vec!["h1,h2\n", "d1,d2\n", "d5,d6\n"]);
+
+        let source = ListTokenSource::new(incremental::get_tokens(&relexed.tokens), &tf);
+        let mut parser = CSVParser::new(CommonTokenStream::new(source));
+        let tree = parser.csvFile().expect("parsed unsuccessfully");
+        assert_eq!(
+            tree.to_string_tree(&*parser),
+            "(csvFile (hdr (row (field h1) , (field h2) \\n)) (row (field d1) , (field d2) \\n) \
+             (row (field d3) , (field xyz) \\n) (row (field d5) , (field d6) \\n))"
+        );
+
+        // shifted tokens have byte offsets in the new input
+        let utf8_tf = ArenaUtf8Factory::default();
+        let utf8_lexer = |text| {
+            LexerInterpreter::new_with_token_factory(
+                "CSVLexer.g4",
+                Box::new(VocabularyImpl::new(
+                    _LITERAL_NAMES.iter(),
+                    _SYMBOLIC_NAMES.iter(),
+                    None,
+                )),
+                ruleNames.iter().map(|&it| it.to_owned()).collect(),
+                modeNames.iter().map(|&it| it.to_owned()).collect(),
+                Arc::new(
+                    ATNDeserializer::new(None)
+                        .deserialize(CSVLexer::<InputStream<&str>>::get_serialized_atn().chars()),
+                ),
+                Utf8CharStream::new(text),
+                &utf8_tf,
+            )
+        };
+        let old = incremental::lex_all(&mut utf8_lexer("é,h2\nd1\n"));
+        let relexed = incremental::relex(
+            &mut utf8_lexer("éé,h2\nd1\n"),
+            &old,
+            &TextEdit::new(0, 1, 2),
+        );
+        assert_eq!(relexed.new_range, 0..1);
+        let expected = incremental::lex_all(&mut utf8_lexer("éé,h2\nd1\n"));
+        let bytes = |lexed: &[LexedToken<&Utf8Token<'_>>]| {
+            lexed
+                .iter()
+                .map(|it| {
+                    (
+                        it.token.get_text().to_owned(),
+                        it.token.byte_start,
+                        it.token.byte_end,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bytes(&relexed.tokens), bytes(&expected));
+    }
+
+    #[test]
+    fn test_incremental_reparse() {
+        let tf = ArenaCommonFactory::default();
+        let csv_parser = |tokens| {
+            let mut parser = ParserInterpreter::new(
+                "CSV.g4",
+                Box::new(VocabularyImpl::new(
+                    csvparser::_LITERAL_NAMES.iter(),
+                    csvparser::_SYMBOLIC_NAMES.iter(),
+                    None,
+                )),
+                csvparser::ruleNames.iter().map(|&it| it.to_owned()).collect(),
+                Arc::new(ATNDeserializer::new(None).deserialize(
+                    CSVStrParser::get_serialized_atn().chars(),
+                )),
+                CommonTokenStream::new(ListTokenSource::new(tokens, &tf)),
+            );
+            parser.get_interpreter().set_profile(true);
+            parser.set_track_lookahead(true);
+            parser
+        };
+        let invocations = |info: Option<ParseInfo>| {
+            let info = info.unwrap();
+            info.get_decision_info().iter().map(|it| it.invocations).sum::<usize>()
+        };
+        fn nodes<'a>(
+            tree: &(dyn InterpreterContext<'a, ArenaCommonFactory<'a>> + 'a),
+        ) -> Vec<(Interval, String, String)> {
+            trees::descendants(tree)
+                .map(|it| {
+                    let tokens = if it.get_rule_index() == usize::MAX {
+                        String::new()
+                    } else {
+                        format!("{} {}", it.start(), it.stop())
+                    };
+                    (it.get_source_interval(), it.get_text(), tokens)
+                })
+                .collect()
+        }
+
+        let old_text = "h1,h2\nd1,d2\nd3,d4\nd5,d6\n";
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(old_text), &tf);
+        let old = incremental::lex_all(&mut lexer);
+        let mut parser = csv_parser(incremental::get_tokens(&old));
+        let tree = parser.parse(csvparser::RULE_csvFile).unwrap();
+
+        let edits = [
+            ("h1,h2\nd1,d2\nd3,xyz\nd5,d6\n", TextEdit::new(15, 2, 3)),
+            ("h1,h2\nd1,e2\nd3,xyz\nd5,d6\n", TextEdit::new(9, 2, 2)),
+            // following rows are shifted by two tokens
+            ("h1,h2\nd1,e2,e3\nd3,xyz\nd5,d6\n", TextEdit::new(11, 0, 3)),
+        ];
+        let mut old = old;
+        let mut tree = tree;
+        for (new_text, edit) in edits.iter() {
+            let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(*new_text), &tf);
+            let relexed = incremental::relex(&mut lexer, &old, edit);
+
+            let mut full = csv_parser(incremental::get_tokens(&relexed.tokens));
+            let expected = full.parse(csvparser::RULE_csvFile).unwrap();
+
+            let mut new_parser = csv_parser(incremental::get_tokens(&relexed.tokens));
+            new_parser.set_reusable_subtrees(&*tree, parser.get_rule_lookahead().unwrap(), &relexed);
+            let actual = new_parser.parse(csvparser::RULE_csvFile).unwrap();
+
+            assert_eq!(
+                actual.to_string_tree(&*new_parser),
+                expected.to_string_tree(&*full)
+            );
+            assert_eq!(nodes(&*actual), nodes(&*expected));
+            // only the edited row and the rules around it have been parsed again
+            assert!(
+                invocations(new_parser.get_interpreter().get_parse_info())
+                    < invocations(full.get_interpreter().get_parse_info())
+            );
+
+            old = relexed.tokens;
+            tree = actual;
+            parser = new_parser;
+        }
+
+        // rows with syntax errors are parsed again, so errors are reported again
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new("h1,h2\nd1,d2"), &tf);
+        let old = incremental::lex_all(&mut lexer);
+        let mut parser = csv_parser(incremental::get_tokens(&old));
+        let tree = parser.parse(csvparser::RULE_csvFile).unwrap();
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new("h1,h3\nd1,d2"), &tf);
+        let relexed = incremental::relex(&mut lexer, &old, &TextEdit::new(4, 1, 1));
+        let mut new_parser = csv_parser(incremental::get_tokens(&relexed.tokens));
+        new_parser.set_reusable_subtrees(&*tree, parser.get_rule_lookahead().unwrap(), &relexed);
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        new_parser.remove_error_listeners();
+        new_parser.add_error_listener(Box::new(SourceNameCollector(errors.clone())));
+        let tree = new_parser.parse(csvparser::RULE_csvFile).unwrap();
+        assert_eq!(
+            *errors.borrow(),
+            vec!["<unknown>: line 2:5 mismatched input '<EOF>' expecting {',', '\r', '\n'}"]
+        );
+        assert_eq!(
+            tree.to_string_tree(&*new_parser),
+            "(csvFile (hdr (row (field h1) , (field h3) \\n)) (row (field d1) , (field d2)))"
+        );
+    }
+
+    #[test]
+    fn test_unbuffered_char_stream() {
+        let text = "名前,値\nd1,\"x,\ny\"\nd2,🎉\n";
+        let tf = ArenaCommonFactory::default();
+        let input = UnbufferedCharStream::new(io::Cursor::new(text.as_bytes().to_vec()))
+            .with_source_name("log.csv".to_owned());
+        let mut lexer = CSVLexer::new_with_token_factory(input, &tf);
+        assert_eq!(lexer.get_source_name(), "log.csv");
+        let tokens = std::iter::from_fn(|| Some(lexer.next_token()))
+            .take_while(|it| it.get_token_type() != TOKEN_EOF)
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>();
+
+        // indexes are the same as with code point input
+        let code_points = text.chars().map(|it| it as u32).collect::<Vec<_>>();
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(&code_points[..]), &tf);
+        let expected = std::iter::from_fn(|| Some(lexer.next_token()))
+            .take_while(|it| it.get_token_type() != TOKEN_EOF)
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, expected);
+
+        let input = UnbufferedCharStream::new(text.as_bytes());
+        let lexer = CSVLexer::new_with_token_factory(input, &tf);
+        let mut parser = CSVParser::new(CommonTokenStream::new(lexer));
+        let tree = parser.csvFile().expect("parsed unsuccessfully");
+        assert_eq!(
+            tree.to_string_tree(&*parser),
+            "(csvFile (hdr (row (field 名前) , (field 値) \\n)) \
+             (row (field d1) , (field \"x,\\ny\") \\n) (row (field d2) , (field 🎉) \\n))"
+        );
+    }
+
+    #[test]
+    fn test_utf8_char_stream() {
+        let text = "名前,値\nd1,\"x,\ny\"\n🎉,é\n";
+        let tf = ArenaCommonFactory::default();
+        let mut lexer = CSVLexer::new_with_token_factory(Utf8CharStream::new(text), &tf);
+        let tokens = std::iter::from_fn(|| Some(lexer.next_token()))
+            .take_while(|it| it.get_token_type() != TOKEN_EOF)
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>();
+
+        // indexes are the same as with code point input
+        let code_points = text.chars().map(|it| it as u32).collect::<Vec<_>>();
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(&code_points[..]), &tf);
+        let expected = std::iter::from_fn(|| Some(lexer.next_token()))
+            .take_while(|it| it.get_token_type() != TOKEN_EOF)
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, expected);
+
+        let tf = ArenaUtf8Factory::default();
+        let atn = CSVLexer::<InputStream<&str>>::get_serialized_atn();
+        let mut lexer = LexerInterpreter::new_with_token_factory(
+            "CSVLexer.g4",
+            Box::new(VocabularyImpl::new(
+                _LITERAL_NAMES.iter(),
+                _SYMBOLIC_NAMES.iter(),
+                None,
+            )),
+            ruleNames.iter().map(|&it| it.to_owned()).collect(),
+            modeNames.iter().map(|&it| it.to_owned()).collect(),
+            Arc::new(ATNDeserializer::new(None).deserialize(atn.chars())),
+            Utf8CharStream::new(text),
+            &tf,
+        );
+        let mut count = 0;
+        loop {
+            let token = lexer.next_token();
+            if token.get_token_type() == TOKEN_EOF {
+                assert_eq!((token.byte_start, token.byte_end), (-1, -1));
+                break;
+            }
+            assert_eq!(token.to_string(), tokens[count]);
+            let bytes = token.byte_start as usize..token.byte_end as usize;
+            assert_eq!(&text[bytes], token.get_text());
+            count += 1;
+        }
+        assert_eq!(count, tokens.len());
+    }
+
+    #[test]
+    fn test_position_config() {
+        let text = "a\tb,🎉x\r\nc\rd,é\n\t\"z";
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let tf = ArenaCommonFactory::default();
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new(text), &tf);
+        lexer.remove_error_listeners();
+        lexer.add_error_listener(Box::new(SourceNameCollector(errors.clone())));
+        lexer.set_position_config(PositionConfig {
+            encoding: PositionEncoding::Utf16,
+            tab_width: 4,
+            crlf: true,
+        });
+        let tokens = std::iter::from_fn(|| Some(lexer.next_token()))
+            .map(|it| (it.get_text().to_owned(), it.get_line(), it.get_column()))
+            .take_while(|it| it.0 != "<EOF>")
+            .collect::<Vec<_>>();
+        let expected = [
+            ("a\tb", 1, 0),
+            (",", 1, 5),
+            ("🎉x", 1, 6),
+            ("\r", 1, 9),
+            ("\n", 1, 10),
+            ("c", 2, 0),
+            ("\r", 2, 1),
+            ("d", 3, 0),
+            (",", 3, 1),
+            ("é", 3, 2),
+            ("\n", 3, 3),
+            ("\t", 4, 0),
+        ];
+        let expected = expected
+            .iter()
+            .map(|&(text, line, column)| (text.to_owned(), line, column))
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, expected);
+        assert_eq!(
+            *errors.borrow(),
+            vec!["<unknown>: line 4:4 token recognition error at: '\"z'".to_owned()]
+        );
+
+        let mut lexer = CSVLexer::new_with_token_factory(InputStream::new("🎉\tx\r\n"), &tf);
+        assert_eq!(lexer.get_position_config(), PositionConfig::default());
+        let columns = std::iter::from_fn(|| Some(lexer.next_token()))
+            .take_while(|it| it.get_token_type() != TOKEN_EOF)
+            .map(|it| (it.get_line(), it.get_column()))
+            .collect::<Vec<_>>();
+        assert_eq!(columns, vec![(1, 0), (1, 3), (1, 4)]);
+    }
+
+    #[test]
+    fn test_diagnostic_collector() {
+        let diagnostics = DiagnosticCollector::new();
+        let tf = ArenaCommonFactory::default();
+        let mut lexer =
+            CSVLexer::new_with_token_factory(InputStream::new("h1,h2\nd1 d2\n\"z"), &tf);
+        lexer.remove_error_listeners();
+        lexer.add_error_listener(Box::new(diagnostics.clone()));
+        let mut parser = CSVParser::new(CommonTokenStream::new(lexer));
+        parser.remove_error_listeners();
+        parser.add_error_listener(Box::new(diagnostics.clone()));
+        let _ = parser.csvFile();
+        assert!(diagnostics.has_errors());
+
+        let collected = diagnostics.take_diagnostics();
+        assert!(diagnostics.get_diagnostics().is_empty());
+        let summary = collected
+            .iter()
+            .map(|it| {
+                (
+                    it.severity,
+                    it.token_text.as_deref(),
+                    it.token_type,
+                    it.start,
+                    it.stop,
+                    it.line,
+                    it.column,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (Severity::Error, Some("d2"), TEXT, 9, 10, 2, 3),
+                (
+                    Severity::Error,
+                    Some("\"z"),
+                    TOKEN_INVALID_TYPE,
+                    12,
+                    13,
+                    3,
+                    0
+                ),
+            ]
+        );
+        assert_eq!(collected[0].source_name, "<unknown>");
+        assert_eq!(collected[0].rule_stack, vec!["row", "csvFile"]);
+        assert!(collected[1].rule_stack.is_empty());
+        assert_eq!(
+            collected[0].message,
+            "extraneous input 'd2' expecting {',', '\r', '\n'}"
+        );
+        assert!(collected[0].error.is_none());
+        assert_eq!(collected[1].message, "token recognition error at: '\"z'");
+        assert!(matches!(
+            collected[1].error,
+            Some(ANTLRError::LexerNoAltError { start_index: 12 })
+        ));
+
+        // trailing `12` can be matched either by the loop or by `ATN?`
+        let lexer = ReferenceToATNLexer::new_with_token_factory(
+            InputStream::new_owned("a 34 b 12".to_owned().into_boxed_str()),
+            &FACTORY,
+        );
+        let mut parser = ReferenceToATNParser::new(CommonTokenStream::new(lexer));
+        parser.remove_error_listeners();
+        parser.add_error_listener(Box::new(diagnostics.clone()));
+        parser
+            .get_interpreter()
+            .set_prediction_mode(PredictionMode::LL_EXACT_AMBIG_DETECTION);
+        parser.a().unwrap();
+        assert!(!diagnostics.has_errors());
+        let collected = diagnostics.get_diagnostics();
+        assert_eq!(collected.len(), 1);
+        assert_eq!(collected[0].severity, Severity::Warning);
+        assert_eq!(
+            collected[0].message,
+            "reportAmbiguity d=0 (a): ambigAlts={1, 2}, input='12'"
+        );
+        assert_eq!(
+            (collected[0].start, collected[0].stop, collected[0].column),
+            (7, 8, 7)
+        );
+        assert_eq!(collected[0].rule_stack, vec!["a"]);
+    }
 }